@@ -0,0 +1,4 @@
+ALTER TABLE bike_trip DROP COLUMN version;
+ALTER TABLE bike DROP COLUMN version;
+ALTER TABLE color DROP COLUMN version;
+ALTER TABLE person DROP COLUMN version;
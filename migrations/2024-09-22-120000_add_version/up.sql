@@ -0,0 +1,5 @@
+-- Add optimistic concurrency version columns
+ALTER TABLE person ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE color ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE bike ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE bike_trip ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,4 @@
+ALTER TABLE bike_trip DROP COLUMN deleted_at;
+ALTER TABLE bike DROP COLUMN deleted_at;
+ALTER TABLE color DROP COLUMN deleted_at;
+ALTER TABLE person DROP COLUMN deleted_at;
@@ -0,0 +1,5 @@
+-- Soft delete markers, rows with a deleted_at are hidden by default
+ALTER TABLE person ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE color ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE bike ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE bike_trip ADD COLUMN deleted_at TIMESTAMPTZ;
@@ -0,0 +1,9 @@
+DROP TRIGGER IF EXISTS bike_trip_history ON bike_trip;
+DROP TRIGGER IF EXISTS bike_history ON bike;
+DROP TRIGGER IF EXISTS color_history ON color;
+DROP TRIGGER IF EXISTS person_history ON person;
+DROP FUNCTION IF EXISTS record_history();
+DROP TABLE IF EXISTS bike_trip_history;
+DROP TABLE IF EXISTS bike_history;
+DROP TABLE IF EXISTS color_history;
+DROP TABLE IF EXISTS person_history;
@@ -0,0 +1,73 @@
+-- Create history tables, one row per insert, update or delete
+CREATE TABLE person_history (
+    history_id BIGSERIAL PRIMARY KEY,
+    row_id TEXT NOT NULL,
+    operation TEXT NOT NULL,
+    old_values JSONB,
+    new_values JSONB,
+    actor_id TEXT,
+    changed_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE color_history (
+    history_id BIGSERIAL PRIMARY KEY,
+    row_id TEXT NOT NULL,
+    operation TEXT NOT NULL,
+    old_values JSONB,
+    new_values JSONB,
+    actor_id TEXT,
+    changed_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE bike_history (
+    history_id BIGSERIAL PRIMARY KEY,
+    row_id TEXT NOT NULL,
+    operation TEXT NOT NULL,
+    old_values JSONB,
+    new_values JSONB,
+    actor_id TEXT,
+    changed_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE bike_trip_history (
+    history_id BIGSERIAL PRIMARY KEY,
+    row_id TEXT NOT NULL,
+    operation TEXT NOT NULL,
+    old_values JSONB,
+    new_values JSONB,
+    actor_id TEXT,
+    changed_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX person_history_row_id_idx ON person_history (row_id, changed_at);
+CREATE INDEX color_history_row_id_idx ON color_history (row_id, changed_at);
+CREATE INDEX bike_history_row_id_idx ON bike_history (row_id, changed_at);
+CREATE INDEX bike_trip_history_row_id_idx ON bike_trip_history (row_id, changed_at);
+
+-- Record a change into <table>_history, the actor is taken from the
+-- pedal_pal.actor_id setting which the DAL sets before writing
+CREATE FUNCTION record_history() RETURNS TRIGGER AS $$
+BEGIN
+    EXECUTE format(
+        'INSERT INTO %I (row_id, operation, old_values, new_values, actor_id, changed_at) VALUES ($1, $2, $3, $4, $5, $6)',
+        TG_TABLE_NAME || '_history'
+    )
+    USING
+        CASE WHEN TG_OP = 'DELETE' THEN OLD.id ELSE NEW.id END,
+        TG_OP,
+        CASE WHEN TG_OP = 'INSERT' THEN NULL ELSE to_jsonb(OLD) END,
+        CASE WHEN TG_OP = 'DELETE' THEN NULL ELSE to_jsonb(NEW) END,
+        NULLIF(current_setting('pedal_pal.actor_id', true), ''),
+        clock_timestamp();
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER person_history AFTER INSERT OR UPDATE OR DELETE ON person
+    FOR EACH ROW EXECUTE FUNCTION record_history();
+CREATE TRIGGER color_history AFTER INSERT OR UPDATE OR DELETE ON color
+    FOR EACH ROW EXECUTE FUNCTION record_history();
+CREATE TRIGGER bike_history AFTER INSERT OR UPDATE OR DELETE ON bike
+    FOR EACH ROW EXECUTE FUNCTION record_history();
+CREATE TRIGGER bike_trip_history AFTER INSERT OR UPDATE OR DELETE ON bike_trip
+    FOR EACH ROW EXECUTE FUNCTION record_history();
@@ -0,0 +1,4 @@
+ALTER TABLE bike_trip DROP COLUMN tenant_id;
+ALTER TABLE bike DROP COLUMN tenant_id;
+ALTER TABLE color DROP COLUMN tenant_id;
+ALTER TABLE person DROP COLUMN tenant_id;
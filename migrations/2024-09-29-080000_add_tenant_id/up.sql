@@ -0,0 +1,11 @@
+-- Rows are stamped with the tenant of the connection that inserts them,
+-- rows inserted without a tenant are not scoped to any tenant
+ALTER TABLE person ADD COLUMN tenant_id TEXT DEFAULT NULLIF(current_setting('pedal_pal.tenant_id', true), '');
+ALTER TABLE color ADD COLUMN tenant_id TEXT DEFAULT NULLIF(current_setting('pedal_pal.tenant_id', true), '');
+ALTER TABLE bike ADD COLUMN tenant_id TEXT DEFAULT NULLIF(current_setting('pedal_pal.tenant_id', true), '');
+ALTER TABLE bike_trip ADD COLUMN tenant_id TEXT DEFAULT NULLIF(current_setting('pedal_pal.tenant_id', true), '');
+
+CREATE INDEX person_tenant_id_idx ON person (tenant_id);
+CREATE INDEX color_tenant_id_idx ON color (tenant_id);
+CREATE INDEX bike_tenant_id_idx ON bike (tenant_id);
+CREATE INDEX bike_trip_tenant_id_idx ON bike_trip (tenant_id);
@@ -0,0 +1,15 @@
+-- Condition trees are stored as JSON in the shape serde gives the condition
+-- enums, schema_version records which shape a row was written in
+CREATE TABLE saved_filter (
+    id TEXT PRIMARY KEY,
+    owner_id TEXT REFERENCES person(id),
+    entity TEXT NOT NULL,
+    name TEXT NOT NULL,
+    schema_version INTEGER NOT NULL,
+    conditions JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    tenant_id TEXT DEFAULT NULLIF(current_setting('pedal_pal.tenant_id', true), '')
+);
+
+CREATE INDEX saved_filter_owner_id_idx ON saved_filter (owner_id);
+CREATE INDEX saved_filter_tenant_id_idx ON saved_filter (tenant_id);
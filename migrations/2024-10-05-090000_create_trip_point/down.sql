@@ -0,0 +1,6 @@
+DROP TABLE trip_point;
+
+ALTER TABLE bike_trip DROP COLUMN elevation_gain_meters;
+ALTER TABLE bike_trip DROP COLUMN duration_seconds;
+ALTER TABLE bike_trip DROP COLUMN distance_meters;
+ALTER TABLE bike_trip DROP COLUMN started_at;
@@ -0,0 +1,17 @@
+-- Summary of the track a trip was recorded with, NULL for trips entered by hand
+ALTER TABLE bike_trip ADD COLUMN started_at TIMESTAMPTZ;
+ALTER TABLE bike_trip ADD COLUMN distance_meters DOUBLE PRECISION;
+ALTER TABLE bike_trip ADD COLUMN duration_seconds BIGINT;
+ALTER TABLE bike_trip ADD COLUMN elevation_gain_meters DOUBLE PRECISION;
+
+-- The samples of a recorded track in recording order, points belong to the
+-- tenant of their trip
+CREATE TABLE trip_point (
+    trip_id TEXT NOT NULL REFERENCES bike_trip(id) ON DELETE CASCADE,
+    seq INTEGER NOT NULL,
+    recorded_at TIMESTAMPTZ,
+    latitude DOUBLE PRECISION NOT NULL,
+    longitude DOUBLE PRECISION NOT NULL,
+    elevation DOUBLE PRECISION,
+    PRIMARY KEY (trip_id, seq)
+);
@@ -0,0 +1 @@
+DROP INDEX trip_point_position_idx;
@@ -0,0 +1,3 @@
+-- Geo filters narrow track points to a latitude range before evaluating the
+-- exact area
+CREATE INDEX trip_point_position_idx ON trip_point (latitude, longitude);
@@ -0,0 +1 @@
+ALTER TABLE bike_trip DROP COLUMN moving_seconds;
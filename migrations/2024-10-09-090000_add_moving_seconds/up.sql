@@ -0,0 +1,24 @@
+ALTER TABLE bike_trip ADD COLUMN moving_seconds BIGINT;
+
+-- Backfill the trips imported so far from their points, counting the steps
+-- between consecutive timestamped points made at walking pace or faster. Segment
+-- breaks weren't stored, but a paused recording is too slow a step to count.
+UPDATE bike_trip SET moving_seconds = moving.seconds
+FROM (
+    SELECT trip_id, ROUND(COALESCE(SUM(seconds) FILTER (WHERE seconds > 0 AND meters / seconds >= 0.5), 0))::BIGINT AS seconds
+    FROM (
+        SELECT
+            trip_id,
+            EXTRACT(EPOCH FROM recorded_at - LAG(recorded_at) OVER w) AS seconds,
+            2 * 6371000 * ASIN(SQRT(
+                POWER(SIN(RADIANS(latitude - LAG(latitude) OVER w) / 2), 2)
+                + COS(RADIANS(latitude)) * COS(RADIANS(LAG(latitude) OVER w))
+                * POWER(SIN(RADIANS(longitude - LAG(longitude) OVER w) / 2), 2)
+            )) AS meters
+        FROM trip_point
+        WHERE recorded_at IS NOT NULL
+        WINDOW w AS (PARTITION BY trip_id ORDER BY seq)
+    ) steps
+    GROUP BY trip_id
+) moving
+WHERE bike_trip.id = moving.trip_id;
@@ -0,0 +1,2 @@
+DROP TABLE maintenance_event;
+DROP TABLE component;
@@ -0,0 +1,30 @@
+-- Parts of a bike that wear with use. installed_at_meters is the distance the
+-- bike's trips had covered when the part went on, so its wear is the distance
+-- covered since. removed_at is set when the part is replaced.
+CREATE TABLE component (
+    id TEXT PRIMARY KEY,
+    bike_id TEXT NOT NULL REFERENCES bike(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    installed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    installed_at_meters DOUBLE PRECISION NOT NULL,
+    removed_at TIMESTAMPTZ,
+    tenant_id TEXT DEFAULT NULLIF(current_setting('pedal_pal.tenant_id', true), '')
+);
+
+CREATE INDEX component_bike_id_idx ON component (bike_id);
+CREATE INDEX component_tenant_id_idx ON component (tenant_id);
+
+-- The maintenance log of a bike, component_id is NULL for work on the bike as a whole
+CREATE TABLE maintenance_event (
+    id TEXT PRIMARY KEY,
+    bike_id TEXT NOT NULL REFERENCES bike(id) ON DELETE CASCADE,
+    component_id TEXT REFERENCES component(id) ON DELETE SET NULL,
+    kind TEXT NOT NULL,
+    odometer_meters DOUBLE PRECISION NOT NULL,
+    note TEXT,
+    performed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    tenant_id TEXT DEFAULT NULLIF(current_setting('pedal_pal.tenant_id', true), '')
+);
+
+CREATE INDEX maintenance_event_bike_id_idx ON maintenance_event (bike_id);
+CREATE INDEX maintenance_event_tenant_id_idx ON maintenance_event (tenant_id);
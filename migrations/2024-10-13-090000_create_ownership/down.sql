@@ -0,0 +1,3 @@
+DROP TRIGGER bike_ownership ON bike;
+DROP FUNCTION record_ownership();
+DROP TABLE ownership;
@@ -0,0 +1,54 @@
+-- Who owned a bike when. A range covers valid_from up to but excluding
+-- valid_to, the range of the current owner is open with valid_to NULL.
+CREATE TABLE ownership (
+    id BIGSERIAL PRIMARY KEY,
+    bike_id TEXT NOT NULL REFERENCES bike(id) ON DELETE CASCADE,
+    owner_id TEXT NOT NULL REFERENCES person(id) ON DELETE CASCADE,
+    valid_from TIMESTAMPTZ NOT NULL,
+    valid_to TIMESTAMPTZ,
+    tenant_id TEXT,
+    CHECK (valid_to IS NULL OR valid_to >= valid_from)
+);
+
+CREATE UNIQUE INDEX ownership_open_idx ON ownership (bike_id) WHERE valid_to IS NULL;
+CREATE INDEX ownership_owner_id_idx ON ownership (owner_id, valid_from);
+CREATE INDEX ownership_tenant_id_idx ON ownership (tenant_id);
+
+-- Keep the ranges in step with bike.owner_id, whichever write changes it. The
+-- old range is closed at the moment the new one opens, so they never overlap
+-- or leave a gap. Like the history, this uses the clock rather than the
+-- transaction start, so changes within one transaction get ranges of their own.
+CREATE FUNCTION record_ownership() RETURNS TRIGGER AS $$
+DECLARE
+    changed_at TIMESTAMPTZ := clock_timestamp();
+BEGIN
+    IF TG_OP = 'UPDATE' THEN
+        IF NEW.owner_id IS NOT DISTINCT FROM OLD.owner_id THEN
+            RETURN NULL;
+        END IF;
+        UPDATE ownership SET valid_to = changed_at WHERE bike_id = NEW.id AND valid_to IS NULL;
+    END IF;
+    IF NEW.owner_id IS NOT NULL THEN
+        INSERT INTO ownership (bike_id, owner_id, valid_from, tenant_id)
+        VALUES (NEW.id, NEW.owner_id, changed_at, NEW.tenant_id);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER bike_ownership AFTER INSERT OR UPDATE OF owner_id ON bike
+    FOR EACH ROW EXECUTE FUNCTION record_ownership();
+
+-- Current owners date back to the change that made them the owner, or to the
+-- migration if the history doesn't have it
+INSERT INTO ownership (bike_id, owner_id, valid_from, tenant_id)
+SELECT bike.id, bike.owner_id, COALESCE(since.changed_at, now()), bike.tenant_id
+FROM bike
+LEFT JOIN LATERAL (
+    SELECT max(history.changed_at) AS changed_at
+    FROM bike_history history
+    WHERE history.row_id = bike.id
+      AND history.new_values->>'owner_id' = bike.owner_id
+      AND history.old_values->>'owner_id' IS DISTINCT FROM bike.owner_id
+) since ON true
+WHERE bike.owner_id IS NOT NULL;
@@ -0,0 +1,96 @@
+use axum::extract::{Path, Query};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use crate::api::{blocking, ApiError, ApiJson, ErrorBody, DeleteParams, RequestDal};
+use crate::dal::DataAccessLayer;
+use crate::models::common::{DeleteStrategy, DeleteSummary};
+use crate::models::bike::{Bike, BikeCondition, BikePatch, NewBike};
+
+pub(super) fn routes() -> Router<DataAccessLayer> {
+    Router::new()
+        .route("/", get(list).post(create))
+        .route("/search", post(search))
+        .route("/:id", get(find).put(update).patch(update_partial).delete(delete))
+        .route("/:id/restore", post(restore))
+}
+
+#[utoipa::path(get, path = "/bikes", operation_id = "list_bikes", tag = "bikes", responses(
+    (status = 200, description = "All live bikes", body = Vec<Bike>),
+))]
+async fn list(RequestDal(dal): RequestDal) -> Result<Json<Vec<Bike>>, ApiError> {
+    blocking(move || dal.bike().find_all()).await.map(Json)
+}
+
+#[utoipa::path(post, path = "/bikes", operation_id = "create_bike", tag = "bikes", request_body = NewBike, responses(
+    (status = 201, description = "The created bike", body = Bike),
+    (status = 409, description = "A bike with the ID exists", body = ErrorBody),
+    (status = 422, description = "The body is invalid or references a missing row", body = ErrorBody),
+))]
+async fn create(RequestDal(dal): RequestDal, ApiJson(new_bike): ApiJson<NewBike>) -> Result<(StatusCode, Json<Bike>), ApiError> {
+    let bike = blocking(move || dal.bike().create(&new_bike)).await?;
+    Ok((StatusCode::CREATED, Json(bike)))
+}
+
+#[utoipa::path(post, path = "/bikes/search", operation_id = "search_bikes", tag = "bikes", request_body(
+    content = Vec<BikeCondition>, description = "Conditions the bikes must all match",
+), responses(
+    (status = 200, description = "The matching bikes", body = Vec<Bike>),
+    (status = 403, description = "A condition filters on a field the caller may not filter on", body = ErrorBody),
+    (status = 422, description = "The conditions are malformed", body = ErrorBody),
+))]
+async fn search(RequestDal(dal): RequestDal, ApiJson(conditions): ApiJson<Vec<BikeCondition>>) -> Result<Json<Vec<Bike>>, ApiError> {
+    blocking(move || dal.bike().find_with_filters(conditions)).await.map(Json)
+}
+
+#[utoipa::path(get, path = "/bikes/{id}", operation_id = "find_bike", tag = "bikes", params(("id" = String, Path, description = "ID of the bike")), responses(
+    (status = 200, description = "The bike", body = Bike),
+    (status = 404, description = "No live bike with the ID", body = ErrorBody),
+))]
+async fn find(RequestDal(dal): RequestDal, Path(id): Path<String>) -> Result<Json<Bike>, ApiError> {
+    blocking(move || dal.bike().find_by_id(&id)).await.map(Json)
+}
+
+#[utoipa::path(put, path = "/bikes/{id}", operation_id = "update_bike", tag = "bikes", params(("id" = String, Path, description = "ID of the bike")), request_body = Bike, responses(
+    (status = 200, description = "The updated bike", body = Bike),
+    (status = 403, description = "The policy doesn't allow updating the bike", body = ErrorBody),
+    (status = 404, description = "No bike with the ID", body = ErrorBody),
+    (status = 409, description = "The version is stale, `current` holds the current bike", body = ErrorBody),
+))]
+async fn update(RequestDal(dal): RequestDal, Path(id): Path<String>, ApiJson(bike): ApiJson<Bike>) -> Result<Json<Bike>, ApiError> {
+    blocking(move || dal.bike().update(&id, &bike)).await.map(Json)
+}
+
+#[utoipa::path(patch, path = "/bikes/{id}", operation_id = "patch_bike", tag = "bikes", params(("id" = String, Path, description = "ID of the bike")), request_body = BikePatch, responses(
+    (status = 200, description = "The updated bike", body = Bike),
+    (status = 403, description = "The policy doesn't allow updating the bike", body = ErrorBody),
+    (status = 404, description = "No bike with the ID", body = ErrorBody),
+))]
+async fn update_partial(RequestDal(dal): RequestDal, Path(id): Path<String>, ApiJson(patch): ApiJson<BikePatch>) -> Result<Json<Bike>, ApiError> {
+    blocking(move || dal.bike().update_partial(&id, &patch)).await.map(Json)
+}
+
+#[utoipa::path(delete, path = "/bikes/{id}", operation_id = "delete_bike", tag = "bikes", params(
+    ("id" = String, Path, description = "ID of the bike"),
+    ("strategy" = Option<DeleteStrategy>, Query, description = "What happens to rows referencing the bike, `restrict` by default"),
+), responses(
+    (status = 200, description = "The bike was deleted", body = DeleteSummary),
+    (status = 403, description = "The policy doesn't allow deleting the bike", body = ErrorBody),
+    (status = 404, description = "No live bike with the ID", body = ErrorBody),
+    (status = 409, description = "Live rows still reference the bike", body = ErrorBody),
+))]
+async fn delete(RequestDal(dal): RequestDal, Path(id): Path<String>, Query(params): Query<DeleteParams>) -> Result<Json<DeleteSummary>, ApiError> {
+    let summary = blocking(move || dal.bike().delete_with(&id, params.strategy)).await?;
+    if summary.deleted == 0 {
+        return Err(ApiError::not_found());
+    }
+    Ok(Json(summary))
+}
+
+#[utoipa::path(post, path = "/bikes/{id}/restore", operation_id = "restore_bike", tag = "bikes", params(("id" = String, Path, description = "ID of the bike")), responses(
+    (status = 200, description = "The restored bike", body = Bike),
+    (status = 404, description = "No deleted bike with the ID", body = ErrorBody),
+))]
+async fn restore(RequestDal(dal): RequestDal, Path(id): Path<String>) -> Result<Json<Bike>, ApiError> {
+    blocking(move || dal.bike().restore(&id)).await.map(Json)
+}
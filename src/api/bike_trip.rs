@@ -0,0 +1,90 @@
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use crate::api::{blocking, ApiError, ApiJson, ErrorBody, RequestDal};
+use crate::dal::DataAccessLayer;
+use crate::models::bike_trip::{BikeTrip, BikeTripCondition, BikeTripPatch, NewBikeTrip};
+use crate::models::common::DeleteSummary;
+
+pub(super) fn routes() -> Router<DataAccessLayer> {
+    Router::new()
+        .route("/", get(list).post(create))
+        .route("/search", post(search))
+        .route("/:id", get(find).put(update).patch(update_partial).delete(delete))
+        .route("/:id/restore", post(restore))
+}
+
+#[utoipa::path(get, path = "/bike_trips", operation_id = "list_bike_trips", tag = "bike_trips", responses(
+    (status = 200, description = "All live bike trips", body = Vec<BikeTrip>),
+))]
+async fn list(RequestDal(dal): RequestDal) -> Result<Json<Vec<BikeTrip>>, ApiError> {
+    blocking(move || dal.bike_trip().find_all()).await.map(Json)
+}
+
+#[utoipa::path(post, path = "/bike_trips", operation_id = "create_bike_trip", tag = "bike_trips", request_body = NewBikeTrip, responses(
+    (status = 201, description = "The created bike trip", body = BikeTrip),
+    (status = 409, description = "A bike trip with the ID exists", body = ErrorBody),
+    (status = 422, description = "The body is invalid or references a missing row", body = ErrorBody),
+))]
+async fn create(RequestDal(dal): RequestDal, ApiJson(new_trip): ApiJson<NewBikeTrip>) -> Result<(StatusCode, Json<BikeTrip>), ApiError> {
+    let trip = blocking(move || dal.bike_trip().create(&new_trip)).await?;
+    Ok((StatusCode::CREATED, Json(trip)))
+}
+
+#[utoipa::path(post, path = "/bike_trips/search", operation_id = "search_bike_trips", tag = "bike_trips", request_body(
+    content = Vec<BikeTripCondition>, description = "Conditions the bike trips must all match",
+), responses(
+    (status = 200, description = "The matching bike trips", body = Vec<BikeTrip>),
+    (status = 403, description = "A condition filters on a field the caller may not filter on", body = ErrorBody),
+    (status = 422, description = "The conditions are malformed", body = ErrorBody),
+))]
+async fn search(RequestDal(dal): RequestDal, ApiJson(conditions): ApiJson<Vec<BikeTripCondition>>) -> Result<Json<Vec<BikeTrip>>, ApiError> {
+    blocking(move || dal.bike_trip().find_with_filters(conditions)).await.map(Json)
+}
+
+#[utoipa::path(get, path = "/bike_trips/{id}", operation_id = "find_bike_trip", tag = "bike_trips", params(("id" = String, Path, description = "ID of the bike trip")), responses(
+    (status = 200, description = "The bike trip", body = BikeTrip),
+    (status = 404, description = "No live bike trip with the ID", body = ErrorBody),
+))]
+async fn find(RequestDal(dal): RequestDal, Path(id): Path<String>) -> Result<Json<BikeTrip>, ApiError> {
+    blocking(move || dal.bike_trip().find_by_id(&id)).await.map(Json)
+}
+
+#[utoipa::path(put, path = "/bike_trips/{id}", operation_id = "update_bike_trip", tag = "bike_trips", params(("id" = String, Path, description = "ID of the bike trip")), request_body = BikeTrip, responses(
+    (status = 200, description = "The updated bike trip", body = BikeTrip),
+    (status = 404, description = "No bike trip with the ID", body = ErrorBody),
+    (status = 409, description = "The version is stale, `current` holds the current bike trip", body = ErrorBody),
+))]
+async fn update(RequestDal(dal): RequestDal, Path(id): Path<String>, ApiJson(trip): ApiJson<BikeTrip>) -> Result<Json<BikeTrip>, ApiError> {
+    blocking(move || dal.bike_trip().update(&id, &trip)).await.map(Json)
+}
+
+#[utoipa::path(patch, path = "/bike_trips/{id}", operation_id = "patch_bike_trip", tag = "bike_trips", params(("id" = String, Path, description = "ID of the bike trip")), request_body = BikeTripPatch, responses(
+    (status = 200, description = "The updated bike trip", body = BikeTrip),
+    (status = 404, description = "No bike trip with the ID", body = ErrorBody),
+))]
+async fn update_partial(RequestDal(dal): RequestDal, Path(id): Path<String>, ApiJson(patch): ApiJson<BikeTripPatch>) -> Result<Json<BikeTrip>, ApiError> {
+    blocking(move || dal.bike_trip().update_partial(&id, &patch)).await.map(Json)
+}
+
+// Trips have nothing referencing them, so there is no delete strategy to pick
+#[utoipa::path(delete, path = "/bike_trips/{id}", operation_id = "delete_bike_trip", tag = "bike_trips", params(("id" = String, Path, description = "ID of the bike trip")), responses(
+    (status = 200, description = "The bike trip was deleted", body = DeleteSummary),
+    (status = 404, description = "No live bike trip with the ID", body = ErrorBody),
+))]
+async fn delete(RequestDal(dal): RequestDal, Path(id): Path<String>) -> Result<Json<DeleteSummary>, ApiError> {
+    let deleted = blocking(move || dal.bike_trip().delete(&id)).await?;
+    if deleted == 0 {
+        return Err(ApiError::not_found());
+    }
+    Ok(Json(DeleteSummary { deleted, dependents: 0 }))
+}
+
+#[utoipa::path(post, path = "/bike_trips/{id}/restore", operation_id = "restore_bike_trip", tag = "bike_trips", params(("id" = String, Path, description = "ID of the bike trip")), responses(
+    (status = 200, description = "The restored bike trip", body = BikeTrip),
+    (status = 404, description = "No deleted bike trip with the ID", body = ErrorBody),
+))]
+async fn restore(RequestDal(dal): RequestDal, Path(id): Path<String>) -> Result<Json<BikeTrip>, ApiError> {
+    blocking(move || dal.bike_trip().restore(&id)).await.map(Json)
+}
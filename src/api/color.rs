@@ -0,0 +1,81 @@
+use axum::extract::{Path, Query};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use crate::api::{blocking, ApiError, ApiJson, ErrorBody, DeleteParams, RequestDal};
+use crate::dal::DataAccessLayer;
+use crate::models::color::{Color, ColorPatch, NewColor};
+use crate::models::common::{DeleteStrategy, DeleteSummary};
+
+pub(super) fn routes() -> Router<DataAccessLayer> {
+    Router::new()
+        .route("/", get(list).post(create))
+        .route("/:id", get(find).put(update).patch(update_partial).delete(delete))
+        .route("/:id/restore", post(restore))
+}
+
+#[utoipa::path(get, path = "/colors", operation_id = "list_colors", tag = "colors", responses(
+    (status = 200, description = "All live colors", body = Vec<Color>),
+))]
+async fn list(RequestDal(dal): RequestDal) -> Result<Json<Vec<Color>>, ApiError> {
+    blocking(move || dal.color().find_all()).await.map(Json)
+}
+
+#[utoipa::path(post, path = "/colors", operation_id = "create_color", tag = "colors", request_body = NewColor, responses(
+    (status = 201, description = "The created color", body = Color),
+    (status = 409, description = "A color with the ID exists", body = ErrorBody),
+    (status = 422, description = "The body is invalid or references a missing row", body = ErrorBody),
+))]
+async fn create(RequestDal(dal): RequestDal, ApiJson(new_color): ApiJson<NewColor>) -> Result<(StatusCode, Json<Color>), ApiError> {
+    let color = blocking(move || dal.color().create(&new_color)).await?;
+    Ok((StatusCode::CREATED, Json(color)))
+}
+
+#[utoipa::path(get, path = "/colors/{id}", operation_id = "find_color", tag = "colors", params(("id" = String, Path, description = "ID of the color")), responses(
+    (status = 200, description = "The color", body = Color),
+    (status = 404, description = "No live color with the ID", body = ErrorBody),
+))]
+async fn find(RequestDal(dal): RequestDal, Path(id): Path<String>) -> Result<Json<Color>, ApiError> {
+    blocking(move || dal.color().find_by_id(&id)).await.map(Json)
+}
+
+#[utoipa::path(put, path = "/colors/{id}", operation_id = "update_color", tag = "colors", params(("id" = String, Path, description = "ID of the color")), request_body = Color, responses(
+    (status = 200, description = "The updated color", body = Color),
+    (status = 404, description = "No color with the ID", body = ErrorBody),
+    (status = 409, description = "The version is stale, `current` holds the current color", body = ErrorBody),
+))]
+async fn update(RequestDal(dal): RequestDal, Path(id): Path<String>, ApiJson(color): ApiJson<Color>) -> Result<Json<Color>, ApiError> {
+    blocking(move || dal.color().update(&id, &color)).await.map(Json)
+}
+
+#[utoipa::path(patch, path = "/colors/{id}", operation_id = "patch_color", tag = "colors", params(("id" = String, Path, description = "ID of the color")), request_body = ColorPatch, responses(
+    (status = 200, description = "The updated color", body = Color),
+    (status = 404, description = "No color with the ID", body = ErrorBody),
+))]
+async fn update_partial(RequestDal(dal): RequestDal, Path(id): Path<String>, ApiJson(patch): ApiJson<ColorPatch>) -> Result<Json<Color>, ApiError> {
+    blocking(move || dal.color().update_partial(&id, &patch)).await.map(Json)
+}
+
+#[utoipa::path(delete, path = "/colors/{id}", operation_id = "delete_color", tag = "colors", params(
+    ("id" = String, Path, description = "ID of the color"),
+    ("strategy" = Option<DeleteStrategy>, Query, description = "What happens to rows referencing the color, `restrict` by default"),
+), responses(
+    (status = 200, description = "The color was deleted", body = DeleteSummary),
+    (status = 404, description = "No live color with the ID", body = ErrorBody),
+    (status = 409, description = "Live rows still reference the color", body = ErrorBody),
+))]
+async fn delete(RequestDal(dal): RequestDal, Path(id): Path<String>, Query(params): Query<DeleteParams>) -> Result<Json<DeleteSummary>, ApiError> {
+    let summary = blocking(move || dal.color().delete_with(&id, params.strategy)).await?;
+    if summary.deleted == 0 {
+        return Err(ApiError::not_found());
+    }
+    Ok(Json(summary))
+}
+
+#[utoipa::path(post, path = "/colors/{id}/restore", operation_id = "restore_color", tag = "colors", params(("id" = String, Path, description = "ID of the color")), responses(
+    (status = 200, description = "The restored color", body = Color),
+    (status = 404, description = "No deleted color with the ID", body = ErrorBody),
+))]
+async fn restore(RequestDal(dal): RequestDal, Path(id): Path<String>) -> Result<Json<Color>, ApiError> {
+    blocking(move || dal.color().restore(&id)).await.map(Json)
+}
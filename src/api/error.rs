@@ -0,0 +1,102 @@
+use std::fmt;
+use axum::extract::rejection::JsonRejection;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use diesel::result::{DatabaseErrorKind, Error};
+use serde::Serialize;
+use serde_json::Value;
+use utoipa::ToSchema;
+use crate::dal::{DeleteError, PolicyDenied, UpdateError};
+use crate::models::common::FieldDenied;
+use crate::models::saved_filter::SavedFilterError;
+
+/// An error response, rendered as `{"error": message}` plus the current row
+/// for version conflicts.
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+    current: Option<Value>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        ApiError { status, message: message.into(), current: None }
+    }
+
+    pub fn not_found() -> Self {
+        ApiError::new(StatusCode::NOT_FOUND, "not found")
+    }
+
+    /// A 500 whose details only go to the server's log, so they can't leak to callers
+    pub fn internal(error: impl fmt::Display) -> Self {
+        eprintln!("Internal server error: {}", error);
+        ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "internal server error")
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+}
+
+/// The body of error responses.
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    /// What went wrong.
+    error: String,
+    /// The current row, for version conflicts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    current: Option<Value>,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody { error: self.message, current: self.current };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        let status = match &error {
+            Error::NotFound => StatusCode::NOT_FOUND,
+            Error::QueryBuilderError(e) if e.is::<FieldDenied>() || e.is::<PolicyDenied>() => StatusCode::FORBIDDEN,
+            Error::QueryBuilderError(_) => StatusCode::BAD_REQUEST,
+            Error::DeserializationError(e) if e.is::<SavedFilterError>() => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => StatusCode::CONFLICT,
+            Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => StatusCode::UNPROCESSABLE_ENTITY,
+            _ => return ApiError::internal(error),
+        };
+        ApiError::new(status, error.to_string())
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::new(rejection.status(), rejection.body_text())
+    }
+}
+
+impl<T: Serialize> From<UpdateError<T>> for ApiError {
+    fn from(error: UpdateError<T>) -> Self {
+        match error {
+            UpdateError::Conflict(current) => ApiError {
+                current: serde_json::to_value(&current).ok(),
+                ..ApiError::new(StatusCode::CONFLICT, "row was modified concurrently")
+            },
+            UpdateError::Denied => ApiError::new(StatusCode::FORBIDDEN, error.to_string()),
+            UpdateError::Database(error) => error.into(),
+        }
+    }
+}
+
+impl From<DeleteError> for ApiError {
+    fn from(error: DeleteError) -> Self {
+        match error {
+            DeleteError::Restricted(_) => ApiError::new(StatusCode::CONFLICT, error.to_string()),
+            DeleteError::Denied => ApiError::new(StatusCode::FORBIDDEN, error.to_string()),
+            DeleteError::Database(error) => error.into(),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use axum::extract::{FromRequest, FromRequestParts, Request};
+use axum::extract::rejection::JsonRejection;
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use clap::ValueEnum;
+use serde::Deserialize;
+use crate::dal::DataAccessLayer;
+use crate::graphql::{self, PedalPalSchema};
+use crate::models::common::DeleteStrategy;
+
+mod bike;
+mod bike_trip;
+mod color;
+mod error;
+mod openapi;
+mod person;
+
+pub use error::{ApiError, ErrorBody};
+pub use openapi::ApiDoc;
+
+/// Header naming the actor requests are made for
+pub const ACTOR_HEADER: &str = "x-actor-id";
+
+/// Header naming the tenant requests are scoped to
+pub const TENANT_HEADER: &str = "x-tenant-id";
+
+/// Where the tenant and actor of a request come from
+///
+/// The server doesn't authenticate callers, so the identity headers can only be
+/// believed when something in front of it does. There is no default, a server
+/// has to be started in one of the modes explicitly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Tenancy {
+    /// Serve the tenant and actor the server's data access layer is scoped to.
+    /// Requests carrying identity headers are rejected, so callers can't pick
+    /// another tenant or pose as another actor.
+    Single,
+    /// A proxy in front of the server authenticates callers and sets the
+    /// identity headers, replacing any the caller sent. Requests without a
+    /// tenant are rejected.
+    TrustedProxy,
+}
+
+/// The HTTP API over `dal`
+///
+/// Every request works on a copy of `dal`, scoped by the identity headers when
+/// `tenancy` trusts them, so policies and field access configured on `dal`
+/// apply to all of them.
+pub fn router(dal: DataAccessLayer, tenancy: Tenancy) -> Router {
+    Router::new()
+        .nest("/persons", person::routes())
+        .nest("/bikes", bike::routes())
+        .nest("/colors", color::routes())
+        .nest("/bike_trips", bike_trip::routes())
+        .route("/openapi.json", get(openapi::openapi_json))
+        .route("/graphql", post(graphql_query).layer(Extension(graphql::schema())))
+        .layer(Extension(tenancy))
+        .with_state(dal)
+}
+
+/// The data access layer of the server, scoped to the request's identity
+pub struct RequestDal(pub DataAccessLayer);
+
+#[axum::async_trait]
+impl FromRequestParts<DataAccessLayer> for RequestDal {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, dal: &DataAccessLayer) -> Result<Self, Self::Rejection> {
+        let header = |name: &str| {
+            parts
+                .headers
+                .get(name)
+                .map(|value| {
+                    value
+                        .to_str()
+                        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, format!("invalid {} header", name)))
+                })
+                .transpose()
+        };
+        let tenant = header(TENANT_HEADER)?;
+        let actor = header(ACTOR_HEADER)?;
+
+        // A router without the extension falls back to the mode that trusts nothing
+        match parts.extensions.get::<Tenancy>().copied().unwrap_or(Tenancy::Single) {
+            Tenancy::Single => match (tenant, actor) {
+                (None, None) => Ok(RequestDal(dal.clone())),
+                _ => Err(ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("the {} and {} headers are only accepted behind a trusted proxy", TENANT_HEADER, ACTOR_HEADER),
+                )),
+            },
+            Tenancy::TrustedProxy => {
+                let tenant_id = tenant.ok_or_else(|| ApiError::new(StatusCode::UNAUTHORIZED, format!("missing {} header", TENANT_HEADER)))?;
+                let dal = dal.for_tenant(tenant_id);
+                Ok(RequestDal(match actor {
+                    Some(actor_id) => dal.with_actor(actor_id),
+                    None => dal,
+                }))
+            }
+        }
+    }
+}
+
+/// A JSON request body, rejected with an `ErrorBody` like every other API error
+pub struct ApiJson<T>(pub T);
+
+#[axum::async_trait]
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(request, state).await?;
+        Ok(ApiJson(value))
+    }
+}
+
+async fn graphql_query(
+    RequestDal(dal): RequestDal,
+    Extension(schema): Extension<PedalPalSchema>,
+    ApiJson(request): ApiJson<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema.execute(graphql::with_dal(request, dal)).await)
+}
+
+/// Query parameters of the delete endpoints, e.g. `?strategy=cascade`
+#[derive(Debug, Default, Deserialize)]
+struct DeleteParams {
+    #[serde(default)]
+    strategy: DeleteStrategy,
+}
+
+/// Runs a blocking DAL call off the async runtime
+async fn blocking<T, E, F>(f: F) -> Result<T, ApiError>
+where
+    F: FnOnce() -> Result<T, E> + Send + 'static,
+    T: Send + 'static,
+    E: Into<ApiError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(ApiError::internal)?
+        .map_err(Into::into)
+}
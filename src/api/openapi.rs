@@ -0,0 +1,51 @@
+use axum::Json;
+use utoipa::openapi::OpenApi as OpenApiDocument;
+use utoipa::OpenApi;
+use crate::api::{bike, bike_trip, color, person, ErrorBody};
+use crate::models::bike::{Bike, BikeCondition, BikePatch, NewBike};
+use crate::models::bike_trip::{BikeTrip, BikeTripCondition, BikeTripPatch, NewBikeTrip};
+use crate::models::color::{Color, ColorPatch, NewColor};
+use crate::models::common::{DeleteStrategy, DeleteSummary, StringFilter};
+use crate::models::person::{NewPerson, Person, PersonCondition, PersonPatch};
+
+/// The OpenAPI document of the HTTP API
+///
+/// The condition schemas list the operators each field can be filtered with,
+/// e.g. `BikeCondition` filters `color` with a `StringFilter`.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "pedal_pal",
+        description = "Persons, bikes, colors and bike trips. Behind a trusted proxy, requests are \
+                       scoped with the `x-tenant-id` header and attributed with the `x-actor-id` \
+                       header. Otherwise the server serves a single tenant and rejects both headers.",
+    ),
+    paths(
+        person::list, person::create, person::search, person::find,
+        person::update, person::update_partial, person::delete, person::restore,
+        bike::list, bike::create, bike::search, bike::find,
+        bike::update, bike::update_partial, bike::delete, bike::restore,
+        color::list, color::create, color::find,
+        color::update, color::update_partial, color::delete, color::restore,
+        bike_trip::list, bike_trip::create, bike_trip::search, bike_trip::find,
+        bike_trip::update, bike_trip::update_partial, bike_trip::delete, bike_trip::restore,
+    ),
+    components(schemas(
+        Person, NewPerson, PersonPatch, PersonCondition,
+        Bike, NewBike, BikePatch, BikeCondition,
+        Color, NewColor, ColorPatch,
+        BikeTrip, NewBikeTrip, BikeTripPatch, BikeTripCondition,
+        StringFilter, DeleteStrategy, DeleteSummary, ErrorBody,
+    )),
+    tags(
+        (name = "persons", description = "Persons owning bikes"),
+        (name = "bikes", description = "Bikes and their owners and colors"),
+        (name = "colors", description = "Colors of bikes"),
+        (name = "bike_trips", description = "Trips taken with bikes"),
+    ),
+)]
+pub struct ApiDoc;
+
+pub(super) async fn openapi_json() -> Json<OpenApiDocument> {
+    Json(ApiDoc::openapi())
+}
@@ -0,0 +1,96 @@
+use axum::extract::{Path, Query};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use crate::api::{blocking, ApiError, ApiJson, ErrorBody, DeleteParams, RequestDal};
+use crate::dal::DataAccessLayer;
+use crate::models::common::{DeleteStrategy, DeleteSummary};
+use crate::models::person::{NewPerson, Person, PersonCondition, PersonPatch};
+
+pub(super) fn routes() -> Router<DataAccessLayer> {
+    Router::new()
+        .route("/", get(list).post(create))
+        .route("/search", post(search))
+        .route("/:id", get(find).put(update).patch(update_partial).delete(delete))
+        .route("/:id/restore", post(restore))
+}
+
+#[utoipa::path(get, path = "/persons", operation_id = "list_persons", tag = "persons", responses(
+    (status = 200, description = "All live persons", body = Vec<Person>),
+))]
+async fn list(RequestDal(dal): RequestDal) -> Result<Json<Vec<Person>>, ApiError> {
+    blocking(move || dal.person().find_all()).await.map(Json)
+}
+
+#[utoipa::path(post, path = "/persons", operation_id = "create_person", tag = "persons", request_body = NewPerson, responses(
+    (status = 201, description = "The created person", body = Person),
+    (status = 409, description = "A person with the ID exists", body = ErrorBody),
+    (status = 422, description = "The body is invalid or references a missing row", body = ErrorBody),
+))]
+async fn create(RequestDal(dal): RequestDal, ApiJson(new_person): ApiJson<NewPerson>) -> Result<(StatusCode, Json<Person>), ApiError> {
+    let person = blocking(move || dal.person().create(&new_person)).await?;
+    Ok((StatusCode::CREATED, Json(person)))
+}
+
+#[utoipa::path(post, path = "/persons/search", operation_id = "search_persons", tag = "persons", request_body(
+    content = Vec<PersonCondition>, description = "Conditions the persons must all match",
+), responses(
+    (status = 200, description = "The matching persons", body = Vec<Person>),
+    (status = 403, description = "A condition filters on a field the caller may not filter on", body = ErrorBody),
+    (status = 422, description = "The conditions are malformed", body = ErrorBody),
+))]
+async fn search(RequestDal(dal): RequestDal, ApiJson(conditions): ApiJson<Vec<PersonCondition>>) -> Result<Json<Vec<Person>>, ApiError> {
+    blocking(move || dal.person().find_with_filters(conditions)).await.map(Json)
+}
+
+#[utoipa::path(get, path = "/persons/{id}", operation_id = "find_person", tag = "persons", params(("id" = String, Path, description = "ID of the person")), responses(
+    (status = 200, description = "The person", body = Person),
+    (status = 404, description = "No live person with the ID", body = ErrorBody),
+))]
+async fn find(RequestDal(dal): RequestDal, Path(id): Path<String>) -> Result<Json<Person>, ApiError> {
+    blocking(move || dal.person().find_by_id(&id)).await.map(Json)
+}
+
+#[utoipa::path(put, path = "/persons/{id}", operation_id = "update_person", tag = "persons", params(("id" = String, Path, description = "ID of the person")), request_body = Person, responses(
+    (status = 200, description = "The updated person", body = Person),
+    (status = 403, description = "The policy doesn't allow updating the person", body = ErrorBody),
+    (status = 404, description = "No person with the ID", body = ErrorBody),
+    (status = 409, description = "The version is stale, `current` holds the current person", body = ErrorBody),
+))]
+async fn update(RequestDal(dal): RequestDal, Path(id): Path<String>, ApiJson(person): ApiJson<Person>) -> Result<Json<Person>, ApiError> {
+    blocking(move || dal.person().update(&id, &person)).await.map(Json)
+}
+
+#[utoipa::path(patch, path = "/persons/{id}", operation_id = "patch_person", tag = "persons", params(("id" = String, Path, description = "ID of the person")), request_body = PersonPatch, responses(
+    (status = 200, description = "The updated person", body = Person),
+    (status = 403, description = "The policy doesn't allow updating the person", body = ErrorBody),
+    (status = 404, description = "No person with the ID", body = ErrorBody),
+))]
+async fn update_partial(RequestDal(dal): RequestDal, Path(id): Path<String>, ApiJson(patch): ApiJson<PersonPatch>) -> Result<Json<Person>, ApiError> {
+    blocking(move || dal.person().update_partial(&id, &patch)).await.map(Json)
+}
+
+#[utoipa::path(delete, path = "/persons/{id}", operation_id = "delete_person", tag = "persons", params(
+    ("id" = String, Path, description = "ID of the person"),
+    ("strategy" = Option<DeleteStrategy>, Query, description = "What happens to rows referencing the person, `restrict` by default"),
+), responses(
+    (status = 200, description = "The person was deleted", body = DeleteSummary),
+    (status = 403, description = "The policy doesn't allow deleting the person", body = ErrorBody),
+    (status = 404, description = "No live person with the ID", body = ErrorBody),
+    (status = 409, description = "Live rows still reference the person", body = ErrorBody),
+))]
+async fn delete(RequestDal(dal): RequestDal, Path(id): Path<String>, Query(params): Query<DeleteParams>) -> Result<Json<DeleteSummary>, ApiError> {
+    let summary = blocking(move || dal.person().delete_with(&id, params.strategy)).await?;
+    if summary.deleted == 0 {
+        return Err(ApiError::not_found());
+    }
+    Ok(Json(summary))
+}
+
+#[utoipa::path(post, path = "/persons/{id}/restore", operation_id = "restore_person", tag = "persons", params(("id" = String, Path, description = "ID of the person")), responses(
+    (status = 200, description = "The restored person", body = Person),
+    (status = 404, description = "No deleted person with the ID", body = ErrorBody),
+))]
+async fn restore(RequestDal(dal): RequestDal, Path(id): Path<String>) -> Result<Json<Person>, ApiError> {
+    blocking(move || dal.person().restore(&id)).await.map(Json)
+}
@@ -0,0 +1,411 @@
+use std::fmt;
+use crate::models::bike::BikeCondition;
+use crate::models::bike_trip::BikeTripCondition;
+use crate::models::common::StringFilter;
+use crate::models::person::PersonCondition;
+
+/// A condition type filter expressions can be parsed into
+pub trait FilterField: Sized {
+    /// The leaf condition filtering `field`, `None` if there is no such field
+    fn field(field: &str, filter: StringFilter) -> Option<Self>;
+    /// The condition `function(argument)` or `function(argument, bike)` stands for
+    fn call(function: &str, argument: &str, bike: Option<BikeCondition>) -> Result<Self, String> {
+        let _ = (argument, bike);
+        Err(format!("unknown function `{}`", function))
+    }
+    fn and(conditions: Vec<Self>) -> Self;
+    fn or(conditions: Vec<Self>) -> Self;
+}
+
+impl FilterField for BikeCondition {
+    fn field(field: &str, filter: StringFilter) -> Option<Self> {
+        match field {
+            "id" => Some(BikeCondition::id(filter)),
+            "name" => Some(BikeCondition::name(filter)),
+            "color" => Some(BikeCondition::color(filter)),
+            "owner_id" => Some(BikeCondition::owner_id(filter)),
+            _ => None,
+        }
+    }
+
+    fn call(function: &str, argument: &str, bike: Option<BikeCondition>) -> Result<Self, String> {
+        match function {
+            "needs_service" if bike.is_some() => Err("`needs_service` takes a single argument".to_string()),
+            "needs_service" => argument
+                .parse()
+                .map(BikeCondition::needs_service)
+                .map_err(|_| "`needs_service` takes a distance in meters".to_string()),
+            _ => Err(format!("unknown function `{}`", function)),
+        }
+    }
+
+    fn and(conditions: Vec<Self>) -> Self {
+        BikeCondition::And(conditions)
+    }
+
+    fn or(conditions: Vec<Self>) -> Self {
+        BikeCondition::Or(conditions)
+    }
+}
+
+impl FilterField for PersonCondition {
+    fn field(field: &str, filter: StringFilter) -> Option<Self> {
+        match field.split_once('.') {
+            Some(("bike", field)) => Some(PersonCondition::bike(vec![BikeCondition::field(field, filter)?])),
+            Some(_) => None,
+            None => match field {
+                "id" => Some(PersonCondition::id(filter)),
+                "name" => Some(PersonCondition::name(filter)),
+                "email" => Some(PersonCondition::email(filter)),
+                _ => None,
+            },
+        }
+    }
+
+    fn call(function: &str, argument: &str, bike: Option<BikeCondition>) -> Result<Self, String> {
+        match function.split_once('.') {
+            Some(("bike", function)) => Ok(PersonCondition::bike(vec![BikeCondition::call(function, argument, bike)?])),
+            Some(_) => Err(format!("unknown function `{}`", function)),
+            None => match function {
+                "owned_bike_at" => {
+                    let at = argument
+                        .parse()
+                        .map_err(|_| "`owned_bike_at` takes an RFC 3339 timestamp".to_string())?;
+                    Ok(PersonCondition::owned_bike_at(at, bike.into_iter().collect()))
+                }
+                _ => Err(format!("unknown function `{}`", function)),
+            },
+        }
+    }
+
+    fn and(conditions: Vec<Self>) -> Self {
+        PersonCondition::And(conditions)
+    }
+
+    fn or(conditions: Vec<Self>) -> Self {
+        PersonCondition::Or(conditions)
+    }
+}
+
+impl FilterField for BikeTripCondition {
+    fn field(field: &str, filter: StringFilter) -> Option<Self> {
+        match field.split_once('.') {
+            Some(("bike", field)) => Some(BikeTripCondition::bike(BikeCondition::field(field, filter)?)),
+            Some(_) => None,
+            None => match field {
+                "name" => Some(BikeTripCondition::name(filter)),
+                _ => None,
+            },
+        }
+    }
+
+    fn call(function: &str, argument: &str, bike: Option<BikeCondition>) -> Result<Self, String> {
+        match function.split_once('.') {
+            Some(("bike", function)) => Ok(BikeTripCondition::bike(BikeCondition::call(function, argument, bike)?)),
+            _ => Err(format!("unknown function `{}`", function)),
+        }
+    }
+
+    fn and(conditions: Vec<Self>) -> Self {
+        BikeTripCondition::And(conditions)
+    }
+
+    fn or(conditions: Vec<Self>) -> Self {
+        BikeTripCondition::Or(conditions)
+    }
+}
+
+/// Why a filter expression couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset of the offending token in the expression
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Eq,
+    NotEq,
+    LParen,
+    RParen,
+    Comma,
+    End,
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | ',' | '=' => {
+                chars.next();
+                let token = match c {
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    ',' => Token::Comma,
+                    _ => Token::Eq,
+                };
+                tokens.push((start, token));
+            }
+            '!' => {
+                chars.next();
+                match chars.next() {
+                    Some((_, '=')) => tokens.push((start, Token::NotEq)),
+                    _ => return Err(ParseError { position: start, message: "expected `!=`".to_string() }),
+                }
+            }
+            '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, escaped)) => value.push(escaped),
+                            None => return Err(ParseError { position: start, message: "unterminated string".to_string() }),
+                        },
+                        Some((_, c)) => value.push(c),
+                        None => return Err(ParseError { position: start, message: "unterminated string".to_string() }),
+                    }
+                }
+                tokens.push((start, Token::Str(value)));
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_' || c == '.') {
+                        break;
+                    }
+                    ident.push(c);
+                    chars.next();
+                }
+                tokens.push((start, Token::Ident(ident)));
+            }
+            c => return Err(ParseError { position: start, message: format!("unexpected `{}`", c) }),
+        }
+    }
+    tokens.push((input.len(), Token::End));
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next].1
+    }
+
+    fn position(&self) -> usize {
+        self.tokens[self.next].0
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.next].1.clone();
+        if token != Token::End {
+            self.next += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, ParseError> {
+        Err(ParseError { position: self.position(), message: message.into() })
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Token::Ident(ident) if ident.eq_ignore_ascii_case(keyword) => {
+                self.advance();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, expected: Token, what: &str) -> Result<(), ParseError> {
+        if *self.peek() == expected {
+            self.advance();
+            Ok(())
+        } else {
+            self.error(format!("expected {}", what))
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        match self.peek().clone() {
+            Token::Str(value) => {
+                self.advance();
+                Ok(value)
+            }
+            _ => self.error("expected a quoted string"),
+        }
+    }
+
+    // or_expr := and_expr ("or" and_expr)*
+    fn or_expr<C: FilterField>(&mut self) -> Result<C, ParseError> {
+        let mut conditions = vec![self.and_expr()?];
+        while self.keyword("or") {
+            conditions.push(self.and_expr()?);
+        }
+        Ok(if conditions.len() == 1 { conditions.remove(0) } else { C::or(conditions) })
+    }
+
+    // and_expr := primary ("and" primary)*
+    fn and_expr<C: FilterField>(&mut self) -> Result<C, ParseError> {
+        let mut conditions = vec![self.primary()?];
+        while self.keyword("and") {
+            conditions.push(self.primary()?);
+        }
+        Ok(if conditions.len() == 1 { conditions.remove(0) } else { C::and(conditions) })
+    }
+
+    // primary := "(" or_expr ")" | field ("=" | "!=" | "like") string | field "in" "(" string ("," string)* ")"
+    //          | function "(" (ident | string) ("," bike or_expr)? ")"
+    fn primary<C: FilterField>(&mut self) -> Result<C, ParseError> {
+        if *self.peek() == Token::LParen {
+            self.advance();
+            let condition = self.or_expr()?;
+            self.expect(Token::RParen, "`)`")?;
+            return Ok(condition);
+        }
+
+        let field_position = self.position();
+        let field = match self.advance() {
+            Token::Ident(field) => field,
+            _ => return Err(ParseError { position: field_position, message: "expected a field".to_string() }),
+        };
+
+        if *self.peek() == Token::LParen {
+            self.advance();
+            let argument = match self.peek().clone() {
+                Token::Ident(argument) | Token::Str(argument) => argument,
+                _ => return self.error("expected an argument"),
+            };
+            self.advance();
+            let bike = if *self.peek() == Token::Comma {
+                self.advance();
+                Some(self.or_expr::<BikeCondition>()?)
+            } else {
+                None
+            };
+            self.expect(Token::RParen, "`)`")?;
+            return C::call(&field, &argument, bike).map_err(|message| ParseError { position: field_position, message });
+        }
+
+        let filter = match self.peek().clone() {
+            Token::Eq => {
+                self.advance();
+                StringFilter::Equal(self.string()?)
+            }
+            Token::NotEq => {
+                self.advance();
+                StringFilter::NotEqual(self.string()?)
+            }
+            _ if self.keyword("like") => StringFilter::Like(self.string()?),
+            _ if self.keyword("in") => {
+                self.expect(Token::LParen, "`(`")?;
+                let mut values = vec![self.string()?];
+                while *self.peek() == Token::Comma {
+                    self.advance();
+                    values.push(self.string()?);
+                }
+                self.expect(Token::RParen, "`)`")?;
+                StringFilter::In(values)
+            }
+            _ => return self.error("expected `=`, `!=`, `like` or `in`"),
+        };
+
+        C::field(&field, filter)
+            .ok_or_else(|| ParseError { position: field_position, message: format!("unknown field `{}`", field) })
+    }
+}
+
+/// Parses a filter expression like `color = "Red" and (name like "Road%" or owner_id in ("a", "b"))`
+///
+/// `and` binds tighter than `or`. Persons and bike trips can filter on their
+/// bike's fields with a `bike.` prefix, e.g. `bike.color = "Red"`. Bikes can
+/// be filtered on worn components with `needs_service(500)`, and persons on
+/// the bikes they owned at some time with
+/// `owned_bike_at("2024-10-05T12:00:00Z", color = "Red")`.
+pub fn parse_filter<C: FilterField>(input: &str) -> Result<C, ParseError> {
+    let mut parser = Parser { tokens: tokenize(input)?, next: 0 };
+    let condition = parser.or_expr()?;
+    if *parser.peek() != Token::End {
+        return parser.error("expected `and`, `or` or the end of the filter");
+    }
+    Ok(condition)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(input: &str) -> ParseError {
+        parse_filter::<BikeCondition>(input).unwrap_err()
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let condition: BikeCondition = parse_filter(r#"name = "a" or name = "b" and color = "Red""#).unwrap();
+        match condition {
+            BikeCondition::Or(conditions) => {
+                assert!(matches!(&conditions[0], BikeCondition::name(StringFilter::Equal(name)) if name == "a"));
+                assert!(matches!(&conditions[1], BikeCondition::And(and) if and.len() == 2));
+            }
+            other => panic!("expected an Or, got {:?}", other),
+        }
+
+        let condition: BikeCondition = parse_filter(r#"(name = "a" or name = "b") AND color = "Red""#).unwrap();
+        match condition {
+            BikeCondition::And(conditions) => assert!(matches!(&conditions[0], BikeCondition::Or(or) if or.len() == 2)),
+            other => panic!("expected an And, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn escapes_in_strings() {
+        let condition: BikeCondition = parse_filter(r#"name = "say \"hi\" \\ bye""#).unwrap();
+        assert!(matches!(condition, BikeCondition::name(StringFilter::Equal(name)) if name == r#"say "hi" \ bye"#));
+    }
+
+    #[test]
+    fn unterminated_strings() {
+        assert_eq!(error(r#"name = "Road"#), ParseError { position: 7, message: "unterminated string".to_string() });
+        // A trailing backslash escapes the closing quote, so the string never ends
+        assert_eq!(error(r#"name = "Road\"#), ParseError { position: 7, message: "unterminated string".to_string() });
+        assert_eq!(error(r#"name = "Road\""#), ParseError { position: 7, message: "unterminated string".to_string() });
+    }
+
+    #[test]
+    fn unknown_fields_and_functions() {
+        assert_eq!(error(r#"colour = "Red""#), ParseError { position: 0, message: "unknown field `colour`".to_string() });
+        assert_eq!(error(r#"name = "a" and bike.color = "Red""#).message, "unknown field `bike.color`");
+        assert!(parse_filter::<PersonCondition>(r#"bike.colour = "Red""#).is_err());
+        assert!(parse_filter::<PersonCondition>(r#"bike.color = "Red""#).is_ok());
+        assert_eq!(error("wheelie(3)").message, "unknown function `wheelie`");
+    }
+
+    #[test]
+    fn malformed_expressions() {
+        assert_eq!(error(r#"name = "a" name = "b""#).position, 11);
+        assert_eq!(error(r#"name ! "a""#).message, "expected `!=`");
+        assert_eq!(error(r#"name in ("a", "b""#).message, "expected `)`");
+        assert_eq!(error("name = Road").message, "expected a quoted string");
+    }
+}
@@ -0,0 +1,313 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use clap::{Parser, Subcommand, ValueEnum};
+use crate::api::Tenancy;
+use crate::dal::DataAccessLayer;
+use crate::models::bike::{BikeCondition, NewBike};
+use crate::models::bike_trip::{BikeTripCondition, NewBikeTrip};
+use crate::models::color::NewColor;
+use crate::models::common::{DeleteStrategy, DeleteSummary};
+use crate::models::person::{NewPerson, PersonCondition};
+use crate::transfer::{self, FileFormat, ImportMode};
+
+mod filter;
+mod output;
+
+pub use filter::{parse_filter, FilterField, ParseError};
+pub use output::{write_rows, Format};
+
+/// Manage persons, bikes, colors and bike trips, or serve the HTTP API
+#[derive(Debug, Parser)]
+#[command(name = "pedal_pal")]
+pub struct Cli {
+    /// Postgres connection URL, required since the credentials are part of it
+    #[arg(long, env = "DATABASE_URL", hide_env_values = true)]
+    pub database_url: String,
+    /// Only see and write rows of this tenant
+    #[arg(long, env = "PEDAL_PAL_TENANT", global = true)]
+    pub tenant: Option<String>,
+    /// Record writes as made by this actor
+    #[arg(long, env = "PEDAL_PAL_ACTOR", global = true)]
+    pub actor: Option<String>,
+    /// How rows are printed
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub format: Format,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+impl Cli {
+    /// The data access layer over `dal` the command runs on, scoped by `--tenant` and `--actor`
+    pub fn scope(&self, dal: DataAccessLayer) -> DataAccessLayer {
+        let dal = match &self.tenant {
+            Some(tenant_id) => dal.for_tenant(tenant_id),
+            None => dal,
+        };
+        match &self.actor {
+            Some(actor_id) => dal.with_actor(actor_id),
+            None => dal,
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Serve the HTTP API
+    Serve {
+        /// Address to listen on
+        #[arg(long, env = "BIND_ADDRESS", default_value = "127.0.0.1:3000")]
+        bind: String,
+        /// Whether to serve the `--tenant` the server runs as, or to trust the
+        /// tenant and actor headers set by an authenticating proxy
+        #[arg(long, value_enum, env = "PEDAL_PAL_TENANCY")]
+        tenancy: Tenancy,
+    },
+    /// Manage persons
+    #[command(subcommand, visible_alias = "persons")]
+    Person(PersonCommand),
+    /// Manage bikes
+    #[command(subcommand, visible_alias = "bikes")]
+    Bike(BikeCommand),
+    /// Manage colors
+    #[command(subcommand, visible_alias = "colors")]
+    Color(ColorCommand),
+    /// Manage bike trips
+    #[command(subcommand, visible_aliases = ["trips", "bike-trips"])]
+    Trip(TripCommand),
+    /// Import rows from a CSV or NDJSON file, referring to owners, colors and bikes by name
+    Import {
+        entity: Entity,
+        file: PathBuf,
+        /// The file's format, guessed from its extension if not given
+        #[arg(long, value_enum)]
+        file_format: Option<FileFormat>,
+        /// Import nothing unless every row is valid
+        #[arg(long)]
+        all_or_nothing: bool,
+    },
+    /// Export rows as CSV or NDJSON, e.g. `export bikes --filter 'color = "Red"'`
+    Export {
+        entity: Entity,
+        /// Only rows matching this filter expression, not supported for colors
+        #[arg(long)]
+        filter: Option<String>,
+        #[arg(long, value_enum, default_value_t)]
+        file_format: FileFormat,
+    },
+}
+
+/// The entities rows can be imported and exported for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Entity {
+    #[value(alias = "person")]
+    Persons,
+    #[value(alias = "color")]
+    Colors,
+    #[value(alias = "bike")]
+    Bikes,
+    #[value(aliases = ["trip", "bike-trips"])]
+    Trips,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PersonCommand {
+    /// List persons, e.g. `--filter 'bike.color = "Red"'`
+    List {
+        /// Only rows matching this filter expression
+        #[arg(long)]
+        filter: Option<String>,
+    },
+    /// Show one person
+    Get { id: String },
+    /// Create a person
+    Create {
+        name: String,
+        #[arg(long)]
+        email: Option<String>,
+    },
+    /// Delete a person
+    Delete {
+        id: String,
+        #[arg(long, value_enum, default_value_t)]
+        strategy: Strategy,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BikeCommand {
+    /// List bikes, e.g. `--filter 'color = "Red" and name like "Road%"'`
+    List {
+        /// Only rows matching this filter expression
+        #[arg(long)]
+        filter: Option<String>,
+    },
+    /// Show one bike
+    Get { id: String },
+    /// Create a bike
+    Create {
+        name: String,
+        /// ID of the owner
+        #[arg(long)]
+        owner: Option<String>,
+        /// ID of the color
+        #[arg(long)]
+        color: Option<String>,
+    },
+    /// Delete a bike
+    Delete {
+        id: String,
+        #[arg(long, value_enum, default_value_t)]
+        strategy: Strategy,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ColorCommand {
+    /// List colors
+    List,
+    /// Show one color
+    Get { id: String },
+    /// Create a color
+    Create { name: String },
+    /// Delete a color
+    Delete {
+        id: String,
+        #[arg(long, value_enum, default_value_t)]
+        strategy: Strategy,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TripCommand {
+    /// List bike trips, e.g. `--filter 'bike.name = "Road Bike"'`
+    List {
+        /// Only rows matching this filter expression
+        #[arg(long)]
+        filter: Option<String>,
+    },
+    /// Show one bike trip
+    Get { id: String },
+    /// Create a bike trip
+    Create {
+        name: String,
+        /// ID of the bike
+        #[arg(long)]
+        bike: Option<String>,
+    },
+    /// Delete a bike trip
+    Delete { id: String },
+}
+
+/// What happens to rows referencing a deleted row, see `DeleteStrategy`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Strategy {
+    #[default]
+    Restrict,
+    Cascade,
+    SetNull,
+}
+
+impl From<Strategy> for DeleteStrategy {
+    fn from(strategy: Strategy) -> Self {
+        match strategy {
+            Strategy::Restrict => DeleteStrategy::Restrict,
+            Strategy::Cascade => DeleteStrategy::Cascade,
+            Strategy::SetNull => DeleteStrategy::SetNull,
+        }
+    }
+}
+
+fn conditions<C: FilterField>(filter: Option<String>) -> Result<Vec<C>, ParseError> {
+    filter.map(|filter| parse_filter(&filter)).into_iter().collect()
+}
+
+impl Command {
+    /// Runs an entity command against `dal`, printing its rows to `out`
+    pub fn execute(self, dal: &DataAccessLayer, format: Format, out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+        match self {
+            Command::Serve { .. } => Err("`serve` runs the server, not a one-off command".into()),
+            Command::Import { entity, file, file_format, all_or_nothing } => {
+                let file_format = file_format
+                    .or_else(|| FileFormat::from_path(&file))
+                    .ok_or("can't tell the file's format from its extension, pass `--file-format`")?;
+                let mode = if all_or_nothing { ImportMode::AllOrNothing } else { ImportMode::BestEffort };
+                let reader = File::open(&file)?;
+                let report = match entity {
+                    Entity::Persons => transfer::import_persons(dal, file_format, mode, reader)?,
+                    Entity::Colors => transfer::import_colors(dal, file_format, mode, reader)?,
+                    Entity::Bikes => transfer::import_bikes(dal, file_format, mode, reader)?,
+                    Entity::Trips => transfer::import_bike_trips(dal, file_format, mode, reader)?,
+                };
+                if report.is_complete() {
+                    writeln!(out, "imported {} rows", report.imported)?;
+                    return Ok(());
+                }
+                write_rows(&report.failed, format, out)?;
+                Err(format!("{} rows failed, {} rows were imported", report.failed.len(), report.imported).into())
+            }
+            Command::Export { entity, filter, file_format } => {
+                match entity {
+                    Entity::Persons => transfer::export_persons(dal, conditions(filter)?, file_format, out)?,
+                    Entity::Colors if filter.is_some() => return Err("colors can't be filtered".into()),
+                    Entity::Colors => transfer::export_colors(dal, file_format, out)?,
+                    Entity::Bikes => transfer::export_bikes(dal, conditions(filter)?, file_format, out)?,
+                    Entity::Trips => transfer::export_bike_trips(dal, conditions(filter)?, file_format, out)?,
+                };
+                Ok(())
+            }
+            Command::Person(command) => match command {
+                PersonCommand::List { filter } => {
+                    let persons = dal.person().find_with_filters(conditions::<PersonCondition>(filter)?)?;
+                    write_rows(&persons, format, out)
+                }
+                PersonCommand::Get { id } => write_rows(&[dal.person().find_by_id(&id)?], format, out),
+                PersonCommand::Create { name, email } => {
+                    let new_person = NewPerson { email, ..NewPerson::new(&name) };
+                    write_rows(&[dal.person().create(&new_person)?], format, out)
+                }
+                PersonCommand::Delete { id, strategy } => {
+                    write_rows(&[dal.person().delete_with(&id, strategy.into())?], format, out)
+                }
+            },
+            Command::Bike(command) => match command {
+                BikeCommand::List { filter } => {
+                    let bikes = dal.bike().find_with_filters(conditions::<BikeCondition>(filter)?)?;
+                    write_rows(&bikes, format, out)
+                }
+                BikeCommand::Get { id } => write_rows(&[dal.bike().find_by_id(&id)?], format, out),
+                BikeCommand::Create { name, owner, color } => {
+                    let new_bike = NewBike::new(&name, owner.as_deref(), color.as_deref());
+                    write_rows(&[dal.bike().create(&new_bike)?], format, out)
+                }
+                BikeCommand::Delete { id, strategy } => {
+                    write_rows(&[dal.bike().delete_with(&id, strategy.into())?], format, out)
+                }
+            },
+            Command::Color(command) => match command {
+                ColorCommand::List => write_rows(&dal.color().find_all()?, format, out),
+                ColorCommand::Get { id } => write_rows(&[dal.color().find_by_id(&id)?], format, out),
+                ColorCommand::Create { name } => write_rows(&[dal.color().create(&NewColor::new(&name))?], format, out),
+                ColorCommand::Delete { id, strategy } => {
+                    write_rows(&[dal.color().delete_with(&id, strategy.into())?], format, out)
+                }
+            },
+            Command::Trip(command) => match command {
+                TripCommand::List { filter } => {
+                    let trips = dal.bike_trip().find_with_filters(conditions::<BikeTripCondition>(filter)?)?;
+                    write_rows(&trips, format, out)
+                }
+                TripCommand::Get { id } => write_rows(&[dal.bike_trip().find_by_id(&id)?], format, out),
+                TripCommand::Create { name, bike } => {
+                    let new_trip = NewBikeTrip::new(&name, bike.as_deref());
+                    write_rows(&[dal.bike_trip().create(&new_trip)?], format, out)
+                }
+                TripCommand::Delete { id } => {
+                    let deleted = dal.bike_trip().delete(&id)?;
+                    write_rows(&[DeleteSummary { deleted, dependents: 0 }], format, out)
+                }
+            },
+        }
+    }
+}
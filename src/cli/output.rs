@@ -0,0 +1,65 @@
+use std::error::Error;
+use std::io::Write;
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// How rows are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Columns aligned for reading
+    #[default]
+    Table,
+    /// A pretty printed JSON array
+    Json,
+    /// CSV with a header row
+    Csv,
+}
+
+/// Writes `rows` to `out` in `format`
+///
+/// Tables go through the CSV serializer, so both list the struct's fields as
+/// columns in declaration order and print missing values as empty cells.
+pub fn write_rows<T: Serialize>(rows: &[T], format: Format, out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, rows)?;
+            writeln!(out)?;
+        }
+        Format::Csv => write_csv(rows, out)?,
+        Format::Table => {
+            let mut csv = Vec::new();
+            write_csv(rows, &mut csv)?;
+            let records = csv::ReaderBuilder::new()
+                .has_headers(false)
+                .from_reader(csv.as_slice())
+                .into_records()
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut widths: Vec<usize> = Vec::new();
+            for record in &records {
+                widths.resize(widths.len().max(record.len()), 0);
+                for (width, cell) in widths.iter_mut().zip(record.iter()) {
+                    *width = (*width).max(cell.chars().count());
+                }
+            }
+            for record in &records {
+                let cells: Vec<String> = record
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                    .collect();
+                writeln!(out, "{}", cells.join("  ").trim_end())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_csv<T: Serialize>(rows: &[T], out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_writer(out);
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -1,39 +1,86 @@
 use diesel::pg::Pg;
 use diesel::{
-    helper_types::{IntoBoxed, LeftJoin, LeftJoinQuerySource},
+    helper_types::{IntoBoxed, LeftJoinQuerySource, Select},
     prelude::*,
-    sql_types::{Bool, Nullable},
+    sql_types::{Bool, Nullable, Text},
 };
+use chrono::{DateTime, Utc};
+use diesel::dsl::{count_star, now};
+use diesel::upsert::{excluded, on_constraint};
 use diesel::r2d2::{self, ConnectionManager};
-use crate::models::bike::{Bike, NewBike};
-use crate::models::common::StringFilter;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use crate::models::bike::{Bike, BikeColumn, BikeGroup, BikePatch, BikeRow, BikeWithRelations, Include, Includes, NewBike};
+use crate::models::bike_trip::BikeTrip;
+use crate::models::color::Color;
+use crate::models::ownership::Ownership;
+use crate::models::person::Person;
+use crate::models::common::{Bucket, DeleteStrategy, FieldAccess, DeleteSummary, DeletedMode, StringFilter, Upsert, WriteMode};
 use crate::schema;
 use crate::schema::bike::dsl::*;
 use crate::models::bike::BikeCondition;
-use crate::dal::string_filter;
+use crate::dal::history::{write_connection, HistoryRow};
+use crate::models::history::HistoryEntry;
+use crate::dal::{batch_size, deleted_filter, ensure_referenced, string_filter, tenant_condition, DeleteError, UpdateError};
+use crate::dal::explain::{explain, QueryPlan};
+use crate::dal::maintenance::worn_components;
+use crate::dal::policy::{Action, Policy, PolicyDenied};
+use crate::dal::saved_filter::SavedFilterDAL;
 use crate::models::AndOr;
 
 /// Type alias for the database connection pool
 type Pool = r2d2::Pool<ConnectionManager<PgConnection>>;
 
-type ConditionSource = LeftJoinQuerySource<schema::bike::dsl::bike, schema::color::dsl::color>;
+type ConditionSource = LeftJoinQuerySource<
+    LeftJoinQuerySource<schema::bike::dsl::bike, schema::color::dsl::color, color_of_bike>,
+    schema::person::dsl::person,
+    owner_of_bike,
+>;
 type BoxedCondition = Box<dyn BoxableExpression<ConditionSource, Pg, SqlType = Nullable<Bool>>>;
-type QuerySource = LeftJoin<schema::bike::dsl::bike, schema::color::dsl::color>;
+type QuerySource = bike_with_relations;
 type BoxedQuery = IntoBoxed<'static, QuerySource, Pg>;
 
+/// Joins the color of a bike, as long as it belongs to the same tenant
+#[diesel::dsl::auto_type]
+fn color_of_bike() -> _ {
+    schema::bike::dsl::color_id
+        .eq(schema::color::dsl::id.nullable())
+        .and(schema::color::dsl::tenant_id.is_not_distinct_from(schema::bike::dsl::tenant_id))
+}
+
+/// Joins the owner of a bike, as long as they belong to the same tenant
+#[diesel::dsl::auto_type]
+fn owner_of_bike() -> _ {
+    schema::bike::dsl::owner_id
+        .eq(schema::person::dsl::id.nullable())
+        .and(schema::person::dsl::tenant_id.is_not_distinct_from(schema::bike::dsl::tenant_id))
+}
+
+/// Bikes joined with their color and owner, which read as missing when they
+/// are in another tenant than the bike
+#[diesel::dsl::auto_type]
+fn bike_with_relations() -> _ {
+    let color_on: color_of_bike = color_of_bike();
+    let owner_on: owner_of_bike = owner_of_bike();
+    schema::bike::dsl::bike
+        .left_join(schema::color::dsl::color.on(color_on))
+        .left_join(schema::person::dsl::person.on(owner_on))
+}
+type ScopeCondition = Box<dyn BoxableExpression<bike, Pg, SqlType = Nullable<Bool>>>;
+
 impl BikeCondition {
-    fn to_boxed_condition(self) -> Option<BoxedCondition> {
+    fn into_boxed_condition(self) -> Option<BoxedCondition> {
         Some(match self {
+            BikeCondition::id(f) => string_filter!(f, schema::bike::dsl::id),
             BikeCondition::name(f) => string_filter!(f, schema::bike::dsl::name),
             BikeCondition::color(f) => string_filter!(f, schema::color::dsl::name),
-            BikeCondition::And(conditions) => match create_filter(conditions, AndOr::And) {
-                Some(boxed_condition) => boxed_condition,
-                None => return None,
-            },
-            BikeCondition::Or(conditions) => match create_filter(conditions, AndOr::Or) {
-                Some(boxed_condition) => boxed_condition,
-                None => return None,
-            },
+            BikeCondition::owner_id(f) => string_filter!(f, schema::bike::dsl::owner_id),
+            BikeCondition::needs_service(threshold) => {
+                let worn = worn_components!(threshold).select(schema::component::dsl::bike_id);
+                Box::new(schema::bike::dsl::id.eq_any(worn).nullable())
+            }
+            BikeCondition::And(conditions) => create_filter(conditions, AndOr::And)?,
+            BikeCondition::Or(conditions) => create_filter(conditions, AndOr::Or)?,
         })
     }
 }
@@ -44,7 +91,7 @@ fn create_filter(conditions: Vec<BikeCondition>, and_or: AndOr) -> Option<BoxedC
     conditions
         .into_iter()
         // Map into array of boxed conditions
-        .filter_map::<BoxedCondition, _>(BikeCondition::to_boxed_condition)
+        .filter_map::<BoxedCondition, _>(BikeCondition::into_boxed_condition)
         // Reduce to a boxed_condition1.and(boxed_condition2).and(boxed_condition3)...
         .fold(None, |boxed_conditions, boxed_condition| {
             Some(match boxed_conditions {
@@ -56,8 +103,12 @@ fn create_filter(conditions: Vec<BikeCondition>, and_or: AndOr) -> Option<BoxedC
             })
         })
 }
-pub(super) fn create_filtered_query(conditions: Vec<BikeCondition>) -> BoxedQuery {
-    let boxed_query = schema::bike::dsl::bike.left_join(schema::color::dsl::color).into_boxed();
+fn create_filtered_query(conditions: Vec<BikeCondition>, deleted: DeletedMode, tenant: Option<&str>) -> BoxedQuery {
+    // The tenant predicate wraps the conditions, so an `Or` can't reach other tenants
+    let boxed_query = bike_with_relations()
+        .into_boxed()
+        .filter(tenant_condition!(tenant, ConditionSource, schema::bike::dsl::tenant_id));
+    let boxed_query = deleted_filter!(boxed_query, deleted, schema::bike::dsl::deleted_at);
 
     match create_filter(conditions, AndOr::And) {
         Some(boxed_conditions) => boxed_query.filter(boxed_conditions),
@@ -69,6 +120,11 @@ pub(super) fn create_filtered_query(conditions: Vec<BikeCondition>) -> BoxedQuer
 /// Data Access Layer for Bike entities
 pub struct BikeDAL {
     pool: Pool,
+    deleted: DeletedMode,
+    actor: Option<String>,
+    tenant: Option<String>,
+    clearance: FieldAccess,
+    policy: Option<Arc<dyn Policy<BikeCondition>>>,
 }
 
 impl BikeDAL {
@@ -78,7 +134,185 @@ impl BikeDAL {
     ///
     /// * `pool` - The database connection pool
     pub fn new(pool: Pool) -> Self {
-        BikeDAL { pool }
+        BikeDAL {
+            pool,
+            deleted: DeletedMode::Exclude,
+            actor: None,
+            tenant: None,
+            clearance: FieldAccess::Public,
+            policy: None,
+        }
+    }
+
+    /// Makes reads return deleted bikes alongside live ones
+    pub fn include_deleted(mut self) -> Self {
+        self.deleted = DeletedMode::Include;
+        self
+    }
+
+    /// Makes reads return only deleted bikes
+    pub fn only_deleted(mut self) -> Self {
+        self.deleted = DeletedMode::Only;
+        self
+    }
+
+    /// Attributes writes to `actor_id` in the bike history
+    ///
+    /// # Arguments
+    ///
+    /// * `actor_id` - ID of the person or system performing the writes
+    pub fn with_actor(mut self, actor_id: &str) -> Self {
+        self.actor = Some(actor_id.to_string());
+        self
+    }
+
+    /// Scopes all reads and writes to the bikes of `tenant`
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant` - ID of the tenant whose bikes are visible
+    pub fn for_tenant(mut self, tenant: &str) -> Self {
+        self.tenant = Some(tenant.to_string());
+        self
+    }
+
+    /// Allows filtering on fields up to `clearance`
+    ///
+    /// Condition trees referencing fields above it are rejected before any SQL is built.
+    ///
+    /// # Arguments
+    ///
+    /// * `clearance` - The highest field access the caller is cleared for
+    pub fn with_field_access(mut self, clearance: FieldAccess) -> Self {
+        self.clearance = clearance;
+        self
+    }
+
+    /// Rejects conditions filtering on fields above the clearance, with a
+    /// `QueryBuilderError` holding the `FieldDenied`
+    fn validate(&self, conditions: &[BikeCondition]) -> QueryResult<()> {
+        conditions
+            .iter()
+            .try_for_each(|condition| condition.validate(self.clearance))
+            .map_err(|denied| diesel::result::Error::QueryBuilderError(Box::new(denied)))
+    }
+
+    /// The IDs of the readable bikes matching `conditions`, as a sub select for other DALs
+    pub(super) fn id_query(&self, conditions: Vec<BikeCondition>) -> QueryResult<Select<BoxedQuery, id>> {
+        self.validate(&conditions)?;
+        let query = create_filtered_query(self.with_policy_condition(conditions, Action::Read), self.deleted, self.tenant.as_deref());
+        Ok(query.select(id))
+    }
+
+    /// The readable bikes matching `conditions`, as the inner statement of conditions of other entities
+    ///
+    /// Unlike `id_query` the deleted mode is up to the caller, and the conditions
+    /// were validated with the outer ones.
+    pub(super) fn nested_query(&self, conditions: Vec<BikeCondition>, deleted: DeletedMode) -> BoxedQuery {
+        create_filtered_query(self.with_policy_condition(conditions, Action::Read), deleted, self.tenant.as_deref())
+    }
+
+    /// Restricts the bikes this DAL may access for its actor
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The policy deciding which bikes the actor may read, update and delete
+    pub fn with_policy(mut self, policy: Arc<dyn Policy<BikeCondition>>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    fn policy_condition(&self, action: Action) -> Option<BikeCondition> {
+        self.policy.as_ref()?.condition(self.actor.as_deref(), action)
+    }
+
+    fn with_policy_condition(&self, mut conditions: Vec<BikeCondition>, action: Action) -> Vec<BikeCondition> {
+        conditions.extend(self.policy_condition(action));
+        conditions
+    }
+
+    /// The bikes of the tenant the policy allows `action` on
+    fn scope(&self, action: Action) -> ScopeCondition {
+        let tenant = tenant_condition!(self.tenant.as_deref(), bike, tenant_id);
+        match self.policy_condition(action) {
+            // Policy conditions may refer to the joined color and owner, so match through a sub select
+            Some(condition) => {
+                let allowed = create_filtered_query(vec![condition], DeletedMode::Include, self.tenant.as_deref());
+                Box::new(tenant.and(id.eq_any(allowed.select(id)).nullable()))
+            }
+            None => tenant,
+        }
+    }
+
+    /// The IDs of the bikes the policy allows `action` on, nullable to match against
+    /// references, `None` if the policy doesn't restrict it
+    pub(super) fn allowed_ids(&self, action: Action) -> Option<schema::bike::BoxedQuery<'static, Pg, Nullable<Text>>> {
+        self.policy_condition(action)?;
+        Some(bike.filter(self.scope(action)).select(id.nullable()).into_boxed())
+    }
+
+    /// Whether the bike exists in the tenant, but the policy doesn't allow `action` on it
+    fn denied(&self, conn: &mut PgConnection, bike_id: &str, action: Action) -> QueryResult<bool> {
+        if self.policy_condition(action).is_none() {
+            return Ok(false);
+        }
+        let tenant = tenant_condition!(self.tenant.as_deref(), bike, tenant_id);
+        let exists = diesel::select(diesel::dsl::exists(bike.find(bike_id).filter(tenant))).get_result::<bool>(conn)?;
+        let allowed = diesel::select(diesel::dsl::exists(bike.find(bike_id).filter(self.scope(action))))
+            .get_result::<bool>(conn)?;
+        Ok(exists && !allowed)
+    }
+
+    /// Whether the policy allows `action` on all of `bike_ids`
+    pub(super) fn allows(&self, conn: &mut PgConnection, bike_ids: &[String], action: Action) -> QueryResult<bool> {
+        if bike_ids.is_empty() || self.policy_condition(action).is_none() {
+            return Ok(true);
+        }
+        let allowed = bike
+            .filter(id.eq_any(bike_ids))
+            .filter(self.scope(action))
+            .count()
+            .get_result::<i64>(conn)?;
+        Ok(allowed as usize == bike_ids.len())
+    }
+
+    /// Fails with a `PolicyDenied` unless the policy allows updating all of the
+    /// written bikes, so writes can't move bikes out of the actor's scope
+    fn check_written<'a>(&self, conn: &mut PgConnection, written: impl IntoIterator<Item = &'a Bike>) -> QueryResult<()> {
+        let bike_ids: Vec<String> = written.into_iter().map(|b| b.id.clone()).collect();
+        if self.allows(conn, &bike_ids, Action::Update)? {
+            Ok(())
+        } else {
+            Err(diesel::result::Error::QueryBuilderError(Box::new(PolicyDenied)))
+        }
+    }
+
+    /// Fails with a foreign key violation unless the referenced owners and colors exist in the tenant
+    fn check_references<'a>(
+        &self,
+        conn: &mut PgConnection,
+        owner_ids: impl IntoIterator<Item = &'a str>,
+        color_ids: impl IntoIterator<Item = &'a str>,
+    ) -> QueryResult<()> {
+        let owners: BTreeSet<&str> = owner_ids.into_iter().collect();
+        if !owners.is_empty() {
+            let found = schema::person::table
+                .filter(schema::person::id.eq_any(owners.iter().copied().collect::<Vec<_>>()))
+                .filter(tenant_condition!(self.tenant.as_deref(), schema::person::table, schema::person::tenant_id))
+                .count()
+                .get_result::<i64>(conn)?;
+            ensure_referenced("person", &owners, found)?;
+        }
+        let colors: BTreeSet<&str> = color_ids.into_iter().collect();
+        if !colors.is_empty() {
+            let found = schema::color::table
+                .filter(schema::color::id.eq_any(colors.iter().copied().collect::<Vec<_>>()))
+                .filter(tenant_condition!(self.tenant.as_deref(), schema::color::table, schema::color::tenant_id))
+                .count()
+                .get_result::<i64>(conn)?;
+            ensure_referenced("color", &colors, found)?;
+        }
+        Ok(())
     }
 
     /// Creates a new bike in the database
@@ -89,12 +323,96 @@ impl BikeDAL {
     ///
     /// # Returns
     ///
-    /// The created bike or a database error
+    /// The created bike, a foreign key violation if the owner or color isn't in
+    /// the tenant, a `PolicyDenied` if the policy wouldn't allow updating the
+    /// bike, or a database error
     pub fn create(&self, new_bike: &NewBike) -> QueryResult<Bike> {
-        let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        diesel::insert_into(bike)
-            .values(new_bike)
-            .get_result(&mut conn)
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        conn.transaction(|conn| {
+            self.check_references(conn, new_bike.owner_id.as_deref(), new_bike.color_id.as_deref())?;
+            let created = diesel::insert_into(bike)
+                .values(new_bike)
+                .get_result(conn)?;
+            self.check_written(conn, [&created])?;
+            Ok(created)
+        })
+    }
+
+    /// Creates many bikes in the database
+    ///
+    /// Rows are inserted in batches that stay under the Postgres bind parameter
+    /// limit, all within a single transaction.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_bikes` - The new bikes to be created
+    ///
+    /// # Returns
+    ///
+    /// The created bikes, a foreign key violation if an owner or color isn't in
+    /// the tenant, a `PolicyDenied` if the policy wouldn't allow updating one of
+    /// them, or a database error
+    pub fn create_many(&self, new_bikes: &[NewBike]) -> QueryResult<Vec<Bike>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        conn.transaction(|conn| {
+            self.check_references(
+                conn,
+                new_bikes.iter().filter_map(|b| b.owner_id.as_deref()),
+                new_bikes.iter().filter_map(|b| b.color_id.as_deref()),
+            )?;
+            let mut created = Vec::with_capacity(new_bikes.len());
+            for batch in new_bikes.chunks(batch_size(4)) {
+                created.extend(diesel::insert_into(bike).values(batch).get_results::<Bike>(conn)?);
+            }
+            self.check_written(conn, &created)?;
+            Ok(created)
+        })
+    }
+
+    /// Creates or updates many bikes in the database
+    ///
+    /// # Arguments
+    ///
+    /// * `new_bikes` - The bikes to be created or updated
+    /// * `upsert` - The conflict target and the columns to overwrite on conflict
+    ///
+    /// # Returns
+    ///
+    /// The inserted and updated bikes, a foreign key violation if an owner or
+    /// color isn't in the tenant, a `PolicyDenied` if the policy wouldn't allow
+    /// updating one of them, or a database error
+    pub fn upsert_many(&self, new_bikes: &[NewBike], upsert: &Upsert<BikeColumn>) -> QueryResult<Vec<Bike>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        let constraint = upsert.conflict_target.constraint_name("bike_pkey");
+        conn.transaction(|conn| {
+            self.check_references(
+                conn,
+                new_bikes.iter().filter_map(|b| b.owner_id.as_deref()),
+                new_bikes.iter().filter_map(|b| b.color_id.as_deref()),
+            )?;
+            let mut upserted = Vec::with_capacity(new_bikes.len());
+            for batch in new_bikes.chunks(batch_size(4)) {
+                let query = diesel::insert_into(bike)
+                    .values(batch)
+                    .on_conflict(on_constraint(constraint));
+                let rows = if upsert.update_columns.is_empty() {
+                    query.do_nothing().get_results::<Bike>(conn)?
+                } else {
+                    let statement = query.do_update().set((
+                        upsert.updates(BikeColumn::Name).then(|| name.eq(excluded(name))),
+                        upsert.updates(BikeColumn::OwnerId).then(|| owner_id.eq(excluded(owner_id))),
+                        upsert.updates(BikeColumn::ColorId).then(|| color_id.eq(excluded(color_id))),
+                        version.eq(version + 1),
+                    ));
+                    // Conflicts with bikes of other tenants are skipped instead of overwritten
+                    diesel::query_dsl::methods::FilterDsl::filter(statement, self.scope(Action::Update))
+                        .get_results::<Bike>(conn)?
+                };
+                upserted.extend(rows);
+            }
+            self.check_written(conn, &upserted)?;
+            Ok(upserted)
+        })
     }
 
     /// Finds a bike by its ID
@@ -108,7 +426,7 @@ impl BikeDAL {
     /// The found bike or a database error
     pub fn find_by_id(&self, bike_id: &str) -> QueryResult<Bike> {
         let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        bike.find(bike_id).first(&mut conn)
+        deleted_filter!(bike.find(bike_id).filter(self.scope(Action::Read)).into_boxed(), self.deleted, deleted_at).first(&mut conn)
     }
 
     /// Retrieves all bikes from the database
@@ -118,11 +436,13 @@ impl BikeDAL {
     /// A vector of all bikes or a database error
     pub fn find_all(&self) -> QueryResult<Vec<Bike>> {
         let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        bike.load::<Bike>(&mut conn)
+        deleted_filter!(bike.filter(self.scope(Action::Read)).into_boxed(), self.deleted, deleted_at).load::<Bike>(&mut conn)
     }
 
     /// Updates an existing bike in the database
     ///
+    /// The update only applies if the bike is still at the version of `updated_bike`.
+    ///
     /// # Arguments
     ///
     /// * `bike_id` - The ID of the bike to update
@@ -130,31 +450,387 @@ impl BikeDAL {
     ///
     /// # Returns
     ///
-    /// The updated bike or a database error
-    pub fn update(&self, bike_id: &str, updated_bike: &Bike) -> QueryResult<Bike> {
-        let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        diesel::update(bike.find(bike_id))
-            .set((
-                name.eq(&updated_bike.name),
-                owner_id.eq(&updated_bike.owner_id),
-                color_id.eq(&updated_bike.color_id),
-            ))
-            .get_result(&mut conn)
+    /// The updated bike, a conflict holding the current bike if it was modified
+    /// in the meantime, `Denied` if the policy doesn't allow updating it before or
+    /// after the change, or a database error, a foreign key violation if the owner
+    /// or color isn't in the tenant
+    pub fn update(&self, bike_id: &str, updated_bike: &Bike) -> Result<Bike, UpdateError<Bike>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        if self.denied(&mut conn, bike_id, Action::Update)? {
+            return Err(UpdateError::Denied);
+        }
+        conn.transaction(|conn| {
+            let updated = diesel::update(
+                bike.find(bike_id)
+                    .filter(self.scope(Action::Update))
+                    .filter(deleted_at.is_null())
+                    .filter(version.eq(updated_bike.version)),
+            )
+                .set((
+                    name.eq(&updated_bike.name),
+                    owner_id.eq(&updated_bike.owner_id),
+                    color_id.eq(&updated_bike.color_id),
+                    version.eq(version + 1),
+                ))
+                .get_result::<Bike>(conn)
+                .optional()?;
+
+            match updated {
+                // Checked after the write, so bikes outside the scope still aren't found
+                Some(updated) => {
+                    self.check_references(conn, updated_bike.owner_id.as_deref(), updated_bike.color_id.as_deref())?;
+                    // The actor may not move the bike out of their own scope
+                    if !self.allows(conn, std::slice::from_ref(&updated.id), Action::Update)? {
+                        return Err(UpdateError::Denied);
+                    }
+                    Ok(updated)
+                }
+                None => {
+                    let current = bike.find(bike_id).filter(self.scope(Action::Update)).filter(deleted_at.is_null()).first(conn)?;
+                    Err(UpdateError::Conflict(Box::new(current)))
+                }
+            }
+        })
+    }
+
+    /// Partially updates an existing bike in the database
+    ///
+    /// Only the fields set in the patch are written, so concurrent edits of
+    /// different fields don't overwrite each other. The version is incremented
+    /// so that full updates based on an older read are rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `bike_id` - The ID of the bike to update
+    /// * `patch` - The fields to change
+    ///
+    /// # Returns
+    ///
+    /// The updated bike, `Denied` if the policy doesn't allow updating it before or
+    /// after the change, or a database error, a foreign key violation if the new
+    /// owner or color isn't in the tenant
+    pub fn update_partial(&self, bike_id: &str, patch: &BikePatch) -> Result<Bike, UpdateError<Bike>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        if self.denied(&mut conn, bike_id, Action::Update)? {
+            return Err(UpdateError::Denied);
+        }
+        conn.transaction(|conn| {
+            let updated: Bike = diesel::update(bike.find(bike_id).filter(self.scope(Action::Update)).filter(deleted_at.is_null()))
+                .set((patch, version.eq(version + 1)))
+                .get_result(conn)?;
+            // Checked after the write, so bikes outside the scope still aren't found
+            self.check_references(conn, patch.owner_id.iter().flatten().map(String::as_str), patch.color_id.iter().flatten().map(String::as_str))?;
+            // The actor may not move the bike out of their own scope
+            if !self.allows(conn, std::slice::from_ref(&updated.id), Action::Update)? {
+                return Err(UpdateError::Denied);
+            }
+            Ok(updated)
+        })
     }
 
     /// Deletes a bike from the database
     ///
+    /// The bike is only marked as deleted and can be brought back with `restore`.
+    ///
     /// # Arguments
     ///
     /// * `bike_id` - The ID of the bike to delete
     ///
     /// # Returns
     ///
-    /// The number of affected rows or a database error
-    pub fn delete(&self, bike_id: &str) -> QueryResult<usize> {
+    /// The number of affected rows, `Denied` if the policy doesn't allow deleting the bike,
+    /// or a database error
+    pub fn delete(&self, bike_id: &str) -> Result<usize, DeleteError> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        if self.denied(&mut conn, bike_id, Action::Delete)? {
+            return Err(DeleteError::Denied);
+        }
+        Ok(diesel::update(bike.find(bike_id).filter(self.scope(Action::Delete)).filter(deleted_at.is_null()))
+            .set((deleted_at.eq(now), version.eq(version + 1)))
+            .execute(&mut conn)?)
+    }
+
+    /// Deletes a bike and handles its trips according to a strategy
+    ///
+    /// Runs in a single transaction, so a restricted delete leaves everything untouched.
+    ///
+    /// # Arguments
+    ///
+    /// * `bike_id` - The ID of the bike to delete
+    /// * `strategy` - What to do with the trips of the bike
+    ///
+    /// # Returns
+    ///
+    /// The number of deleted bikes and affected trips, `Restricted` if live trips
+    /// reference the bike under `DeleteStrategy::Restrict`, `Denied` if the policy doesn't
+    /// allow deleting the bike, or a database error
+    pub fn delete_with(&self, bike_id: &str, strategy: DeleteStrategy) -> Result<DeleteSummary, DeleteError> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        if self.denied(&mut conn, bike_id, Action::Delete)? {
+            return Err(DeleteError::Denied);
+        }
+        conn.transaction(|conn| {
+            let trips = || {
+                schema::bike_trip::table
+                    .filter(schema::bike_trip::bike_id.eq(bike_id))
+                    .filter(tenant_condition!(
+                        self.tenant.as_deref(),
+                        schema::bike_trip::table,
+                        schema::bike_trip::tenant_id
+                    ))
+            };
+            let live_trips = || trips().filter(schema::bike_trip::deleted_at.is_null());
+
+            if strategy == DeleteStrategy::Restrict {
+                let referencing = live_trips().count().get_result::<i64>(conn)?;
+                if referencing > 0 {
+                    return Err(DeleteError::Restricted(referencing as usize));
+                }
+            }
+
+            let deleted = diesel::update(bike.find(bike_id).filter(self.scope(Action::Delete)).filter(deleted_at.is_null()))
+                .set((deleted_at.eq(now), version.eq(version + 1)))
+                .execute(conn)?;
+            if deleted == 0 {
+                return Ok(DeleteSummary::default());
+            }
+
+            let dependents = match strategy {
+                DeleteStrategy::Restrict => 0,
+                DeleteStrategy::Cascade => diesel::update(live_trips())
+                    .set((
+                        schema::bike_trip::deleted_at.eq(now),
+                        schema::bike_trip::version.eq(schema::bike_trip::version + 1),
+                    ))
+                    .execute(conn)?,
+                DeleteStrategy::SetNull => diesel::update(trips())
+                    .set((
+                        schema::bike_trip::bike_id.eq(None::<String>),
+                        schema::bike_trip::version.eq(schema::bike_trip::version + 1),
+                    ))
+                    .execute(conn)?,
+            };
+
+            Ok(DeleteSummary { deleted, dependents })
+        })
+    }
+
+    /// Restores a deleted bike
+    ///
+    /// # Arguments
+    ///
+    /// * `bike_id` - The ID of the bike to restore
+    ///
+    /// # Returns
+    ///
+    /// The restored bike, `Denied` if the policy doesn't allow updating it, or a database
+    /// error, `NotFound` if it isn't deleted
+    pub fn restore(&self, bike_id: &str) -> Result<Bike, UpdateError<Bike>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        if self.denied(&mut conn, bike_id, Action::Update)? {
+            return Err(UpdateError::Denied);
+        }
+        Ok(diesel::update(bike.find(bike_id).filter(self.scope(Action::Update)).filter(deleted_at.is_not_null()))
+            .set((deleted_at.eq(None::<DateTime<Utc>>), version.eq(version + 1)))
+            .get_result(&mut conn)?)
+    }
+
+    /// Hands a bike over to a new owner
+    ///
+    /// Runs in a single transaction that locks the bike, so concurrent transfers
+    /// can't both succeed. The previous ownership range is closed by the same
+    /// write that opens the new one. Transferring a bike to its current owner
+    /// changes nothing. Only the bike before the transfer is checked against the
+    /// policy, since handing a bike over usually moves it out of the actor's scope.
+    ///
+    /// # Arguments
+    ///
+    /// * `bike_id` - The ID of the bike to transfer
+    /// * `new_owner_id` - The ID of the person taking the bike over
+    ///
+    /// # Returns
+    ///
+    /// The transferred bike, `Denied` if the policy doesn't allow updating it, or a
+    /// database error, `NotFound` if the bike or the new owner doesn't exist in the tenant
+    pub fn transfer(&self, bike_id: &str, new_owner_id: &str) -> Result<Bike, UpdateError<Bike>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        conn.transaction(|conn| {
+            schema::person::table
+                .find(new_owner_id)
+                .filter(schema::person::deleted_at.is_null())
+                .filter(tenant_condition!(self.tenant.as_deref(), schema::person::table, schema::person::tenant_id))
+                .select(schema::person::id)
+                .first::<String>(conn)?;
+            let current = bike
+                .find(bike_id)
+                .filter(self.scope(Action::Update))
+                .filter(deleted_at.is_null())
+                .for_update()
+                .first::<Bike>(conn)
+                .optional()?;
+            let current = match current {
+                Some(current) => current,
+                None if self.denied(conn, bike_id, Action::Update)? => return Err(UpdateError::Denied),
+                None => return Err(diesel::result::Error::NotFound.into()),
+            };
+            if current.owner_id.as_deref() == Some(new_owner_id) {
+                return Ok(current);
+            }
+            Ok(diesel::update(bike.find(bike_id))
+                .set((owner_id.eq(new_owner_id), version.eq(version + 1)))
+                .get_result(conn)?)
+        })
+    }
+
+    /// Lists who owned a bike and when, oldest first
+    ///
+    /// # Arguments
+    ///
+    /// * `bike_id` - The ID of the bike
+    ///
+    /// # Returns
+    ///
+    /// The ownership ranges of the bike or a database error
+    pub fn ownerships(&self, bike_id: &str) -> QueryResult<Vec<Ownership>> {
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+        schema::ownership::table
+            .filter(schema::ownership::bike_id.eq(bike_id))
+            .filter(schema::ownership::bike_id.eq_any(bike.filter(self.scope(Action::Read)).select(id).into_boxed()))
+            .order_by((schema::ownership::valid_from, schema::ownership::id))
+            .load(&mut conn)
+    }
+
+    /// Lists the recorded changes of a bike, oldest first
+    ///
+    /// # Arguments
+    ///
+    /// * `bike_id` - The ID of the bike
+    ///
+    /// # Returns
+    ///
+    /// The history entries of the bike or a database error
+    pub fn history(&self, bike_id: &str) -> QueryResult<Vec<HistoryEntry<Bike>>> {
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+        schema::bike_history::table
+            .filter(schema::bike_history::row_id.eq(bike_id))
+            .filter(schema::bike_history::row_id.eq_any(bike.filter(self.scope(Action::Read)).select(id).into_boxed()))
+            .order_by((schema::bike_history::changed_at, schema::bike_history::history_id))
+            .load::<HistoryRow>(&mut conn)?
+            .into_iter()
+            .map(HistoryRow::into_entry)
+            .collect()
+    }
+
+    /// Reconstructs a bike as it was at a point in time
+    ///
+    /// # Arguments
+    ///
+    /// * `bike_id` - The ID of the bike
+    /// * `at` - The point in time to read the bike at
+    ///
+    /// # Returns
+    ///
+    /// The bike as of `at`, `None` if it didn't exist yet or was removed, or a database error
+    pub fn as_of(&self, bike_id: &str, at: DateTime<Utc>) -> QueryResult<Option<Bike>> {
         let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        diesel::delete(bike.find(bike_id))
-            .execute(&mut conn)
+        let latest = schema::bike_history::table
+            .filter(schema::bike_history::row_id.eq(bike_id))
+            .filter(schema::bike_history::row_id.eq_any(bike.filter(self.scope(Action::Read)).select(id).into_boxed()))
+            .filter(schema::bike_history::changed_at.le(at))
+            .order_by((schema::bike_history::changed_at.desc(), schema::bike_history::history_id.desc()))
+            .first::<HistoryRow>(&mut conn)
+            .optional()?;
+
+        match latest {
+            Some(row) => Ok(row.into_entry::<Bike>()?.new),
+            None => Ok(None),
+        }
+    }
+
+    /// Updates all bikes matching the filters
+    ///
+    /// # Arguments
+    ///
+    /// * `conditions` - A vector of Condition enums selecting the bikes to update
+    /// * `patch` - The column changes to apply
+    /// * `mode` - Whether to apply the update or only count the matching bikes
+    ///
+    /// # Returns
+    ///
+    /// The number of affected rows, a foreign key violation if the new owner or
+    /// color isn't in the tenant, a `PolicyDenied` if the policy wouldn't allow
+    /// updating one of the bikes afterwards, or a database error
+    pub fn update_where(
+        &self,
+        conditions: Vec<BikeCondition>,
+        patch: &BikePatch,
+        mode: WriteMode,
+    ) -> QueryResult<usize> {
+        self.validate(&conditions)?;
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+
+        self.check_references(&mut conn, patch.owner_id.iter().flatten().map(String::as_str), patch.color_id.iter().flatten().map(String::as_str))?;
+
+        // Postgres can't update a joined source, so match ids through a sub select instead
+        let filtered_ids = create_filtered_query(self.with_policy_condition(conditions, Action::Update), self.deleted, self.tenant.as_deref()).select(id);
+        // Like update, deleted bikes stay untouched even when they're visible
+        let target = bike.filter(id.eq_any(filtered_ids)).filter(deleted_at.is_null());
+
+        match mode {
+            WriteMode::Execute => conn.transaction(|conn| {
+                let updated = diesel::update(target)
+                    .set((patch, version.eq(version + 1)))
+                    .get_results::<Bike>(conn)?;
+                self.check_written(conn, &updated)?;
+                Ok(updated.len())
+            }),
+            WriteMode::DryRun => target.count().get_result::<i64>(&mut conn).map(|count| count as usize),
+        }
+    }
+
+    /// Deletes all bikes matching the filters
+    ///
+    /// Like `delete`, the bikes are only marked as deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `conditions` - A vector of Condition enums selecting the bikes to delete
+    /// * `mode` - Whether to apply the delete or only count the matching bikes
+    ///
+    /// # Returns
+    ///
+    /// The number of affected rows or a database error
+    pub fn delete_where(&self, conditions: Vec<BikeCondition>, mode: WriteMode) -> QueryResult<usize> {
+        self.validate(&conditions)?;
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+
+        let filtered_ids = create_filtered_query(self.with_policy_condition(conditions, Action::Delete), self.deleted, self.tenant.as_deref()).select(id);
+        let target = bike.filter(id.eq_any(filtered_ids)).filter(deleted_at.is_null());
+
+        match mode {
+            WriteMode::Execute => diesel::update(target)
+                .set((deleted_at.eq(now), version.eq(version + 1)))
+                .execute(&mut conn),
+            WriteMode::DryRun => target.count().get_result::<i64>(&mut conn).map(|count| count as usize),
+        }
+    }
+
+    /// Runs a saved filter
+    ///
+    /// The stored conditions go through `find_with_filters`, so they are
+    /// validated and scoped like conditions passed in directly.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter_id` - The ID of a saved filter of bikes
+    ///
+    /// # Returns
+    ///
+    /// A vector of bikes matching the saved filter, or a deserialization error
+    /// if the filter selects another entity or can't be read
+    pub fn run_saved_filter(&self, filter_id: &str) -> QueryResult<Vec<Bike>> {
+        let conditions = SavedFilterDAL::scoped(self.pool.clone(), self.actor.as_deref(), self.tenant.as_deref()).load_conditions(filter_id)?;
+        self.find_with_filters(conditions)
     }
 
     /// Finds bikes with filters using Condition
@@ -167,14 +843,218 @@ impl BikeDAL {
     ///
     /// A vector of bikes matching the filters or a database error
     pub fn find_with_filters(&self, conditions: Vec<BikeCondition>) -> QueryResult<Vec<Bike>> {
+        self.validate(&conditions)?;
         let mut conn = self.pool.get().expect("Couldn't get DB connection");
         
-        let query = create_filtered_query(conditions);
+        let query = create_filtered_query(self.with_policy_condition(conditions, Action::Read), self.deleted, self.tenant.as_deref());
 
         query
             .select(bike::all_columns())
             .distinct()
             .load::<Bike>(&mut conn)
-    }  
+    }
+
+    /// Finds bikes with filters, projected together with their owner and color names
+    ///
+    /// # Arguments
+    ///
+    /// * `conditions` - A vector of Condition enums for filtering
+    ///
+    /// # Returns
+    ///
+    /// A vector of bike rows matching the filters or a database error
+    pub fn find_rows_with_filters(&self, conditions: Vec<BikeCondition>) -> QueryResult<Vec<BikeRow>> {
+        self.validate(&conditions)?;
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+
+        let query = create_filtered_query(self.with_policy_condition(conditions, Action::Read), self.deleted, self.tenant.as_deref());
+
+        query
+            .select((
+                id,
+                name,
+                owner_id,
+                color_id,
+                schema::person::dsl::name.nullable(),
+                schema::color::dsl::name.nullable(),
+            ))
+            .distinct()
+            .load::<BikeRow>(&mut conn)
+    }
+
+    /// Finds bikes with filters and eagerly loads the requested relations
+    ///
+    /// Relations are loaded with one query each, regardless of the number of bikes found.
+    ///
+    /// # Arguments
+    ///
+    /// * `conditions` - A vector of Condition enums for filtering
+    /// * `includes` - The relations to load, e.g. `Include::Color | Include::Trips`
+    ///
+    /// # Returns
+    ///
+    /// A vector of bikes with their relations or a database error
+    pub fn find_with_filters_including(
+        &self,
+        conditions: Vec<BikeCondition>,
+        includes: impl Into<Includes>,
+    ) -> QueryResult<Vec<BikeWithRelations>> {
+        self.validate(&conditions)?;
+        let includes = includes.into();
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+
+        let bikes = create_filtered_query(self.with_policy_condition(conditions, Action::Read), self.deleted, self.tenant.as_deref())
+            .select(bike::all_columns())
+            .distinct()
+            .load::<Bike>(&mut conn)?;
+
+        // belonging_to and grouped_by only load children of a parent, like the trips below.
+        // Colors and owners are parents of the bike that many bikes may share, so they're
+        // loaded by ID once and looked up per bike
+        let colors: HashMap<String, Color> = if includes.contains(Include::Color) {
+            let color_ids: Vec<&str> = bikes.iter().filter_map(|b| b.color_id.as_deref()).collect();
+            schema::color::table
+                .filter(schema::color::dsl::id.eq_any(color_ids))
+                .filter(schema::color::dsl::deleted_at.is_null())
+                .filter(tenant_condition!(self.tenant.as_deref(), schema::color::table, schema::color::dsl::tenant_id))
+                .load::<Color>(&mut conn)?
+                .into_iter()
+                .map(|c| (c.id.clone(), c))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        let owners: HashMap<String, Person> = if includes.contains(Include::Owner) {
+            let person_ids: Vec<&str> = bikes.iter().filter_map(|b| b.owner_id.as_deref()).collect();
+            schema::person::table
+                .filter(schema::person::dsl::id.eq_any(person_ids))
+                .filter(schema::person::dsl::deleted_at.is_null())
+                .filter(tenant_condition!(self.tenant.as_deref(), schema::person::table, schema::person::dsl::tenant_id))
+                .load::<Person>(&mut conn)?
+                .into_iter()
+                .map(|p| (p.id.clone(), p))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        let trips: Vec<Vec<BikeTrip>> = if includes.contains(Include::Trips) {
+            BikeTrip::belonging_to(&bikes)
+                .filter(schema::bike_trip::dsl::deleted_at.is_null())
+                .filter(tenant_condition!(self.tenant.as_deref(), schema::bike_trip::table, schema::bike_trip::dsl::tenant_id))
+                .load::<BikeTrip>(&mut conn)?
+                .grouped_by(&bikes)
+        } else {
+            vec![Vec::new(); bikes.len()]
+        };
+
+        Ok(bikes
+            .into_iter()
+            .zip(trips)
+            // Like the joins, relations in another tenant than the bike read as missing
+            .map(|(b, trips)| BikeWithRelations {
+                color: b.color_id.as_ref().and_then(|c| colors.get(c)).filter(|c| c.tenant_id == b.tenant_id).cloned(),
+                owner: b.owner_id.as_ref().and_then(|o| owners.get(o)).filter(|o| o.tenant_id == b.tenant_id).cloned(),
+                trips: trips.into_iter().filter(|trip| trip.tenant_id == b.tenant_id).collect(),
+                bike: b,
+            })
+            .collect())
+    }
+
+    /// Counts bikes matching the filters per group
+    ///
+    /// # Arguments
+    ///
+    /// * `conditions` - A vector of Condition enums for filtering
+    /// * `group` - The relation to group the bikes by
+    ///
+    /// # Returns
+    ///
+    /// The buckets ordered by descending count, bikes without the grouped
+    /// relation are counted in a bucket with a `None` key, or a database error
+    pub fn count_by(&self, conditions: Vec<BikeCondition>, group: BikeGroup) -> QueryResult<Vec<Bucket>> {
+        self.validate(&conditions)?;
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+
+        let filtered_ids = create_filtered_query(self.with_policy_condition(conditions, Action::Read), self.deleted, self.tenant.as_deref()).select(id);
+
+        match group {
+            BikeGroup::Color => {
+                let counts = bike
+                    .filter(id.eq_any(filtered_ids))
+                    .group_by(color_id)
+                    .select((color_id, count_star()))
+                    .order_by(count_star().desc())
+                    .load::<(Option<String>, i64)>(&mut conn)?;
+                let keys: Vec<&str> = counts.iter().filter_map(|(key, _)| key.as_deref()).collect();
+                let labels: HashMap<String, String> = schema::color::table
+                    .filter(schema::color::dsl::id.eq_any(keys))
+                    .filter(tenant_condition!(self.tenant.as_deref(), schema::color::table, schema::color::dsl::tenant_id))
+                    .select((schema::color::dsl::id, schema::color::dsl::name))
+                    .load(&mut conn)?
+                    .into_iter()
+                    .collect();
+                Ok(Bucket::labelled(counts, &labels))
+            }
+            BikeGroup::Owner => {
+                let counts = bike
+                    .filter(id.eq_any(filtered_ids))
+                    .group_by(owner_id)
+                    .select((owner_id, count_star()))
+                    .order_by(count_star().desc())
+                    .load::<(Option<String>, i64)>(&mut conn)?;
+                let keys: Vec<&str> = counts.iter().filter_map(|(key, _)| key.as_deref()).collect();
+                let labels: HashMap<String, String> = schema::person::table
+                    .filter(schema::person::dsl::id.eq_any(keys))
+                    .filter(tenant_condition!(self.tenant.as_deref(), schema::person::table, schema::person::dsl::tenant_id))
+                    .select((schema::person::dsl::id, schema::person::dsl::name))
+                    .load(&mut conn)?
+                    .into_iter()
+                    .collect();
+                Ok(Bucket::labelled(counts, &labels))
+            }
+        }
+    }
+
+    /// Renders the SQL that `find_with_filters` would run, for debugging and logging
+    ///
+    /// # Arguments
+    ///
+    /// * `conditions` - A vector of Condition enums for filtering
+    ///
+    /// # Returns
+    ///
+    /// The generated SQL followed by its bind values, or the error `find_with_filters`
+    /// would fail with for conditions on fields beyond the clearance
+    pub fn to_sql_string(&self, conditions: Vec<BikeCondition>) -> QueryResult<String> {
+        self.validate(&conditions)?;
+        let query = create_filtered_query(self.with_policy_condition(conditions, Action::Read), self.deleted, self.tenant.as_deref())
+            .select(bike::all_columns())
+            .distinct();
+
+        Ok(diesel::debug_query::<Pg, _>(&query).to_string())
+    }
+
+    /// Explains the query that `find_with_filters` would run
+    ///
+    /// # Arguments
+    ///
+    /// * `conditions` - A vector of Condition enums for filtering
+    /// * `analyze` - Whether to run `EXPLAIN ANALYZE`, which executes the query
+    ///
+    /// # Returns
+    ///
+    /// A summary of the query plan or a database error
+    pub fn explain_with_filters(&self, conditions: Vec<BikeCondition>, analyze: bool) -> QueryResult<QueryPlan> {
+        self.validate(&conditions)?;
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+
+        let query = create_filtered_query(self.with_policy_condition(conditions, Action::Read), self.deleted, self.tenant.as_deref())
+            .select(bike::all_columns())
+            .distinct();
+
+        explain(&mut conn, query, analyze)
+    }
 }
 
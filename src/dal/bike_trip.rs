@@ -1,49 +1,564 @@
+use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::r2d2::{self, ConnectionManager};
-use crate::models::bike_trip::{BikeTrip, NewBikeTrip};
+use diesel::dsl::{count_star, now, sql};
+use chrono::{DateTime, Utc};
+use diesel::upsert::{excluded, on_constraint};
+use diesel::helper_types::IntoBoxed;
+use diesel::sql_types::{Bool, Float8, Nullable};
+use std::collections::{BTreeSet, HashMap};
+use std::io::Read;
+use crate::gpx;
+use crate::models::bike_trip::{BikeTrip, BikeTripColumn, BikeTripCondition, BikeTripGroup, BikeTripMetric, BikeTripPatch, NewBikeTrip};
+use crate::models::common::{Aggregate, AggregateBucket, Bucket, FieldAccess, DeletedMode, StringFilter, Upsert};
+use crate::models::trip_point::{NewTripPoint, TripPoint};
+use crate::models::AndOr;
+use crate::schema;
 use crate::schema::bike_trip::dsl::*;
+use crate::dal::explain::{explain, QueryPlan};
+use crate::dal::geo;
+use crate::dal::history::write_connection;
+use crate::dal::saved_filter::SavedFilterDAL;
+use crate::dal::policy::{Action, PolicyDenied};
+use crate::dal::{batch_size, deleted_filter, ensure_referenced, string_filter, tenant_condition, BikeDAL, UpdateError};
 
 type Pool = r2d2::Pool<ConnectionManager<PgConnection>>;
 
+type ConditionSource = schema::bike_trip::dsl::bike_trip;
+type BoxedCondition = Box<dyn BoxableExpression<ConditionSource, Pg, SqlType = Nullable<Bool>>>;
+type QuerySource = schema::bike_trip::dsl::bike_trip;
+pub(super) type BoxedQuery = IntoBoxed<'static, QuerySource, Pg>;
+
+/// Name of trips imported from a GPX file that names neither its track nor itself
+const UNNAMED_TRACK: &str = "Untitled trip";
+
 pub struct BikeTripDAL {
     pool: Pool,
+    deleted: DeletedMode,
+    actor: Option<String>,
+    tenant: Option<String>,
+    clearance: FieldAccess,
+    bikes: BikeDAL,
 }
 
 impl BikeTripDAL {
     pub fn new(pool: Pool) -> Self {
-        BikeTripDAL { pool }
+        BikeTripDAL {
+            bikes: BikeDAL::new(pool.clone()),
+            pool,
+            deleted: DeletedMode::Exclude,
+            actor: None,
+            tenant: None,
+            clearance: FieldAccess::Public,
+        }
+    }
+
+    pub fn include_deleted(mut self) -> Self {
+        self.deleted = DeletedMode::Include;
+        self
+    }
+
+    pub fn only_deleted(mut self) -> Self {
+        self.deleted = DeletedMode::Only;
+        self
+    }
+
+    pub fn with_actor(mut self, actor_id: &str) -> Self {
+        self.actor = Some(actor_id.to_string());
+        self
+    }
+
+    pub fn for_tenant(mut self, tenant: &str) -> Self {
+        self.tenant = Some(tenant.to_string());
+        self.bikes = self.bikes.for_tenant(tenant);
+        self
+    }
+
+    /// Matches the bikes of conditions through `bikes` instead, so its read policy applies
+    pub(super) fn scoped_by(mut self, bikes: BikeDAL) -> Self {
+        self.bikes = bikes;
+        self
+    }
+
+    pub fn with_field_access(mut self, clearance: FieldAccess) -> Self {
+        self.clearance = clearance;
+        self
+    }
+
+    fn validate(&self, conditions: &[BikeTripCondition]) -> QueryResult<()> {
+        conditions
+            .iter()
+            .try_for_each(|condition| condition.validate(self.clearance))
+            .map_err(|denied| diesel::result::Error::QueryBuilderError(Box::new(denied)))?;
+        conditions
+            .iter()
+            .try_for_each(BikeTripCondition::validate_geo)
+            .map_err(|invalid| diesel::result::Error::QueryBuilderError(Box::new(invalid)))
+    }
+
+    fn tenant_condition(&self) -> BoxedCondition {
+        tenant_condition!(self.tenant.as_deref(), ConditionSource, tenant_id)
+    }
+
+    /// The trips of the tenant the bike policy allows `action` on, trips take the
+    /// policy of their bike and trips without a bike are always allowed
+    fn scope(&self, action: Action) -> BoxedCondition {
+        let tenant = self.tenant_condition();
+        match self.bikes.allowed_ids(action) {
+            Some(allowed) => Box::new(tenant.and(bike_id.is_null().or(bike_id.eq_any(allowed)).nullable())),
+            None => tenant,
+        }
+    }
+
+    /// Whether the trip exists in the tenant, but the bike policy doesn't allow `action` on it
+    fn denied(&self, conn: &mut PgConnection, bike_trip_id: &str, action: Action) -> QueryResult<bool> {
+        if self.bikes.allowed_ids(action).is_none() {
+            return Ok(false);
+        }
+        let exists = diesel::select(diesel::dsl::exists(bike_trip.find(bike_trip_id).filter(self.tenant_condition())))
+            .get_result::<bool>(conn)?;
+        let allowed = diesel::select(diesel::dsl::exists(bike_trip.find(bike_trip_id).filter(self.scope(action))))
+            .get_result::<bool>(conn)?;
+        Ok(exists && !allowed)
+    }
+
+    /// Whether the bike policy allows updating the bikes of all of the written trips
+    fn allows<'a>(&self, conn: &mut PgConnection, written: impl IntoIterator<Item = &'a BikeTrip>) -> QueryResult<bool> {
+        let bike_ids: BTreeSet<String> = written.into_iter().filter_map(|t| t.bike_id.clone()).collect();
+        self.bikes.allows(conn, &bike_ids.into_iter().collect::<Vec<_>>(), Action::Update)
+    }
+
+    /// Fails with a `PolicyDenied` unless the bike policy allows updating the bikes
+    /// of all of the written trips, so trips can't be put on bikes outside the actor's scope
+    fn check_written<'a>(&self, conn: &mut PgConnection, written: impl IntoIterator<Item = &'a BikeTrip>) -> QueryResult<()> {
+        if self.allows(conn, written)? {
+            Ok(())
+        } else {
+            Err(diesel::result::Error::QueryBuilderError(Box::new(PolicyDenied)))
+        }
+    }
+
+    /// Fails with a `PolicyDenied` if the trip exists, but the bike policy doesn't allow `action` on it
+    fn check_denied(&self, conn: &mut PgConnection, bike_trip_id: &str, action: Action) -> QueryResult<()> {
+        if self.denied(conn, bike_trip_id, action)? {
+            Err(diesel::result::Error::QueryBuilderError(Box::new(PolicyDenied)))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Fails with a foreign key violation unless the referenced bikes exist in the
+    /// tenant, and with `live` also aren't deleted
+    fn check_references<'a>(&self, conn: &mut PgConnection, bike_ids: impl IntoIterator<Item = &'a str>, live: bool) -> QueryResult<()> {
+        let bikes: BTreeSet<&str> = bike_ids.into_iter().collect();
+        if bikes.is_empty() {
+            return Ok(());
+        }
+        let query = schema::bike::table
+            .filter(schema::bike::id.eq_any(bikes.iter().copied().collect::<Vec<_>>()))
+            .filter(tenant_condition!(self.tenant.as_deref(), schema::bike::table, schema::bike::tenant_id))
+            .into_boxed();
+        let query = if live { query.filter(schema::bike::deleted_at.is_null()) } else { query };
+        let found = query.count().get_result::<i64>(conn)?;
+        ensure_referenced("bike", &bikes, found)
     }
 
     pub fn create(&self, new_bike_trip: &NewBikeTrip) -> QueryResult<BikeTrip> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        conn.transaction(|conn| {
+            self.check_references(conn, new_bike_trip.bike_id.as_deref(), false)?;
+            let created = diesel::insert_into(bike_trip)
+                .values(new_bike_trip)
+                .get_result::<BikeTrip>(conn)?;
+            self.check_written(conn, [&created])?;
+            Ok(created)
+        })
+    }
+
+    pub fn create_many(&self, new_bike_trips: &[NewBikeTrip]) -> QueryResult<Vec<BikeTrip>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        conn.transaction(|conn| {
+            self.check_references(conn, new_bike_trips.iter().filter_map(|t| t.bike_id.as_deref()), false)?;
+            let mut created = Vec::with_capacity(new_bike_trips.len());
+            for batch in new_bike_trips.chunks(batch_size(3)) {
+                created.extend(diesel::insert_into(bike_trip).values(batch).get_results::<BikeTrip>(conn)?);
+            }
+            self.check_written(conn, &created)?;
+            Ok(created)
+        })
+    }
+
+    /// Creates a trip with `trip_bike_id` from a GPX file, along with its track points
+    ///
+    /// The trip is named after the track and gets the track's distance, duration,
+    /// moving time and elevation gain. The trip and its points are inserted in one
+    /// transaction. A file that isn't valid GPX fails with a
+    /// `DeserializationError` holding the `GpxError`, a bike that isn't live in
+    /// the tenant with a foreign key violation and a bike the policy doesn't allow
+    /// updating with a `PolicyDenied`.
+    pub fn import_gpx(&self, trip_bike_id: &str, reader: impl Read) -> QueryResult<BikeTrip> {
+        let track = gpx::parse(reader).map_err(|error| diesel::result::Error::DeserializationError(Box::new(error)))?;
+        let summary = track.summary();
+        let new_bike_trip = NewBikeTrip::new(track.name.as_deref().unwrap_or(UNNAMED_TRACK), Some(trip_bike_id));
+        let points: Vec<NewTripPoint> = track
+            .points()
+            .enumerate()
+            .map(|(index, point)| NewTripPoint {
+                trip_id: new_bike_trip.id.clone(),
+                seq: index as i32,
+                recorded_at: point.time,
+                latitude: point.latitude,
+                longitude: point.longitude,
+                elevation: point.elevation,
+            })
+            .collect();
+
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        conn.transaction(|conn| {
+            self.check_references(conn, Some(trip_bike_id), true)?;
+            let created = diesel::insert_into(bike_trip)
+                .values((
+                    &new_bike_trip,
+                    started_at.eq(summary.started_at),
+                    distance_meters.eq(summary.distance_meters),
+                    duration_seconds.eq(summary.duration_seconds),
+                    elevation_gain_meters.eq(summary.elevation_gain_meters),
+                    moving_seconds.eq(summary.moving_seconds),
+                ))
+                .get_result::<BikeTrip>(conn)?;
+            self.check_written(conn, [&created])?;
+            for batch in points.chunks(batch_size(6)) {
+                diesel::insert_into(schema::trip_point::table).values(batch).execute(conn)?;
+            }
+            Ok(created)
+        })
+    }
+
+    /// The track points of a trip in recording order, empty for trips without a recorded track
+    pub fn find_points(&self, bike_trip_id: &str) -> QueryResult<Vec<TripPoint>> {
         let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        diesel::insert_into(bike_trip)
-            .values(new_bike_trip)
-            .get_result(&mut conn)
+        let visible_trips = create_filtered_query(vec![], self.deleted, self.tenant.as_deref(), &self.bikes).select(id);
+        schema::trip_point::table
+            .filter(schema::trip_point::dsl::trip_id.eq(bike_trip_id))
+            .filter(schema::trip_point::dsl::trip_id.eq_any(visible_trips))
+            .order_by(schema::trip_point::dsl::seq)
+            .load::<TripPoint>(&mut conn)
+    }
+
+    pub fn upsert_many(&self, new_bike_trips: &[NewBikeTrip], upsert: &Upsert<BikeTripColumn>) -> QueryResult<Vec<BikeTrip>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        let constraint = upsert.conflict_target.constraint_name("bike_trip_pkey");
+        conn.transaction(|conn| {
+            self.check_references(conn, new_bike_trips.iter().filter_map(|t| t.bike_id.as_deref()), false)?;
+            let mut upserted = Vec::with_capacity(new_bike_trips.len());
+            for batch in new_bike_trips.chunks(batch_size(3)) {
+                let query = diesel::insert_into(bike_trip)
+                    .values(batch)
+                    .on_conflict(on_constraint(constraint));
+                let rows = if upsert.update_columns.is_empty() {
+                    query.do_nothing().get_results::<BikeTrip>(conn)?
+                } else {
+                    let statement = query.do_update().set((
+                        upsert.updates(BikeTripColumn::Name).then(|| name.eq(excluded(name))),
+                        upsert.updates(BikeTripColumn::BikeId).then(|| bike_id.eq(excluded(bike_id))),
+                        version.eq(version + 1),
+                    ));
+                    // Conflicts with trips of other tenants or on bikes outside the scope are skipped
+                    diesel::query_dsl::methods::FilterDsl::filter(statement, self.scope(Action::Update))
+                        .get_results::<BikeTrip>(conn)?
+                };
+                upserted.extend(rows);
+            }
+            self.check_written(conn, &upserted)?;
+            Ok(upserted)
+        })
     }
 
     pub fn find_by_id(&self, bike_trip_id: &str) -> QueryResult<BikeTrip> {
         let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        bike_trip.find(bike_trip_id).first(&mut conn)
+        deleted_filter!(bike_trip.find(bike_trip_id).filter(self.tenant_condition()).into_boxed(), self.deleted, deleted_at).first(&mut conn)
     }
 
     pub fn find_all(&self) -> QueryResult<Vec<BikeTrip>> {
         let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        bike_trip.load::<BikeTrip>(&mut conn)
+        deleted_filter!(bike_trip.filter(self.tenant_condition()).into_boxed(), self.deleted, deleted_at).load::<BikeTrip>(&mut conn)
     }
 
-    pub fn update(&self, bike_trip_id: &str, updated_bike_trip: &BikeTrip) -> QueryResult<BikeTrip> {
-        let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        diesel::update(bike_trip.find(bike_trip_id))
-            .set((
-                name.eq(&updated_bike_trip.name),
-                bike_id.eq(&updated_bike_trip.bike_id)
-            ))
-            .get_result(&mut conn)
+    pub fn update(&self, bike_trip_id: &str, updated_bike_trip: &BikeTrip) -> Result<BikeTrip, UpdateError<BikeTrip>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        conn.transaction(|conn| {
+            if self.denied(conn, bike_trip_id, Action::Update)? {
+                return Err(UpdateError::Denied);
+            }
+            let updated = diesel::update(
+                bike_trip
+                    .find(bike_trip_id)
+                    .filter(self.scope(Action::Update))
+                    .filter(deleted_at.is_null())
+                    .filter(version.eq(updated_bike_trip.version)),
+            )
+                .set((
+                    name.eq(&updated_bike_trip.name),
+                    bike_id.eq(&updated_bike_trip.bike_id),
+                    version.eq(version + 1),
+                ))
+                .get_result(conn)
+                .optional()?;
+
+            match updated {
+                // Checked after the write, so trips outside the scope still aren't found
+                Some(updated) => {
+                    self.check_references(conn, updated_bike_trip.bike_id.as_deref(), false)?;
+                    // The actor may not move the trip onto a bike outside their scope
+                    if !self.allows(conn, [&updated])? {
+                        return Err(UpdateError::Denied);
+                    }
+                    Ok(updated)
+                }
+                None => {
+                    let current = bike_trip.find(bike_trip_id).filter(self.scope(Action::Update)).filter(deleted_at.is_null()).first(conn)?;
+                    Err(UpdateError::Conflict(Box::new(current)))
+                }
+            }
+        })
+    }
+
+    pub fn update_partial(&self, bike_trip_id: &str, patch: &BikeTripPatch) -> Result<BikeTrip, UpdateError<BikeTrip>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        conn.transaction(|conn| {
+            if self.denied(conn, bike_trip_id, Action::Update)? {
+                return Err(UpdateError::Denied);
+            }
+            let updated = diesel::update(bike_trip.find(bike_trip_id).filter(self.scope(Action::Update)).filter(deleted_at.is_null()))
+                .set((patch, version.eq(version + 1)))
+                .get_result(conn)?;
+            // Checked after the write, so trips outside the scope still aren't found
+            self.check_references(conn, patch.bike_id.iter().flatten().map(String::as_str), false)?;
+            if !self.allows(conn, [&updated])? {
+                return Err(UpdateError::Denied);
+            }
+            Ok(updated)
+        })
     }
 
     pub fn delete(&self, bike_trip_id: &str) -> QueryResult<usize> {
-        let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        diesel::delete(bike_trip.find(bike_trip_id))
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        self.check_denied(&mut conn, bike_trip_id, Action::Delete)?;
+        diesel::update(bike_trip.find(bike_trip_id).filter(self.scope(Action::Delete)).filter(deleted_at.is_null()))
+            .set((deleted_at.eq(now), version.eq(version + 1)))
             .execute(&mut conn)
     }
+
+    pub fn restore(&self, bike_trip_id: &str) -> QueryResult<BikeTrip> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        self.check_denied(&mut conn, bike_trip_id, Action::Update)?;
+        diesel::update(bike_trip.find(bike_trip_id).filter(self.scope(Action::Update)).filter(deleted_at.is_not_null()))
+            .set((deleted_at.eq(None::<DateTime<Utc>>), version.eq(version + 1)))
+            .get_result(&mut conn)
+    }
+
+    pub fn run_saved_filter(&self, filter_id: &str) -> QueryResult<Vec<BikeTrip>> {
+        let conditions = SavedFilterDAL::scoped(self.pool.clone(), self.actor.as_deref(), self.tenant.as_deref()).load_conditions(filter_id)?;
+        self.find_with_filters(conditions)
+    }
+
+    pub fn find_with_filters(&self, conditions: Vec<BikeTripCondition>) -> QueryResult<Vec<BikeTrip>> {
+        self.validate(&conditions)?;
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+
+        let query = create_filtered_query(conditions, self.deleted, self.tenant.as_deref(), &self.bikes);
+
+        query.load::<BikeTrip>(&mut conn)
+    }
+
+    // Render the SQL of find_with_filters, for debugging and logging
+    pub fn to_sql_string(&self, conditions: Vec<BikeTripCondition>) -> QueryResult<String> {
+        self.validate(&conditions)?;
+        let query = create_filtered_query(conditions, self.deleted, self.tenant.as_deref(), &self.bikes);
+
+        Ok(diesel::debug_query::<Pg, _>(&query).to_string())
+    }
+
+    // Explain find_with_filters, `analyze` executes the query
+    pub fn explain_with_filters(&self, conditions: Vec<BikeTripCondition>, analyze: bool) -> QueryResult<QueryPlan> {
+        self.validate(&conditions)?;
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+
+        let query = create_filtered_query(conditions, self.deleted, self.tenant.as_deref(), &self.bikes);
+
+        explain(&mut conn, query, analyze)
+    }
+
+    // Count the filtered trips per group, trips without a bike or owner end up in a None bucket
+    pub fn count_by(&self, conditions: Vec<BikeTripCondition>, group: BikeTripGroup) -> QueryResult<Vec<Bucket>> {
+        self.validate(&conditions)?;
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+
+        let filtered_ids = create_filtered_query(conditions, self.deleted, self.tenant.as_deref(), &self.bikes).select(id);
+
+        let counts = match group {
+            BikeTripGroup::Bike => bike_trip
+                .filter(id.eq_any(filtered_ids))
+                .group_by(bike_id)
+                .select((bike_id, count_star()))
+                .order_by(count_star().desc())
+                .load::<(Option<String>, i64)>(&mut conn)?,
+            BikeTripGroup::Owner => bike_trip
+                .left_join(owning_bike())
+                .filter(id.eq_any(filtered_ids))
+                .group_by(schema::bike::dsl::owner_id)
+                .select((schema::bike::dsl::owner_id.nullable(), count_star()))
+                .order_by(count_star().desc())
+                .load::<(Option<String>, i64)>(&mut conn)?,
+        };
+        let labels = self.group_labels(&mut conn, group, counts.iter().filter_map(|(key, _)| key.as_deref()))?;
+        Ok(Bucket::labelled(counts, &labels))
+    }
+
+    // Like count_by, also combining `metric` of each group's trips; trips without a value are skipped
+    pub fn aggregate_by(
+        &self,
+        conditions: Vec<BikeTripCondition>,
+        group: BikeTripGroup,
+        metric: BikeTripMetric,
+        aggregate: Aggregate,
+    ) -> QueryResult<Vec<AggregateBucket>> {
+        self.validate(&conditions)?;
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+
+        let filtered_ids = create_filtered_query(conditions, self.deleted, self.tenant.as_deref(), &self.bikes).select(id);
+        let value = aggregate_sql(metric, aggregate);
+
+        let rows = match group {
+            BikeTripGroup::Bike => bike_trip
+                .filter(id.eq_any(filtered_ids))
+                .group_by(bike_id)
+                .select((bike_id, count_star(), value))
+                .order_by(count_star().desc())
+                .load::<(Option<String>, i64, Option<f64>)>(&mut conn)?,
+            BikeTripGroup::Owner => bike_trip
+                .left_join(owning_bike())
+                .filter(id.eq_any(filtered_ids))
+                .group_by(schema::bike::dsl::owner_id)
+                .select((schema::bike::dsl::owner_id.nullable(), count_star(), value))
+                .order_by(count_star().desc())
+                .load::<(Option<String>, i64, Option<f64>)>(&mut conn)?,
+        };
+        let labels = self.group_labels(&mut conn, group, rows.iter().filter_map(|(key, _, _)| key.as_deref()))?;
+        Ok(AggregateBucket::labelled(rows, &labels))
+    }
+
+    // Names of the bikes or owners the buckets are keyed by, within the tenant
+    fn group_labels<'a>(
+        &self,
+        conn: &mut PgConnection,
+        group: BikeTripGroup,
+        keys: impl Iterator<Item = &'a str>,
+    ) -> QueryResult<HashMap<String, String>> {
+        let keys: Vec<&str> = keys.collect();
+        let labels: Vec<(String, String)> = match group {
+            BikeTripGroup::Bike => schema::bike::table
+                .filter(schema::bike::dsl::id.eq_any(keys))
+                .filter(tenant_condition!(self.tenant.as_deref(), schema::bike::table, schema::bike::dsl::tenant_id))
+                .select((schema::bike::dsl::id, schema::bike::dsl::name))
+                .load(conn)?,
+            BikeTripGroup::Owner => schema::person::table
+                .filter(schema::person::dsl::id.eq_any(keys))
+                .filter(tenant_condition!(self.tenant.as_deref(), schema::person::table, schema::person::dsl::tenant_id))
+                .select((schema::person::dsl::id, schema::person::dsl::name))
+                .load(conn)?,
+        };
+        Ok(labels.into_iter().collect())
+    }
+}
+
+/// The bike of a trip, if it's in the trip's tenant
+#[diesel::dsl::auto_type]
+fn owning_bike() -> _ {
+    schema::bike::table.on(schema::bike::dsl::id
+        .nullable()
+        .eq(bike_id)
+        .and(schema::bike::dsl::tenant_id.is_not_distinct_from(tenant_id)))
+}
+
+/// `aggregate` over `metric` as FLOAT8, so integer columns don't sum into NUMERIC
+fn aggregate_sql(metric: BikeTripMetric, aggregate: Aggregate) -> diesel::expression::SqlLiteral<Nullable<Float8>> {
+    let column = match metric {
+        BikeTripMetric::Distance => "distance_meters",
+        BikeTripMetric::Duration => "duration_seconds",
+        BikeTripMetric::ElevationGain => "elevation_gain_meters",
+    };
+    let function = match aggregate {
+        Aggregate::Sum => "SUM",
+        Aggregate::Avg => "AVG",
+        Aggregate::Min => "MIN",
+        Aggregate::Max => "MAX",
+    };
+    sql::<Nullable<Float8>>(&format!("{}(bike_trip.{})::FLOAT8", function, column))
+}
+
+impl BikeTripCondition {
+    fn into_boxed_condition(self, bikes: &BikeDAL) -> Option<BoxedCondition> {
+        Some(match self {
+            BikeTripCondition::name(f) => string_filter!(f, schema::bike_trip::dsl::name),
+            BikeTripCondition::bike(condition) => {
+                // Inner statement, reusing conditions defined in bike and the bike read policy
+                let inner_statement = bikes.nested_query(vec![condition], DeletedMode::Exclude);
+                Box::new(
+                    schema::bike_trip::dsl::bike_id
+                        .eq_any(inner_statement.select(schema::bike::dsl::id.nullable()))
+                        .nullable(),
+                )
+            }
+            BikeTripCondition::track(filter) => {
+                let points = schema::trip_point::table
+                    .into_boxed()
+                    .filter(geo::point_condition(filter))
+                    .select(schema::trip_point::dsl::trip_id);
+                Box::new(id.eq_any(points).nullable())
+            }
+            BikeTripCondition::start(filter) => {
+                let points = schema::trip_point::table
+                    .into_boxed()
+                    .filter(geo::point_condition(filter))
+                    .filter(schema::trip_point::dsl::seq.eq(0))
+                    .select(schema::trip_point::dsl::trip_id);
+                Box::new(id.eq_any(points).nullable())
+            }
+            BikeTripCondition::And(conditions) => create_filter(conditions, AndOr::And, bikes)?,
+            BikeTripCondition::Or(conditions) => create_filter(conditions, AndOr::Or, bikes)?,
+        })
+    }
+}
+
+fn create_filter(conditions: Vec<BikeTripCondition>, and_or: AndOr, bikes: &BikeDAL) -> Option<BoxedCondition> {
+    conditions
+        .into_iter()
+        .filter_map::<BoxedCondition, _>(|condition| condition.into_boxed_condition(bikes))
+        .fold(None, |boxed_conditions, boxed_condition| {
+            Some(match boxed_conditions {
+                Some(bc) => match and_or {
+                    AndOr::And => Box::new(bc.and(boxed_condition)),
+                    AndOr::Or => Box::new(bc.or(boxed_condition)),
+                },
+                None => boxed_condition,
+            })
+        })
+}
+
+pub(super) fn create_filtered_query(
+    conditions: Vec<BikeTripCondition>,
+    deleted: DeletedMode,
+    tenant: Option<&str>,
+    bikes: &BikeDAL,
+) -> BoxedQuery {
+    let boxed_query = schema::bike_trip::table
+        .into_boxed()
+        .filter(tenant_condition!(tenant, ConditionSource, schema::bike_trip::dsl::tenant_id));
+    let boxed_query = deleted_filter!(boxed_query, deleted, schema::bike_trip::dsl::deleted_at);
+
+    match create_filter(conditions, AndOr::And, bikes) {
+        Some(boxed_conditions) => boxed_query.filter(boxed_conditions),
+        None => boxed_query,
+    }
 }
\ No newline at end of file
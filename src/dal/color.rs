@@ -1,46 +1,219 @@
 use diesel::prelude::*;
+use diesel::pg::Pg;
+use diesel::sql_types::{Bool, Nullable};
 use diesel::r2d2::{self, ConnectionManager};
-use crate::models::color::{Color, NewColor};
+use diesel::dsl::now;
+use diesel::upsert::{excluded, on_constraint};
+use chrono::{DateTime, Utc};
+use crate::models::color::{Color, ColorColumn, ColorPatch, NewColor};
+use crate::models::common::{DeleteStrategy, DeleteSummary, DeletedMode, Upsert};
+use crate::dal::history::write_connection;
+use crate::dal::{batch_size, deleted_filter, tenant_condition, Action, BikeDAL, DeleteError, UpdateError};
+use crate::schema;
 use crate::schema::color::dsl::*;
 
 type Pool = r2d2::Pool<ConnectionManager<PgConnection>>;
 
+type TenantCondition = Box<dyn BoxableExpression<color, Pg, SqlType = Nullable<Bool>>>;
+
+/// Colors are shared by all bikes of a tenant, so no row policy applies to them
 pub struct ColorDAL {
     pool: Pool,
+    deleted: DeletedMode,
+    actor: Option<String>,
+    tenant: Option<String>,
+    bikes: BikeDAL,
 }
 
 impl ColorDAL {
     pub fn new(pool: Pool) -> Self {
-        ColorDAL { pool }
+        ColorDAL {
+            bikes: BikeDAL::new(pool.clone()),
+            pool,
+            deleted: DeletedMode::Exclude,
+            actor: None,
+            tenant: None,
+        }
+    }
+
+    pub fn include_deleted(mut self) -> Self {
+        self.deleted = DeletedMode::Include;
+        self
+    }
+
+    pub fn only_deleted(mut self) -> Self {
+        self.deleted = DeletedMode::Only;
+        self
+    }
+
+    pub fn with_actor(mut self, actor_id: &str) -> Self {
+        self.actor = Some(actor_id.to_string());
+        self
+    }
+
+    pub fn for_tenant(mut self, tenant: &str) -> Self {
+        self.tenant = Some(tenant.to_string());
+        self.bikes = self.bikes.for_tenant(tenant);
+        self
+    }
+
+    /// Checks writes to the bikes of deleted colors against the policy of `bikes`
+    pub(super) fn scoped_by(mut self, bikes: BikeDAL) -> Self {
+        self.bikes = bikes;
+        self
+    }
+
+    fn tenant_condition(&self) -> TenantCondition {
+        tenant_condition!(self.tenant.as_deref(), color, tenant_id)
     }
 
     pub fn create(&self, new_color: &NewColor) -> QueryResult<Color> {
-        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
         diesel::insert_into(color)
             .values(new_color)
             .get_result(&mut conn)
     }
 
+    pub fn create_many(&self, new_colors: &[NewColor]) -> QueryResult<Vec<Color>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        conn.transaction(|conn| {
+            let mut created = Vec::with_capacity(new_colors.len());
+            for batch in new_colors.chunks(batch_size(2)) {
+                created.extend(diesel::insert_into(color).values(batch).get_results::<Color>(conn)?);
+            }
+            Ok(created)
+        })
+    }
+
+    pub fn upsert_many(&self, new_colors: &[NewColor], upsert: &Upsert<ColorColumn>) -> QueryResult<Vec<Color>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        let constraint = upsert.conflict_target.constraint_name("color_pkey");
+        conn.transaction(|conn| {
+            let mut upserted = Vec::with_capacity(new_colors.len());
+            for batch in new_colors.chunks(batch_size(2)) {
+                let query = diesel::insert_into(color)
+                    .values(batch)
+                    .on_conflict(on_constraint(constraint));
+                let rows = if upsert.update_columns.is_empty() {
+                    query.do_nothing().get_results::<Color>(conn)?
+                } else {
+                    let statement = query.do_update().set((
+                        upsert.updates(ColorColumn::Name).then(|| name.eq(excluded(name))),
+                        version.eq(version + 1),
+                    ));
+                    // Conflicting rows of other tenants are left alone and not returned
+                    diesel::query_dsl::methods::FilterDsl::filter(statement, self.tenant_condition())
+                        .get_results::<Color>(conn)?
+                };
+                upserted.extend(rows);
+            }
+            Ok(upserted)
+        })
+    }
+
     pub fn find_by_id(&self, color_id: &str) -> QueryResult<Color> {
         let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        color.find(color_id).first(&mut conn)
+        deleted_filter!(color.find(color_id).filter(self.tenant_condition()).into_boxed(), self.deleted, deleted_at).first(&mut conn)
     }
 
     pub fn find_all(&self) -> QueryResult<Vec<Color>> {
         let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        color.load::<Color>(&mut conn)
+        deleted_filter!(color.filter(self.tenant_condition()).into_boxed(), self.deleted, deleted_at).load::<Color>(&mut conn)
     }
 
-    pub fn update(&self, color_id: &str, updated_color: &Color) -> QueryResult<Color> {
+    pub fn find_by_ids(&self, color_ids: &[String]) -> QueryResult<Vec<Color>> {
         let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        diesel::update(color.find(color_id))
-            .set(name.eq(&updated_color.name))
+        deleted_filter!(color.filter(id.eq_any(color_ids)).filter(self.tenant_condition()).into_boxed(), self.deleted, deleted_at).load::<Color>(&mut conn)
+    }
+
+    pub fn update(&self, color_id: &str, updated_color: &Color) -> Result<Color, UpdateError<Color>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        let updated = diesel::update(
+            color
+                .find(color_id)
+                .filter(self.tenant_condition())
+                .filter(deleted_at.is_null())
+                .filter(version.eq(updated_color.version)),
+        )
+            .set((name.eq(&updated_color.name), version.eq(version + 1)))
             .get_result(&mut conn)
+            .optional()?;
+
+        match updated {
+            Some(updated) => Ok(updated),
+            None => {
+                let current = color.find(color_id).filter(self.tenant_condition()).filter(deleted_at.is_null()).first(&mut conn)?;
+                Err(UpdateError::Conflict(Box::new(current)))
+            }
+        }
+    }
+
+    pub fn update_partial(&self, color_id: &str, patch: &ColorPatch) -> Result<Color, UpdateError<Color>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        Ok(diesel::update(color.find(color_id).filter(self.tenant_condition()).filter(deleted_at.is_null()))
+            .set((patch, version.eq(version + 1)))
+            .get_result(&mut conn)?)
     }
 
     pub fn delete(&self, color_id: &str) -> QueryResult<usize> {
-        let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        diesel::delete(color.find(color_id))
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        diesel::update(color.find(color_id).filter(self.tenant_condition()).filter(deleted_at.is_null()))
+            .set((deleted_at.eq(now), version.eq(version + 1)))
             .execute(&mut conn)
     }
+
+    pub fn delete_with(&self, color_id: &str, strategy: DeleteStrategy) -> Result<DeleteSummary, DeleteError> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        conn.transaction(|conn| {
+            let bikes = || {
+                schema::bike::table
+                    .filter(schema::bike::color_id.eq(color_id))
+                    .filter(tenant_condition!(self.tenant.as_deref(), schema::bike::table, schema::bike::tenant_id))
+            };
+            let live_bikes = || bikes().filter(schema::bike::deleted_at.is_null());
+
+            if strategy == DeleteStrategy::Restrict {
+                let referencing = live_bikes().count().get_result::<i64>(conn)?;
+                if referencing > 0 {
+                    return Err(DeleteError::Restricted(referencing as usize));
+                }
+            }
+
+            // The bikes are written too, so the bike policy has to allow it on each of them
+            let (dependent_ids, action) = match strategy {
+                DeleteStrategy::Restrict => (Vec::new(), Action::Delete),
+                DeleteStrategy::Cascade => (live_bikes().select(schema::bike::id).load::<String>(conn)?, Action::Delete),
+                DeleteStrategy::SetNull => (bikes().select(schema::bike::id).load::<String>(conn)?, Action::Update),
+            };
+            if !self.bikes.allows(conn, &dependent_ids, action)? {
+                return Err(DeleteError::Denied);
+            }
+
+            let deleted = diesel::update(color.find(color_id).filter(self.tenant_condition()).filter(deleted_at.is_null()))
+                .set((deleted_at.eq(now), version.eq(version + 1)))
+                .execute(conn)?;
+            if deleted == 0 {
+                return Ok(DeleteSummary::default());
+            }
+
+            let dependents = match strategy {
+                DeleteStrategy::Restrict => 0,
+                DeleteStrategy::Cascade => diesel::update(schema::bike::table.filter(schema::bike::id.eq_any(&dependent_ids)))
+                    .set((schema::bike::deleted_at.eq(now), schema::bike::version.eq(schema::bike::version + 1)))
+                    .execute(conn)?,
+                DeleteStrategy::SetNull => diesel::update(schema::bike::table.filter(schema::bike::id.eq_any(&dependent_ids)))
+                    .set((schema::bike::color_id.eq(None::<String>), schema::bike::version.eq(schema::bike::version + 1)))
+                    .execute(conn)?,
+            };
+
+            Ok(DeleteSummary { deleted, dependents })
+        })
+    }
+
+    pub fn restore(&self, color_id: &str) -> QueryResult<Color> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        diesel::update(color.find(color_id).filter(self.tenant_condition()).filter(deleted_at.is_not_null()))
+            .set((deleted_at.eq(None::<DateTime<Utc>>), version.eq(version + 1)))
+            .get_result(&mut conn)
+    }
 }
\ No newline at end of file
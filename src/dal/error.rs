@@ -0,0 +1,75 @@
+use std::fmt;
+
+/// Error returned by versioned updates.
+#[derive(Debug)]
+pub enum UpdateError<T> {
+    /// The row was modified since it was read. Holds the current row so the
+    /// caller can merge and retry. Boxed to keep the `Result` small.
+    Conflict(Box<T>),
+    /// The actor's policy doesn't allow writing the row.
+    Denied,
+    /// The row doesn't exist or the query failed.
+    Database(diesel::result::Error),
+}
+
+impl<T> From<diesel::result::Error> for UpdateError<T> {
+    fn from(error: diesel::result::Error) -> Self {
+        UpdateError::Database(error)
+    }
+}
+
+impl<T> fmt::Display for UpdateError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdateError::Conflict(_) => write!(f, "row was modified concurrently"),
+            UpdateError::Denied => write!(f, "access to row denied by policy"),
+            UpdateError::Database(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for UpdateError<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UpdateError::Conflict(_) | UpdateError::Denied => None,
+            UpdateError::Database(error) => Some(error),
+        }
+    }
+}
+
+/// Error returned by deletes with a delete strategy.
+#[derive(Debug)]
+pub enum DeleteError {
+    /// The delete was restricted because live rows still reference the row.
+    /// Holds the number of referencing rows.
+    Restricted(usize),
+    /// The actor's policy doesn't allow deleting the row.
+    Denied,
+    /// The query failed.
+    Database(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for DeleteError {
+    fn from(error: diesel::result::Error) -> Self {
+        DeleteError::Database(error)
+    }
+}
+
+impl fmt::Display for DeleteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeleteError::Restricted(dependents) => write!(f, "row is still referenced by {} rows", dependents),
+            DeleteError::Denied => write!(f, "access to row denied by policy"),
+            DeleteError::Database(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for DeleteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DeleteError::Restricted(_) | DeleteError::Denied => None,
+            DeleteError::Database(error) => Some(error),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_builder::{AstPass, Query, QueryFragment, QueryId};
+use diesel::sql_types::Json;
+use serde_json::Value;
+
+/// A summary of the plan Postgres chose for a filtered query.
+///
+/// Produced by the `explain_with_filters` methods on the DALs so that slow or
+/// surprising queries can be logged together with the SQL that produced them.
+#[derive(Debug, Clone)]
+pub struct QueryPlan {
+    /// The rendered SQL, including bind values, as produced by `debug_query`.
+    pub sql: String,
+    /// The planner's total cost estimate for the top plan node.
+    pub total_cost: f64,
+    /// The planner's row estimate for the top plan node.
+    pub estimated_rows: u64,
+    /// Rows actually returned by the top plan node (only when analyzed).
+    pub actual_rows: Option<u64>,
+    /// Execution time in milliseconds (only when analyzed).
+    pub execution_time_ms: Option<f64>,
+    /// Relations read with a sequential scan anywhere in the plan.
+    pub seq_scans: Vec<String>,
+    /// The full JSON plan returned by Postgres.
+    pub raw: Value,
+}
+
+/// Wraps a query in `EXPLAIN (FORMAT JSON)`, keeping its bind parameters.
+struct Explain<Q> {
+    query: Q,
+    analyze: bool,
+}
+
+impl<Q> QueryId for Explain<Q> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<Q> Query for Explain<Q> {
+    type SqlType = Json;
+}
+
+impl<Q> RunQueryDsl<PgConnection> for Explain<Q> {}
+
+impl<Q: QueryFragment<Pg>> QueryFragment<Pg> for Explain<Q> {
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+        out.push_sql("EXPLAIN (");
+        if self.analyze {
+            out.push_sql("ANALYZE, ");
+        }
+        out.push_sql("FORMAT JSON) ");
+        self.query.walk_ast(out.reborrow())
+    }
+}
+
+/// Runs `EXPLAIN` (optionally with `ANALYZE`) for `query` and summarises the plan.
+///
+/// Note that `analyze` executes the query.
+pub(super) fn explain<Q>(conn: &mut PgConnection, query: Q, analyze: bool) -> QueryResult<QueryPlan>
+where
+    Q: QueryFragment<Pg>,
+{
+    let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+    let raw = Explain { query, analyze }.get_result::<Value>(conn)?;
+
+    // EXPLAIN (FORMAT JSON) returns a single element array
+    let root = &raw[0];
+    let plan = &root["Plan"];
+
+    let mut seq_scans = Vec::new();
+    collect_seq_scans(plan, &mut seq_scans);
+
+    Ok(QueryPlan {
+        sql,
+        total_cost: plan["Total Cost"].as_f64().unwrap_or_default(),
+        estimated_rows: plan["Plan Rows"].as_u64().unwrap_or_default(),
+        actual_rows: plan["Actual Rows"].as_u64(),
+        execution_time_ms: root["Execution Time"].as_f64(),
+        seq_scans,
+        raw,
+    })
+}
+
+fn collect_seq_scans(plan: &Value, seq_scans: &mut Vec<String>) {
+    if plan["Node Type"] == "Seq Scan" {
+        if let Some(relation) = plan["Relation Name"].as_str() {
+            seq_scans.push(relation.to_string());
+        }
+    }
+
+    if let Some(children) = plan["Plans"].as_array() {
+        for child in children {
+            collect_seq_scans(child, seq_scans);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use crate::models::common::{GeoFilter, GeoPoint};
+use crate::schema::trip_point::dsl::*;
+
+type PointCondition = Box<dyn BoxableExpression<trip_point, Pg, SqlType = Bool>>;
+
+/// Whether a track point lies in the area of `filter`
+///
+/// Boxes are plain comparisons on the latitude and longitude columns. Radii and
+/// polygons use trigonometry in SQL, or PostGIS with the `postgis` feature.
+/// Every area also gets a latitude range, so the position index narrows the
+/// points before the math runs.
+pub(super) fn point_condition(filter: GeoFilter) -> PointCondition {
+    match filter {
+        GeoFilter::WithinBBox { south_west, north_east } => within_bbox(south_west, north_east),
+        GeoFilter::WithinRadius { center, radius_meters } => {
+            let spread = (radius_meters / crate::gpx::EARTH_RADIUS_METERS).to_degrees();
+            Box::new(latitude.between(center.latitude - spread, center.latitude + spread).and(within_radius(center, radius_meters)))
+        }
+        GeoFilter::WithinPolygon(vertices) => {
+            let south = vertices.iter().map(|vertex| vertex.latitude).fold(f64::INFINITY, f64::min);
+            let north = vertices.iter().map(|vertex| vertex.latitude).fold(f64::NEG_INFINITY, f64::max);
+            Box::new(latitude.between(south, north).and(inside_polygon(&vertices)))
+        }
+    }
+}
+
+fn within_bbox(south_west: GeoPoint, north_east: GeoPoint) -> PointCondition {
+    let latitudes = latitude.between(south_west.latitude, north_east.latitude);
+    if south_west.longitude <= north_east.longitude {
+        Box::new(latitudes.and(longitude.between(south_west.longitude, north_east.longitude)))
+    } else {
+        Box::new(latitudes.and(longitude.ge(south_west.longitude).or(longitude.le(north_east.longitude))))
+    }
+}
+
+#[cfg(not(feature = "postgis"))]
+mod math {
+    use diesel::expression::functions::define_sql_function;
+    use diesel::sql_types::Float8;
+
+    define_sql_function!(fn sin(x: Float8) -> Float8);
+    define_sql_function!(fn cos(x: Float8) -> Float8);
+    define_sql_function!(fn radians(x: Float8) -> Float8);
+}
+
+/// The haversine formula, compared without the square root and arcsine of the
+/// distance so Postgres only needs `sin`, `cos` and `radians`
+#[cfg(not(feature = "postgis"))]
+fn within_radius(center: GeoPoint, radius_meters: f64) -> PointCondition {
+    use math::{cos, radians, sin};
+
+    let center_latitude = center.latitude.to_radians();
+    let center_longitude = center.longitude.to_radians();
+    let half_central_angle = (radius_meters / (2.0 * crate::gpx::EARTH_RADIUS_METERS)).min(std::f64::consts::FRAC_PI_2);
+
+    let half_d_lat = || sin((radians(latitude) - center_latitude) / 2.0);
+    let half_d_lon = || sin((radians(longitude) - center_longitude) / 2.0);
+    let haversine = half_d_lat() * half_d_lat() + cos(radians(latitude)) * center_latitude.cos() * half_d_lon() * half_d_lon();
+    Box::new(haversine.le(half_central_angle.sin().powi(2)))
+}
+
+#[cfg(feature = "postgis")]
+fn within_radius(center: GeoPoint, radius_meters: f64) -> PointCondition {
+    use diesel::dsl::sql;
+    use diesel::sql_types::Float8;
+
+    Box::new(
+        sql::<Bool>("ST_DWithin(ST_MakePoint(trip_point.longitude, trip_point.latitude)::geography, ST_MakePoint(")
+            .bind::<Float8, _>(center.longitude)
+            .sql(", ")
+            .bind::<Float8, _>(center.latitude)
+            .sql(")::geography, ")
+            .bind::<Float8, _>(radius_meters)
+            .sql(")"),
+    )
+}
+
+/// Ray casting: a point is inside if a ray from it towards the east crosses an
+/// odd number of edges
+#[cfg(not(feature = "postgis"))]
+fn inside_polygon(vertices: &[GeoPoint]) -> PointCondition {
+    let mut inside: PointCondition = Box::new(false.into_sql::<Bool>());
+    let edges = vertices.iter().zip(vertices.iter().cycle().skip(1));
+    for (from, to) in edges {
+        // Horizontal edges are never crossed by an eastward ray
+        if from.latitude == to.latitude {
+            continue;
+        }
+        let slope = (to.longitude - from.longitude) / (to.latitude - from.latitude);
+        let crosses = latitude
+            .gt(from.latitude)
+            .ne(latitude.gt(to.latitude))
+            .and(longitude.lt((latitude - from.latitude) * slope + from.longitude));
+        inside = Box::new(inside.ne(crosses));
+    }
+    inside
+}
+
+/// Points on the boundary count as inside, as `ST_Covers` has it
+#[cfg(feature = "postgis")]
+fn inside_polygon(vertices: &[GeoPoint]) -> PointCondition {
+    use diesel::dsl::sql;
+    use diesel::sql_types::Text;
+
+    let ring: Vec<String> = vertices
+        .iter()
+        .chain(vertices.first())
+        .map(|vertex| format!("{} {}", vertex.longitude, vertex.latitude))
+        .collect();
+    Box::new(
+        sql::<Bool>("ST_Covers(ST_GeomFromText(")
+            .bind::<Text, _>(format!("POLYGON(({}))", ring.join(", ")))
+            .sql(", 4326), ST_SetSRID(ST_MakePoint(trip_point.longitude, trip_point.latitude), 4326))"),
+    )
+}
@@ -0,0 +1,75 @@
+use diesel::prelude::*;
+use diesel::r2d2::{self, ConnectionManager, PooledConnection};
+use diesel::sql_types::{Bool, Text};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use crate::models::history::{HistoryEntry, Operation};
+
+type Pool = r2d2::Pool<ConnectionManager<PgConnection>>;
+
+define_sql_function!(fn set_config(setting_name: Text, new_value: Text, is_local: Bool) -> Text);
+
+/// A row of one of the `*_history` tables
+#[derive(Queryable)]
+pub(super) struct HistoryRow {
+    history_id: i64,
+    row_id: String,
+    operation: String,
+    old_values: Option<Value>,
+    new_values: Option<Value>,
+    actor_id: Option<String>,
+    changed_at: DateTime<Utc>,
+}
+
+/// Gets a connection for writes
+///
+/// Writes are attributed to `actor` by the history triggers, and inserted rows
+/// are stamped with `tenant` by the `tenant_id` column defaults. The settings
+/// are session wide, so they are always set, to reset whatever a previous user
+/// of the pooled connection left behind.
+pub(super) fn write_connection(
+    pool: &Pool,
+    actor: Option<&str>,
+    tenant: Option<&str>,
+) -> QueryResult<PooledConnection<ConnectionManager<PgConnection>>> {
+    let mut conn = pool.get().expect("Couldn't get DB connection");
+    diesel::select((
+        set_config("pedal_pal.actor_id", actor.unwrap_or(""), false),
+        set_config("pedal_pal.tenant_id", tenant.unwrap_or(""), false),
+    ))
+    .execute(&mut conn)?;
+    Ok(conn)
+}
+
+fn deserialize<T: DeserializeOwned>(values: Option<Value>) -> QueryResult<Option<T>> {
+    values
+        .map(serde_json::from_value)
+        .transpose()
+        .map_err(|e| diesel::result::Error::DeserializationError(Box::new(e)))
+}
+
+impl HistoryRow {
+    pub(super) fn into_entry<T: DeserializeOwned>(self) -> QueryResult<HistoryEntry<T>> {
+        let operation = match self.operation.as_str() {
+            "INSERT" => Operation::Insert,
+            "UPDATE" => Operation::Update,
+            "DELETE" => Operation::Delete,
+            other => {
+                return Err(diesel::result::Error::DeserializationError(
+                    format!("Unknown history operation {}", other).into(),
+                ))
+            }
+        };
+
+        Ok(HistoryEntry {
+            history_id: self.history_id,
+            row_id: self.row_id,
+            operation,
+            old: deserialize(self.old_values)?,
+            new: deserialize(self.new_values)?,
+            actor_id: self.actor_id,
+            changed_at: self.changed_at,
+        })
+    }
+}
@@ -0,0 +1,206 @@
+use diesel::dsl::now;
+use diesel::prelude::*;
+use diesel::pg::Pg;
+use diesel::r2d2::{self, ConnectionManager};
+use diesel::sql_types::{Bool, Nullable};
+use crate::dal::history::write_connection;
+use crate::dal::{tenant_condition, BikeDAL};
+use crate::models::bike::BikeCondition;
+use crate::models::common::StringFilter;
+use crate::models::maintenance::{Component, ComponentKind, MaintenanceEvent, MaintenanceKind, NewComponent, NewMaintenanceEvent, ServiceReminder};
+use crate::schema;
+use crate::schema::component::dsl::*;
+
+type Pool = r2d2::Pool<ConnectionManager<PgConnection>>;
+
+type TenantCondition = Box<dyn BoxableExpression<component, Pg, SqlType = Nullable<Bool>>>;
+
+/// Installed components grouped with the live trips of their bike, keeping
+/// those whose bike covered more than `$threshold` meters since they went on
+///
+/// The trip distances are summed in SQL, so components of bikes without trip
+/// distances never show up.
+macro_rules! worn_components {
+    ($threshold:expr) => {{
+        let threshold: f64 = $threshold;
+        schema::component::table
+            .inner_join(
+                schema::bike_trip::table.on(schema::bike_trip::dsl::bike_id.eq(schema::component::dsl::bike_id.nullable())),
+            )
+            .filter(schema::component::dsl::removed_at.is_null())
+            .filter(schema::bike_trip::dsl::deleted_at.is_null())
+            .group_by(schema::component::dsl::id)
+            .having(
+                diesel::dsl::sum(schema::bike_trip::dsl::distance_meters)
+                    .gt((schema::component::dsl::installed_at_meters + threshold).nullable()),
+            )
+    }};
+}
+
+pub(super) use worn_components;
+
+/// Data Access Layer for the components of bikes and their maintenance log
+///
+/// Components are tracked by the distance the trips of their bike covered since
+/// they were installed. Bikes are looked up through a `BikeDAL`, so only bikes
+/// it can read can be maintained and show up in reminders.
+pub struct MaintenanceDAL {
+    pool: Pool,
+    actor: Option<String>,
+    tenant: Option<String>,
+    bikes: BikeDAL,
+}
+
+impl MaintenanceDAL {
+    pub fn new(pool: Pool) -> Self {
+        MaintenanceDAL {
+            bikes: BikeDAL::new(pool.clone()),
+            pool,
+            actor: None,
+            tenant: None,
+        }
+    }
+
+    pub fn with_actor(mut self, actor_id: &str) -> Self {
+        self.actor = Some(actor_id.to_string());
+        self
+    }
+
+    pub fn for_tenant(mut self, tenant: &str) -> Self {
+        self.tenant = Some(tenant.to_string());
+        self.bikes = self.bikes.for_tenant(tenant);
+        self
+    }
+
+    /// Looks bikes up through `bikes` instead
+    pub(super) fn scoped_by(mut self, bikes: BikeDAL) -> Self {
+        self.bikes = bikes;
+        self
+    }
+
+    fn tenant_condition(&self) -> TenantCondition {
+        tenant_condition!(self.tenant.as_deref(), component, tenant_id)
+    }
+
+    /// The distance the live trips of a bike covered, in meters, or `NotFound`
+    /// if the bike can't be read
+    fn odometer(&self, conn: &mut PgConnection, component_bike_id: &str) -> QueryResult<f64> {
+        let visible = self.bikes.id_query(vec![BikeCondition::id(StringFilter::Equal(component_bike_id.to_string()))])?;
+        schema::bike::table
+            .filter(schema::bike::dsl::id.eq_any(visible))
+            .select(schema::bike::dsl::id)
+            .first::<String>(conn)?;
+        let distance = schema::bike_trip::table
+            .filter(schema::bike_trip::dsl::bike_id.eq(component_bike_id))
+            .filter(schema::bike_trip::dsl::deleted_at.is_null())
+            .select(diesel::dsl::sum(schema::bike_trip::dsl::distance_meters))
+            .first::<Option<f64>>(conn)?;
+        Ok(distance.unwrap_or(0.0))
+    }
+
+    /// Installs a component on a bike
+    ///
+    /// Runs in a single transaction. A component of the same kind still on the
+    /// bike is taken off, and both the removal and the install are logged.
+    ///
+    /// # Returns
+    ///
+    /// The installed component, `NotFound` if the bike can't be read, or a database error
+    pub fn install(&self, component_bike_id: &str, component_kind: ComponentKind, note: Option<&str>) -> QueryResult<Component> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        conn.transaction(|conn| {
+            let odometer = self.odometer(conn, component_bike_id)?;
+            let replaced = diesel::update(
+                component
+                    .filter(bike_id.eq(component_bike_id))
+                    .filter(kind.eq(component_kind.as_str()))
+                    .filter(removed_at.is_null())
+                    .filter(self.tenant_condition()),
+            )
+            .set(removed_at.eq(now))
+            .get_results::<Component>(conn)?;
+            for old in &replaced {
+                let removal = NewMaintenanceEvent::new(component_bike_id, Some(&old.id), MaintenanceKind::Removal, odometer, None);
+                diesel::insert_into(schema::maintenance_event::table).values(&removal).execute(conn)?;
+            }
+
+            let new_component = NewComponent::new(component_bike_id, component_kind, odometer);
+            let installed = diesel::insert_into(component).values(&new_component).get_result::<Component>(conn)?;
+            let install = NewMaintenanceEvent::new(component_bike_id, Some(&installed.id), MaintenanceKind::Install, odometer, note);
+            diesel::insert_into(schema::maintenance_event::table).values(&install).execute(conn)?;
+            Ok(installed)
+        })
+    }
+
+    /// Logs work on a bike, or on one of its components, that replaced nothing
+    ///
+    /// # Returns
+    ///
+    /// The logged event, `NotFound` if the bike can't be read or the component
+    /// isn't on it, or a database error
+    pub fn log_service(&self, component_bike_id: &str, serviced_component_id: Option<&str>, note: Option<&str>) -> QueryResult<MaintenanceEvent> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        conn.transaction(|conn| {
+            let odometer = self.odometer(conn, component_bike_id)?;
+            if let Some(serviced_component_id) = serviced_component_id {
+                component
+                    .find(serviced_component_id)
+                    .filter(bike_id.eq(component_bike_id))
+                    .filter(self.tenant_condition())
+                    .select(id)
+                    .first::<String>(conn)?;
+            }
+            let event = NewMaintenanceEvent::new(component_bike_id, serviced_component_id, MaintenanceKind::Service, odometer, note);
+            diesel::insert_into(schema::maintenance_event::table).values(&event).get_result(conn)
+        })
+    }
+
+    /// The components installed on a bike, by kind
+    pub fn find_components(&self, component_bike_id: &str) -> QueryResult<Vec<Component>> {
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+        component
+            .filter(bike_id.eq(component_bike_id))
+            .filter(bike_id.eq_any(self.bikes.id_query(vec![])?))
+            .filter(removed_at.is_null())
+            .filter(self.tenant_condition())
+            .order((kind, installed_at))
+            .load(&mut conn)
+    }
+
+    /// The maintenance log of a bike, oldest first
+    pub fn find_events(&self, event_bike_id: &str) -> QueryResult<Vec<MaintenanceEvent>> {
+        use crate::schema::maintenance_event::dsl as event;
+
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+        event::maintenance_event
+            .filter(event::bike_id.eq(event_bike_id))
+            .filter(event::bike_id.eq_any(self.bikes.id_query(vec![])?))
+            .filter(tenant_condition!(self.tenant.as_deref(), event::maintenance_event, event::tenant_id))
+            .order(event::performed_at)
+            .load(&mut conn)
+    }
+
+    /// Lists the installed components whose bike covered more than
+    /// `threshold_meters` since they went on, most worn first
+    pub fn due_for_service(&self, threshold_meters: f64) -> QueryResult<Vec<ServiceReminder>> {
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+        let worn = worn_components!(threshold_meters)
+            .filter(bike_id.eq_any(self.bikes.id_query(vec![])?))
+            .filter(id.eq_any(component.filter(self.tenant_condition()).select(id).into_boxed()))
+            .select((
+                Component::as_select(),
+                diesel::dsl::sum(schema::bike_trip::dsl::distance_meters),
+            ))
+            .load::<(Component, Option<f64>)>(&mut conn)?;
+
+        let mut reminders: Vec<ServiceReminder> = worn
+            .into_iter()
+            .map(|(worn_component, distance)| ServiceReminder {
+                wear_meters: distance.unwrap_or(0.0) - worn_component.installed_at_meters,
+                component: worn_component,
+            })
+            .collect();
+        reminders.sort_by(|a, b| b.wear_meters.total_cmp(&a.wear_meters).then_with(|| a.component.id.cmp(&b.component.id)));
+        Ok(reminders)
+    }
+}
@@ -1,56 +1,212 @@
 use diesel::pg::PgConnection;
+use diesel::prelude::QueryResult;
 use diesel::r2d2::{self, ConnectionManager};
+use diesel::result::{DatabaseErrorKind, Error};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use crate::models::bike::BikeCondition;
+use crate::models::common::FieldAccess;
+use crate::models::person::PersonCondition;
 
 mod person;
 mod bike;
 mod color;
 mod bike_trip;
+mod error;
+mod explain;
+mod geo;
+mod history;
+mod maintenance;
+mod policy;
+mod saved_filter;
+mod stats;
 
 
 pub use person::PersonDAL;
 pub use bike::BikeDAL;
 pub use color::ColorDAL;
 pub use bike_trip::BikeTripDAL;
+pub use saved_filter::SavedFilterDAL;
+pub use maintenance::MaintenanceDAL;
+pub use stats::StatsDAL;
+pub use error::{DeleteError, UpdateError};
+pub use explain::QueryPlan;
+pub use policy::{Action, Policy, PolicyDenied};
 
 type Pool = r2d2::Pool<ConnectionManager<PgConnection>>;
 
+/// Postgres accepts at most this many bind parameters in a single statement
+const MAX_BIND_PARAMS: usize = 65535;
+
+/// Number of rows that fit in one batch insert of rows with `columns` columns
+fn batch_size(columns: usize) -> usize {
+    MAX_BIND_PARAMS / columns
+}
+
+/// Checks that all `referenced` IDs of `table` were `found` in the tenant
+///
+/// A reference into another tenant fails with the same foreign key violation
+/// as a reference to a row that doesn't exist, so other tenants' IDs can't be probed.
+fn ensure_referenced(table: &str, referenced: &BTreeSet<&str>, found: i64) -> QueryResult<()> {
+    if found as usize == referenced.len() {
+        return Ok(());
+    }
+    Err(Error::DatabaseError(
+        DatabaseErrorKind::ForeignKeyViolation,
+        Box::new(format!("referenced {} does not exist", table)),
+    ))
+}
+
+#[derive(Clone)]
 pub struct DataAccessLayer {
     pool: Pool,
+    actor: Option<String>,
+    tenant: Option<String>,
+    bike_policy: Option<Arc<dyn Policy<BikeCondition>>>,
+    person_policy: Option<Arc<dyn Policy<PersonCondition>>>,
+    clearance: FieldAccess,
 }
 
 impl DataAccessLayer {
     pub fn new(pool: Pool) -> Self {
-        DataAccessLayer { pool }
+        DataAccessLayer {
+            pool,
+            actor: None,
+            tenant: None,
+            bike_policy: None,
+            person_policy: None,
+            clearance: FieldAccess::Public,
+        }
+    }
+
+    /// A data access layer whose DALs only see and write rows of `tenant_id`
+    pub fn for_tenant(&self, tenant_id: &str) -> Self {
+        DataAccessLayer {
+            pool: self.pool.clone(),
+            actor: self.actor.clone(),
+            tenant: Some(tenant_id.to_string()),
+            bike_policy: self.bike_policy.clone(),
+            person_policy: self.person_policy.clone(),
+            clearance: self.clearance,
+        }
+    }
+
+    /// Restricts the bikes every bike DAL handed out may access
+    ///
+    /// Trips take the policy of their bike. Colors are shared by all bikes of a
+    /// tenant and have no policy, only deleting them checks the policy on the
+    /// bikes they're removed from.
+    pub fn with_bike_policy(mut self, policy: Arc<dyn Policy<BikeCondition>>) -> Self {
+        self.bike_policy = Some(policy);
+        self
+    }
+
+    /// Restricts the persons every person DAL handed out may access
+    pub fn with_person_policy(mut self, policy: Arc<dyn Policy<PersonCondition>>) -> Self {
+        self.person_policy = Some(policy);
+        self
+    }
+
+    /// Allows the DALs handed out to filter on fields up to `clearance`
+    pub fn with_field_access(mut self, clearance: FieldAccess) -> Self {
+        self.clearance = clearance;
+        self
+    }
+
+    /// The highest field access the DALs handed out are cleared for
+    pub fn field_access(&self) -> FieldAccess {
+        self.clearance
+    }
+
+    /// Acts as `actor_id`, for policies and in the history tables
+    pub fn with_actor(mut self, actor_id: &str) -> Self {
+        self.actor = Some(actor_id.to_string());
+        self
     }
 
     pub fn person(&self) -> PersonDAL {
-        PersonDAL::new(self.pool.clone())
+        let mut dal = PersonDAL::new(self.pool.clone());
+        if let Some(actor_id) = &self.actor {
+            dal = dal.with_actor(actor_id);
+        }
+        if let Some(tenant_id) = &self.tenant {
+            dal = dal.for_tenant(tenant_id);
+        }
+        if let Some(policy) = &self.person_policy {
+            dal = dal.with_policy(policy.clone());
+        }
+        dal.with_field_access(self.clearance).scoped_by(self.bike())
     }
 
     pub fn bike(&self) -> BikeDAL {
-        BikeDAL::new(self.pool.clone())
+        let mut dal = BikeDAL::new(self.pool.clone());
+        if let Some(actor_id) = &self.actor {
+            dal = dal.with_actor(actor_id);
+        }
+        if let Some(tenant_id) = &self.tenant {
+            dal = dal.for_tenant(tenant_id);
+        }
+        if let Some(policy) = &self.bike_policy {
+            dal = dal.with_policy(policy.clone());
+        }
+        dal.with_field_access(self.clearance)
     }
 
     pub fn color(&self) -> ColorDAL {
-        ColorDAL::new(self.pool.clone())
+        let mut dal = ColorDAL::new(self.pool.clone());
+        if let Some(actor_id) = &self.actor {
+            dal = dal.with_actor(actor_id);
+        }
+        if let Some(tenant_id) = &self.tenant {
+            dal = dal.for_tenant(tenant_id);
+        }
+        dal.scoped_by(self.bike())
     }
 
     pub fn bike_trip(&self) -> BikeTripDAL {
-        BikeTripDAL::new(self.pool.clone())
+        let mut dal = BikeTripDAL::new(self.pool.clone());
+        if let Some(actor_id) = &self.actor {
+            dal = dal.with_actor(actor_id);
+        }
+        if let Some(tenant_id) = &self.tenant {
+            dal = dal.for_tenant(tenant_id);
+        }
+        dal.with_field_access(self.clearance).scoped_by(self.bike())
     }
 
-}
+    pub fn stats(&self) -> StatsDAL {
+        let mut dal = StatsDAL::new(self.pool.clone());
+        if let Some(tenant_id) = &self.tenant {
+            dal = dal.for_tenant(tenant_id);
+        }
+        dal.scoped_by(self.person(), self.bike())
+    }
+
+    pub fn maintenance(&self) -> MaintenanceDAL {
+        let mut dal = MaintenanceDAL::new(self.pool.clone());
+        if let Some(actor_id) = &self.actor {
+            dal = dal.with_actor(actor_id);
+        }
+        if let Some(tenant_id) = &self.tenant {
+            dal = dal.for_tenant(tenant_id);
+        }
+        dal.scoped_by(self.bike())
+    }
 
+    pub fn saved_filter(&self) -> SavedFilterDAL {
+        let mut dal = SavedFilterDAL::new(self.pool.clone());
+        if let Some(actor_id) = &self.actor {
+            dal = dal.with_actor(actor_id);
+        }
+        if let Some(tenant_id) = &self.tenant {
+            dal = dal.for_tenant(tenant_id);
+        }
+        dal
+    }
 
-enum NumberFilter<T> {
-    Equal(T),
-    NotEqual(T),
-    GreaterThen(T),
-    LowerThen(T),
-    IsNull,
-    IsNotNull,
 }
 
+#[allow(unused_macros)]
 macro_rules! number_filter {
     ($filter:ident, $dsl_field:expr ) => {{
         match $filter {
@@ -64,13 +220,6 @@ macro_rules! number_filter {
     }};
 }
 
-enum StringFilter {
-    Equal(String),
-    NotEqual(String),
-    Like(String),
-    In(Vec<String>),
-}
-
 macro_rules! string_filter {
     ($filter:ident, $dsl_field:expr ) => {{
         match $filter {
@@ -82,13 +231,7 @@ macro_rules! string_filter {
     }};
 }
 
-enum BooleanFilter {
-    True,
-    False,
-    IsNull,
-    IsNotNull,
-}
-
+#[allow(unused_macros)]
 macro_rules! boolean_filter {
     ($filter:ident, $dsl_field:expr ) => {{
         match $filter {
@@ -100,11 +243,33 @@ macro_rules! boolean_filter {
     }};
 }
 
-enum AndOr {
-    And,
-    Or,
+macro_rules! deleted_filter {
+    ($query:expr, $mode:expr, $dsl_field:expr ) => {{
+        match $mode {
+            DeletedMode::Exclude => $query.filter($dsl_field.is_null()),
+            DeletedMode::Include => $query,
+            DeletedMode::Only => $query.filter($dsl_field.is_not_null()),
+        }
+    }};
+}
+
+/// Boxed `tenant_id = tenant` predicate on `$source`, always true for DALs that aren't scoped to a tenant
+macro_rules! tenant_condition {
+    ($tenant:expr, $source:ty, $dsl_field:expr) => {{
+        let condition: Box<
+            dyn BoxableExpression<$source, diesel::pg::Pg, SqlType = diesel::sql_types::Nullable<diesel::sql_types::Bool>>,
+        > = match $tenant {
+            Some(tenant) => Box::new($dsl_field.eq(tenant.to_string())),
+            None => Box::new(diesel::dsl::sql::<diesel::sql_types::Bool>("TRUE").nullable()),
+        };
+        condition
+    }};
 }
 
+#[allow(unused_imports)]
 use boolean_filter;
+#[allow(unused_imports)]
 use number_filter;
-use string_filter;
\ No newline at end of file
+use deleted_filter;
+use string_filter;
+use tenant_condition;
\ No newline at end of file
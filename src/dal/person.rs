@@ -2,12 +2,20 @@ use diesel::prelude::*;
 use diesel::r2d2::{self, ConnectionManager};
 use diesel::pg::Pg;
 use diesel::sql_types::{Bool, Nullable};
-use diesel::helper_types::IntoBoxed;
-use crate::models::person::{Person, NewPerson, PersonCondition};
+use diesel::helper_types::{IntoBoxed, Select};
+use diesel::dsl::now;
+use diesel::upsert::{excluded, on_constraint};
+use chrono::{DateTime, Utc};
+use crate::models::person::{Person, NewPerson, PersonColumn, PersonCondition, PersonOrder, PersonPatch};
 use crate::schema;
-use crate::models::common::StringFilter;
+use crate::models::common::{DeleteStrategy, FieldAccess, DeleteSummary, DeletedMode, Page, SortDirection, StringFilter, Upsert};
 use crate::models::AndOr;
-use crate::dal::string_filter;
+use crate::dal::history::write_connection;
+use crate::dal::{batch_size, deleted_filter, string_filter, tenant_condition, BikeDAL, DeleteError, UpdateError};
+use crate::dal::explain::{explain, QueryPlan};
+use crate::dal::policy::{Action, Policy, PolicyDenied};
+use crate::dal::saved_filter::SavedFilterDAL;
+use std::sync::Arc;
 
 
 type Pool = r2d2::Pool<ConnectionManager<PgConnection>>;
@@ -19,86 +27,514 @@ type BoxedQuery = IntoBoxed<'static, QuerySource, Pg>;
 
 pub struct PersonDAL {
     pool: Pool,
+    deleted: DeletedMode,
+    actor: Option<String>,
+    tenant: Option<String>,
+    clearance: FieldAccess,
+    policy: Option<Arc<dyn Policy<PersonCondition>>>,
+    bikes: BikeDAL,
 }
 
 impl PersonDAL {
     pub fn new(pool: Pool) -> Self {
-        PersonDAL { pool }
+        PersonDAL {
+            bikes: BikeDAL::new(pool.clone()),
+            pool,
+            deleted: DeletedMode::Exclude,
+            actor: None,
+            tenant: None,
+            clearance: FieldAccess::Public,
+            policy: None,
+        }
     }
 
-    // Create
+    // Read deleted persons alongside live ones
+    pub fn include_deleted(mut self) -> Self {
+        self.deleted = DeletedMode::Include;
+        self
+    }
+
+    // Read only deleted persons
+    pub fn only_deleted(mut self) -> Self {
+        self.deleted = DeletedMode::Only;
+        self
+    }
+
+    // Attribute writes to an actor in the person history
+    pub fn with_actor(mut self, actor_id: &str) -> Self {
+        self.actor = Some(actor_id.to_string());
+        self
+    }
+
+    // Scope all reads and writes to the persons of a tenant
+    pub fn for_tenant(mut self, tenant: &str) -> Self {
+        self.tenant = Some(tenant.to_string());
+        self.bikes = self.bikes.for_tenant(tenant);
+        self
+    }
+
+    // Match the bikes of conditions through this bike DAL instead, so its read policy applies
+    pub(super) fn scoped_by(mut self, bikes: BikeDAL) -> Self {
+        self.bikes = bikes;
+        self
+    }
+
+    // Allow filtering on fields up to the clearance, condition trees referencing others are rejected
+    pub fn with_field_access(mut self, clearance: FieldAccess) -> Self {
+        self.clearance = clearance;
+        self
+    }
+
+    // Reject conditions filtering on fields above the clearance, before any SQL is built
+    fn validate(&self, conditions: &[PersonCondition]) -> QueryResult<()> {
+        conditions
+            .iter()
+            .try_for_each(|condition| condition.validate(self.clearance))
+            .map_err(|denied| diesel::result::Error::QueryBuilderError(Box::new(denied)))
+    }
+
+    // The IDs of the readable persons matching the conditions, as a sub select for other DALs
+    pub(super) fn id_query(&self, conditions: Vec<PersonCondition>) -> QueryResult<Select<BoxedQuery, schema::person::dsl::id>> {
+        self.validate(&conditions)?;
+        let query = create_filtered_query(self.with_policy_condition(conditions, Action::Read), self.deleted, self.tenant.as_deref(), &self.bikes);
+        Ok(query.select(schema::person::dsl::id))
+    }
+
+    // Restrict the persons this DAL may access for its actor
+    pub fn with_policy(mut self, policy: Arc<dyn Policy<PersonCondition>>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    fn policy_condition(&self, action: Action) -> Option<PersonCondition> {
+        self.policy.as_ref()?.condition(self.actor.as_deref(), action)
+    }
+
+    fn with_policy_condition(&self, mut conditions: Vec<PersonCondition>, action: Action) -> Vec<PersonCondition> {
+        conditions.extend(self.policy_condition(action));
+        conditions
+    }
+
+    // The persons of the tenant the policy allows the action on
+    fn scope(&self, action: Action) -> BoxedCondition {
+        let tenant = tenant_condition!(self.tenant.as_deref(), ConditionSource, schema::person::tenant_id);
+        match self
+            .policy_condition(action)
+            .and_then(|condition| condition.into_boxed_condition(self.tenant.as_deref(), &self.bikes))
+        {
+            Some(allowed) => Box::new(tenant.and(allowed)),
+            None => tenant,
+        }
+    }
+
+    // Whether the person exists in the tenant, but the policy doesn't allow the action on them
+    fn denied(&self, conn: &mut PgConnection, person_id: &str, action: Action) -> QueryResult<bool> {
+        if self.policy_condition(action).is_none() {
+            return Ok(false);
+        }
+        let tenant = tenant_condition!(self.tenant.as_deref(), ConditionSource, schema::person::tenant_id);
+        let exists = diesel::select(diesel::dsl::exists(schema::person::table.find(person_id).filter(tenant)))
+            .get_result::<bool>(conn)?;
+        let allowed = diesel::select(diesel::dsl::exists(
+            schema::person::table.find(person_id).filter(self.scope(action)),
+        ))
+        .get_result::<bool>(conn)?;
+        Ok(exists && !allowed)
+    }
+
+    // Whether the policy allows the action on all of person_ids
+    fn allows(&self, conn: &mut PgConnection, person_ids: &[String], action: Action) -> QueryResult<bool> {
+        if person_ids.is_empty() || self.policy_condition(action).is_none() {
+            return Ok(true);
+        }
+        let allowed = schema::person::table
+            .filter(schema::person::id.eq_any(person_ids))
+            .filter(self.scope(action))
+            .count()
+            .get_result::<i64>(conn)?;
+        Ok(allowed as usize == person_ids.len())
+    }
+
+    // Fail with a PolicyDenied unless the policy allows updating all of the written persons,
+    // so writes can't create persons outside the actor's scope or move them out of it
+    fn check_written<'a>(&self, conn: &mut PgConnection, written: impl IntoIterator<Item = &'a Person>) -> QueryResult<()> {
+        let person_ids: Vec<String> = written.into_iter().map(|p| p.id.clone()).collect();
+        if self.allows(conn, &person_ids, Action::Update)? {
+            Ok(())
+        } else {
+            Err(diesel::result::Error::QueryBuilderError(Box::new(PolicyDenied)))
+        }
+    }
+
+    // Create, a PolicyDenied if the policy wouldn't allow updating the person
     pub fn create(&self, new_person: &NewPerson) -> QueryResult<Person> {
-        let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        diesel::insert_into(schema::person::table)
-            .values(new_person)
-            .get_result(&mut conn)
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        conn.transaction(|conn| {
+            let created = diesel::insert_into(schema::person::table)
+                .values(new_person)
+                .get_result(conn)?;
+            self.check_written(conn, [&created])?;
+            Ok(created)
+        })
+    }
+
+    // Create many, batched under the bind parameter limit in one transaction
+    pub fn create_many(&self, new_persons: &[NewPerson]) -> QueryResult<Vec<Person>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        conn.transaction(|conn| {
+            let mut created = Vec::with_capacity(new_persons.len());
+            for batch in new_persons.chunks(batch_size(3)) {
+                created.extend(
+                    diesel::insert_into(schema::person::table)
+                        .values(batch)
+                        .get_results::<Person>(conn)?,
+                );
+            }
+            self.check_written(conn, &created)?;
+            Ok(created)
+        })
+    }
+
+    // Create or update many, a PolicyDenied if the policy wouldn't allow updating one of the written persons
+    pub fn upsert_many(&self, new_persons: &[NewPerson], upsert: &Upsert<PersonColumn>) -> QueryResult<Vec<Person>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        let constraint = upsert.conflict_target.constraint_name("person_pkey");
+        conn.transaction(|conn| {
+            let mut upserted = Vec::with_capacity(new_persons.len());
+            for batch in new_persons.chunks(batch_size(3)) {
+                let query = diesel::insert_into(schema::person::table)
+                    .values(batch)
+                    .on_conflict(on_constraint(constraint));
+                let rows = if upsert.update_columns.is_empty() {
+                    query.do_nothing().get_results::<Person>(conn)?
+                } else {
+                    let statement = query.do_update().set((
+                        upsert
+                            .updates(PersonColumn::Name)
+                            .then(|| schema::person::name.eq(excluded(schema::person::name))),
+                        upsert
+                            .updates(PersonColumn::Email)
+                            .then(|| schema::person::email.eq(excluded(schema::person::email))),
+                        schema::person::version.eq(schema::person::version + 1),
+                    ));
+                    // Leave conflicting persons of other tenants untouched
+                    diesel::query_dsl::methods::FilterDsl::filter(statement, self.scope(Action::Update))
+                        .get_results::<Person>(conn)?
+                };
+                upserted.extend(rows);
+            }
+            self.check_written(conn, &upserted)?;
+            Ok(upserted)
+        })
     }
 
     // Read (by id)
     pub fn find_by_id(&self, person_id: &str) -> QueryResult<Person> {
         let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        schema::person::table.find(person_id).first(&mut conn)
+        deleted_filter!(
+            schema::person::table.find(person_id).filter(self.scope(Action::Read)).into_boxed(),
+            self.deleted,
+            schema::person::deleted_at
+        )
+            .first(&mut conn)
     }
 
     // Read (all)
     pub fn find_all(&self) -> QueryResult<Vec<Person>> {
         let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        schema::person::table.load::<Person>(&mut conn)
+        deleted_filter!(
+            schema::person::table.filter(self.scope(Action::Read)).into_boxed(),
+            self.deleted,
+            schema::person::deleted_at
+        )
+            .load::<Person>(&mut conn)
     }
 
-    // Update
-    pub fn update(&self, person_id: &str, updated_person: &Person) -> QueryResult<Person> {
-        let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        diesel::update(schema::person::table.find(person_id))
-            .set(schema::person::name.eq(&updated_person.name))
-            .get_result(&mut conn)
+    // Update, only if the person is still at the version of updated_person.
+    // Denied if the policy wouldn't allow updating the person afterwards
+    pub fn update(&self, person_id: &str, updated_person: &Person) -> Result<Person, UpdateError<Person>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        if self.denied(&mut conn, person_id, Action::Update)? {
+            return Err(UpdateError::Denied);
+        }
+        conn.transaction(|conn| {
+            let updated = diesel::update(
+                schema::person::table
+                    .find(person_id)
+                    .filter(self.scope(Action::Update))
+                    .filter(schema::person::deleted_at.is_null())
+                    .filter(schema::person::version.eq(updated_person.version)),
+            )
+            .set((
+                schema::person::name.eq(&updated_person.name),
+                schema::person::email.eq(&updated_person.email),
+                schema::person::version.eq(schema::person::version + 1),
+            ))
+            .get_result::<Person>(conn)
+            .optional()?;
+
+            match updated {
+                Some(updated) => {
+                    // The actor may not move the person out of their own scope
+                    if !self.allows(conn, std::slice::from_ref(&updated.id), Action::Update)? {
+                        return Err(UpdateError::Denied);
+                    }
+                    Ok(updated)
+                }
+                None => {
+                    let current = schema::person::table
+                        .find(person_id)
+                        .filter(self.scope(Action::Update))
+                        .filter(schema::person::deleted_at.is_null())
+                        .first(conn)?;
+                    Err(UpdateError::Conflict(Box::new(current)))
+                }
+            }
+        })
     }
 
-    // Delete
-    pub fn delete(&self, person_id: &str) -> QueryResult<usize> {
-        let mut conn = self.pool.get().expect("Couldn't get DB connection");
-        diesel::delete(schema::person::table.find(person_id)).execute(&mut conn)
+    // Partial update, only the fields set in the patch are written.
+    // Denied if the policy wouldn't allow updating the person afterwards
+    pub fn update_partial(&self, person_id: &str, patch: &PersonPatch) -> Result<Person, UpdateError<Person>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        if self.denied(&mut conn, person_id, Action::Update)? {
+            return Err(UpdateError::Denied);
+        }
+        conn.transaction(|conn| {
+            let updated: Person = diesel::update(
+                schema::person::table
+                    .find(person_id)
+                    .filter(self.scope(Action::Update))
+                    .filter(schema::person::deleted_at.is_null()),
+            )
+            .set((patch, schema::person::version.eq(schema::person::version + 1)))
+            .get_result(conn)?;
+            // The actor may not move the person out of their own scope
+            if !self.allows(conn, std::slice::from_ref(&updated.id), Action::Update)? {
+                return Err(UpdateError::Denied);
+            }
+            Ok(updated)
+        })
+    }
+
+    // Delete, only marks the person as deleted so their bikes keep a valid owner
+    pub fn delete(&self, person_id: &str) -> Result<usize, DeleteError> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        if self.denied(&mut conn, person_id, Action::Delete)? {
+            return Err(DeleteError::Denied);
+        }
+        Ok(diesel::update(
+            schema::person::table
+                .find(person_id)
+                .filter(self.scope(Action::Delete))
+                .filter(schema::person::deleted_at.is_null()),
+        )
+        .set((
+            schema::person::deleted_at.eq(now),
+            schema::person::version.eq(schema::person::version + 1),
+        ))
+        .execute(&mut conn)?)
+    }
+
+    // Delete, handling the person's bikes according to the strategy, all in one transaction.
+    // Cascading also deletes the live trips of the deleted bikes, counted as dependents too.
+    // Denied unless the bike policy also allows deleting or updating each of the bikes
+    pub fn delete_with(&self, person_id: &str, strategy: DeleteStrategy) -> Result<DeleteSummary, DeleteError> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        if self.denied(&mut conn, person_id, Action::Delete)? {
+            return Err(DeleteError::Denied);
+        }
+        conn.transaction(|conn| {
+            let bikes = || {
+                schema::bike::table
+                    .filter(schema::bike::owner_id.eq(person_id))
+                    .filter(tenant_condition!(self.tenant.as_deref(), schema::bike::table, schema::bike::tenant_id))
+            };
+            let live_bikes = || bikes().filter(schema::bike::deleted_at.is_null());
+
+            if strategy == DeleteStrategy::Restrict {
+                let referencing = live_bikes().count().get_result::<i64>(conn)?;
+                if referencing > 0 {
+                    return Err(DeleteError::Restricted(referencing as usize));
+                }
+            }
+
+            // The bikes are written too, so the bike policy has to allow it on each of them
+            let (dependent_ids, action) = match strategy {
+                DeleteStrategy::Restrict => (Vec::new(), Action::Delete),
+                DeleteStrategy::Cascade => (live_bikes().select(schema::bike::id).load::<String>(conn)?, Action::Delete),
+                DeleteStrategy::SetNull => (bikes().select(schema::bike::id).load::<String>(conn)?, Action::Update),
+            };
+            if !self.bikes.allows(conn, &dependent_ids, action)? {
+                return Err(DeleteError::Denied);
+            }
+
+            let deleted = diesel::update(
+                schema::person::table
+                    .find(person_id)
+                    .filter(self.scope(Action::Delete))
+                    .filter(schema::person::deleted_at.is_null()),
+            )
+            .set((
+                schema::person::deleted_at.eq(now),
+                schema::person::version.eq(schema::person::version + 1),
+            ))
+            .execute(conn)?;
+            if deleted == 0 {
+                return Ok(DeleteSummary::default());
+            }
+
+            let dependents = match strategy {
+                DeleteStrategy::Restrict => 0,
+                DeleteStrategy::Cascade => {
+                    let bikes = diesel::update(schema::bike::table.filter(schema::bike::id.eq_any(&dependent_ids)))
+                        .set((
+                            schema::bike::deleted_at.eq(now),
+                            schema::bike::version.eq(schema::bike::version + 1),
+                        ))
+                        .execute(conn)?;
+                    // The bikes cascade in turn, as BikeDAL::delete_with does
+                    let trips = diesel::update(
+                        schema::bike_trip::table
+                            .filter(schema::bike_trip::bike_id.eq_any(dependent_ids.iter().map(Some)))
+                            .filter(schema::bike_trip::deleted_at.is_null())
+                            .filter(tenant_condition!(
+                                self.tenant.as_deref(),
+                                schema::bike_trip::table,
+                                schema::bike_trip::tenant_id
+                            )),
+                    )
+                    .set((
+                        schema::bike_trip::deleted_at.eq(now),
+                        schema::bike_trip::version.eq(schema::bike_trip::version + 1),
+                    ))
+                    .execute(conn)?;
+                    bikes + trips
+                }
+                DeleteStrategy::SetNull => diesel::update(schema::bike::table.filter(schema::bike::id.eq_any(&dependent_ids)))
+                    .set((
+                        schema::bike::owner_id.eq(None::<String>),
+                        schema::bike::version.eq(schema::bike::version + 1),
+                    ))
+                    .execute(conn)?,
+            };
+
+            Ok(DeleteSummary { deleted, dependents })
+        })
+    }
+
+    // Restore a deleted person
+    pub fn restore(&self, person_id: &str) -> Result<Person, UpdateError<Person>> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        if self.denied(&mut conn, person_id, Action::Update)? {
+            return Err(UpdateError::Denied);
+        }
+        Ok(diesel::update(
+            schema::person::table
+                .find(person_id)
+                .filter(self.scope(Action::Update))
+                .filter(schema::person::deleted_at.is_not_null()),
+        )
+        .set((
+            schema::person::deleted_at.eq(None::<DateTime<Utc>>),
+            schema::person::version.eq(schema::person::version + 1),
+        ))
+        .get_result(&mut conn)?)
+    }
+
+    // Run the conditions of a saved person filter through find_with_filters
+    pub fn run_saved_filter(&self, filter_id: &str) -> QueryResult<Vec<Person>> {
+        let conditions = SavedFilterDAL::scoped(self.pool.clone(), self.actor.as_deref(), self.tenant.as_deref()).load_conditions(filter_id)?;
+        self.find_with_filters(conditions)
     }
 
     // Find with filters
     pub fn find_with_filters(&self, conditions: Vec<PersonCondition>) -> QueryResult<Vec<Person>> {
+        self.validate(&conditions)?;
         let mut conn = self.pool.get().expect("Couldn't get DB connection");
         
-        let query = create_filtered_query(conditions);
+        let query = create_filtered_query(self.with_policy_condition(conditions, Action::Read), self.deleted, self.tenant.as_deref(), &self.bikes);
 
         query.load::<Person>(&mut conn)
     }
+
+    // Find with filters, one sorted page at a time
+    pub fn find_page(
+        &self,
+        conditions: Vec<PersonCondition>,
+        order: PersonOrder,
+        direction: SortDirection,
+        page: Page,
+    ) -> QueryResult<Vec<Person>> {
+        self.validate(&conditions)?;
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+
+        let query = create_filtered_query(self.with_policy_condition(conditions, Action::Read), self.deleted, self.tenant.as_deref(), &self.bikes);
+        let query = match (order, direction) {
+            (PersonOrder::Name, SortDirection::Asc) => query.order_by((schema::person::name.asc(), schema::person::id.asc())),
+            (PersonOrder::Name, SortDirection::Desc) => query.order_by((schema::person::name.desc(), schema::person::id.desc())),
+            (PersonOrder::Id, SortDirection::Asc) => query.order_by(schema::person::id.asc()),
+            (PersonOrder::Id, SortDirection::Desc) => query.order_by(schema::person::id.desc()),
+        };
+
+        query.limit(page.limit).offset(page.offset).load::<Person>(&mut conn)
+    }
+
+    // Render the SQL of find_with_filters, for debugging and logging
+    pub fn to_sql_string(&self, conditions: Vec<PersonCondition>) -> QueryResult<String> {
+        self.validate(&conditions)?;
+        let query = create_filtered_query(self.with_policy_condition(conditions, Action::Read), self.deleted, self.tenant.as_deref(), &self.bikes);
+
+        Ok(diesel::debug_query::<Pg, _>(&query).to_string())
+    }
+
+    // Explain find_with_filters, `analyze` executes the query
+    pub fn explain_with_filters(&self, conditions: Vec<PersonCondition>, analyze: bool) -> QueryResult<QueryPlan> {
+        self.validate(&conditions)?;
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+
+        let query = create_filtered_query(self.with_policy_condition(conditions, Action::Read), self.deleted, self.tenant.as_deref(), &self.bikes);
+
+        explain(&mut conn, query, analyze)
+    }
 }
 
 impl PersonCondition {
-    fn to_boxed_condition(self) -> Option<BoxedCondition> {
+    fn into_boxed_condition(self, tenant: Option<&str>, bikes: &BikeDAL) -> Option<BoxedCondition> {
         Some(match self {
             PersonCondition::name(f) => string_filter!(f, schema::person::dsl::name),
-            PersonCondition::And(conditions) => match create_filter(conditions, AndOr::And) {
-                Some(boxed_condition) => boxed_condition,
-                None => return None,
-            },
-            PersonCondition::Or(conditions) => match create_filter(conditions, AndOr::Or) {
-                Some(boxed_condition) => boxed_condition,
-                None => return None,
-            },
+            PersonCondition::id(f) => string_filter!(f, schema::person::dsl::id),
+            PersonCondition::email(f) => string_filter!(f, schema::person::dsl::email),
+            PersonCondition::And(conditions) => create_filter(conditions, AndOr::And, tenant, bikes)?,
+            PersonCondition::Or(conditions) => create_filter(conditions, AndOr::Or, tenant, bikes)?,
             PersonCondition::bike(conditions) => {
-                // Inner statement, reusing conditions defined in bike
-                let inner_statement = crate::dal::bike::create_filtered_query(conditions);
+                // Inner statement, reusing conditions defined in bike and the bike read policy
+                let inner_statement = bikes.nested_query(conditions, DeletedMode::Exclude);
                 Box::new(
                     schema::person::dsl::id
+                        .nullable()
                         .eq_any(inner_statement.select(schema::bike::dsl::owner_id))
                         .nullable(),
                 )
             }
+            PersonCondition::owned_bike_at(at, conditions) => {
+                let owned = bikes.nested_query(conditions, DeletedMode::Include);
+                let owners = schema::ownership::table
+                    .filter(schema::ownership::dsl::valid_from.le(at))
+                    .filter(schema::ownership::dsl::valid_to.is_null().or(schema::ownership::dsl::valid_to.gt(at)))
+                    .filter(schema::ownership::dsl::bike_id.eq_any(owned.select(schema::bike::dsl::id)))
+                    .select(schema::ownership::dsl::owner_id)
+                    .into_boxed();
+                Box::new(schema::person::dsl::id.eq_any(owners).nullable())
+            }
         })
     }
 }
 
-fn create_filter(conditions: Vec<PersonCondition>, and_or: AndOr) -> Option<BoxedCondition> {
+fn create_filter(conditions: Vec<PersonCondition>, and_or: AndOr, tenant: Option<&str>, bikes: &BikeDAL) -> Option<BoxedCondition> {
     conditions
         .into_iter()
-        .filter_map::<BoxedCondition, _>(PersonCondition::to_boxed_condition)
+        .filter_map::<BoxedCondition, _>(|condition| condition.into_boxed_condition(tenant, bikes))
         .fold(None, |boxed_conditions, boxed_condition| {
             Some(match boxed_conditions {
                 Some(bc) => match and_or {
@@ -110,10 +546,14 @@ fn create_filter(conditions: Vec<PersonCondition>, and_or: AndOr) -> Option<Boxe
         })
 }
 
-fn create_filtered_query(conditions: Vec<PersonCondition>) -> BoxedQuery {
-    let boxed_query = schema::person::table.into_boxed();
+fn create_filtered_query(conditions: Vec<PersonCondition>, deleted: DeletedMode, tenant: Option<&str>, bikes: &BikeDAL) -> BoxedQuery {
+    // The tenant is ANDed outside of the conditions, so no condition can widen it
+    let boxed_query = schema::person::table
+        .into_boxed()
+        .filter(tenant_condition!(tenant, ConditionSource, schema::person::tenant_id));
+    let boxed_query = deleted_filter!(boxed_query, deleted, schema::person::deleted_at);
 
-    match create_filter(conditions, AndOr::And) {
+    match create_filter(conditions, AndOr::And, tenant, bikes) {
         Some(boxed_conditions) => boxed_query.filter(boxed_conditions),
         None => boxed_query,
     }
@@ -0,0 +1,51 @@
+/// The kind of access a policy is asked about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Reading rows, including the rows matched by bulk writes.
+    Read,
+    /// Updating or restoring rows.
+    Update,
+    /// Deleting rows.
+    Delete,
+}
+
+/// Row-level authorization for the rows of one entity.
+///
+/// `C` is the condition type of the entity, e.g. `BikeCondition`. The
+/// condition a policy returns is ANDed into every query the DAL runs for the
+/// action, outside of the caller's conditions, so an `Or` can't widen it.
+/// Writes to a single row outside of the policy fail with a `Denied` error.
+///
+/// ```ignore
+/// struct OwnBikes;
+///
+/// impl Policy<BikeCondition> for OwnBikes {
+///     fn condition(&self, actor: Option<&str>, _action: Action) -> Option<BikeCondition> {
+///         match actor {
+///             Some("admin") => None,
+///             Some(actor) => Some(BikeCondition::owner_id(StringFilter::Equal(actor.to_string()))),
+///             None => Some(BikeCondition::owner_id(StringFilter::In(vec![]))),
+///         }
+///     }
+/// }
+/// ```
+pub trait Policy<C>: Send + Sync {
+    /// The condition rows must match for `actor` to perform `action` on them,
+    /// `None` if the actor may access every row.
+    fn condition(&self, actor: Option<&str>, action: Action) -> Option<C>;
+}
+
+/// A write would leave rows the policy doesn't allow the actor to update.
+///
+/// Writes returning a plain `QueryResult`, like creates, fail with a
+/// `QueryBuilderError` holding it and write nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PolicyDenied;
+
+impl std::fmt::Display for PolicyDenied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "access to row denied by policy")
+    }
+}
+
+impl std::error::Error for PolicyDenied {}
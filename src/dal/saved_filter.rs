@@ -0,0 +1,136 @@
+use diesel::prelude::*;
+use diesel::pg::Pg;
+use diesel::sql_types::{Bool, Nullable};
+use diesel::r2d2::{self, ConnectionManager};
+use serde_json::Value;
+use crate::models::bike::BikeCondition;
+use crate::models::bike_trip::BikeTripCondition;
+use crate::models::person::PersonCondition;
+use crate::models::saved_filter::{
+    FilterEntity, NewSavedFilter, SavedCondition, SavedFilter, CONDITION_SCHEMA_VERSION,
+};
+use crate::dal::history::write_connection;
+use crate::dal::policy::PolicyDenied;
+use crate::dal::tenant_condition;
+use crate::schema::saved_filter::dsl::*;
+
+type Pool = r2d2::Pool<ConnectionManager<PgConnection>>;
+
+type ScopeCondition = Box<dyn BoxableExpression<saved_filter, Pg, SqlType = Nullable<Bool>>>;
+
+pub struct SavedFilterDAL {
+    pool: Pool,
+    actor: Option<String>,
+    tenant: Option<String>,
+}
+
+impl SavedFilterDAL {
+    pub fn new(pool: Pool) -> Self {
+        SavedFilterDAL { pool, actor: None, tenant: None }
+    }
+
+    // A DAL scoped like the entity DAL running one of the filters
+    pub(crate) fn scoped(pool: Pool, actor: Option<&str>, tenant: Option<&str>) -> Self {
+        SavedFilterDAL { pool, actor: actor.map(str::to_string), tenant: tenant.map(str::to_string) }
+    }
+
+    // Acting as a person restricts the DAL to the filters they saved, and for
+    // reads to the shared filters without an owner
+    pub fn with_actor(mut self, actor_id: &str) -> Self {
+        self.actor = Some(actor_id.to_string());
+        self
+    }
+
+    pub fn for_tenant(mut self, tenant: &str) -> Self {
+        self.tenant = Some(tenant.to_string());
+        self
+    }
+
+    // The filters of the tenant the actor may read, or with `write` change
+    fn scope(&self, write: bool) -> ScopeCondition {
+        let tenant = tenant_condition!(self.tenant.as_deref(), saved_filter, tenant_id);
+        match (&self.actor, write) {
+            (Some(actor_id), true) => Box::new(tenant.and(owner_id.eq(actor_id.clone()))),
+            (Some(actor_id), false) => Box::new(tenant.and(owner_id.eq(actor_id.clone()).or(owner_id.is_null()))),
+            (None, _) => tenant,
+        }
+    }
+
+    // Create, a PolicyDenied if an actor saves a filter for someone else or a shared one
+    pub fn create(&self, new_filter: &NewSavedFilter) -> QueryResult<SavedFilter> {
+        if self.actor.is_some() && new_filter.owner_id != self.actor {
+            return Err(diesel::result::Error::QueryBuilderError(Box::new(PolicyDenied)));
+        }
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        diesel::insert_into(saved_filter)
+            .values(new_filter)
+            .get_result(&mut conn)
+    }
+
+    pub fn find_by_id(&self, filter_id: &str) -> QueryResult<SavedFilter> {
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+        saved_filter.find(filter_id).filter(self.scope(false)).first(&mut conn)
+    }
+
+    pub fn find_by_owner(&self, person_id: &str) -> QueryResult<Vec<SavedFilter>> {
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+        saved_filter
+            .filter(owner_id.eq(person_id))
+            .filter(self.scope(false))
+            .order(name)
+            .load(&mut conn)
+    }
+
+    pub fn delete(&self, filter_id: &str) -> QueryResult<usize> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        diesel::delete(saved_filter.find(filter_id).filter(self.scope(true))).execute(&mut conn)
+    }
+
+    // Load the conditions of a saved filter for one of the entity DALs
+    pub(crate) fn load_conditions<C: SavedCondition>(&self, filter_id: &str) -> QueryResult<Vec<C>> {
+        self.find_by_id(filter_id)?
+            .conditions()
+            .map_err(|e| diesel::result::Error::DeserializationError(Box::new(e)))
+    }
+
+    // Rewrite filters saved with an older schema version in the current shape.
+    // Deserializing accepts the old names of renamed variants through their
+    // serde aliases and serializing writes the new ones, so afterwards the
+    // aliases are no longer needed. Returns the number of rewritten filters,
+    // with an actor only their own ones are rewritten.
+    pub fn migrate(&self) -> QueryResult<usize> {
+        let mut conn = write_connection(&self.pool, self.actor.as_deref(), self.tenant.as_deref())?;
+        conn.transaction(|conn| {
+            let outdated = saved_filter
+                .filter(schema_version.lt(CONDITION_SCHEMA_VERSION))
+                .filter(self.scope(true))
+                .for_update()
+                .load::<SavedFilter>(conn)?;
+
+            for filter in &outdated {
+                let upgraded = match filter.entity.as_str() {
+                    e if e == FilterEntity::Person.as_str() => reserialize::<PersonCondition>(filter)?,
+                    e if e == FilterEntity::Bike.as_str() => reserialize::<BikeCondition>(filter)?,
+                    e if e == FilterEntity::BikeTrip.as_str() => reserialize::<BikeTripCondition>(filter)?,
+                    other => {
+                        return Err(diesel::result::Error::DeserializationError(
+                            format!("Unknown saved filter entity {}", other).into(),
+                        ))
+                    }
+                };
+                diesel::update(saved_filter.find(&filter.id))
+                    .set((conditions.eq(upgraded), schema_version.eq(CONDITION_SCHEMA_VERSION)))
+                    .execute(conn)?;
+            }
+
+            Ok(outdated.len())
+        })
+    }
+}
+
+fn reserialize<C: SavedCondition>(filter: &SavedFilter) -> QueryResult<Value> {
+    let parsed = filter
+        .conditions::<C>()
+        .map_err(|e| diesel::result::Error::DeserializationError(Box::new(e)))?;
+    serde_json::to_value(parsed).map_err(|e| diesel::result::Error::SerializationError(Box::new(e)))
+}
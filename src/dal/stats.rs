@@ -0,0 +1,159 @@
+use diesel::dsl::{count_star, sql};
+use diesel::helper_types::Select;
+use diesel::prelude::*;
+use diesel::r2d2::{self, ConnectionManager};
+use diesel::sql_types::{BigInt, Nullable};
+use crate::dal::bike_trip::{create_filtered_query, BoxedQuery};
+use crate::dal::{BikeDAL, PersonDAL};
+use crate::models::bike::BikeCondition;
+use crate::models::common::DeletedMode;
+use crate::models::person::PersonCondition;
+use crate::models::stats::{LeaderboardRow, RankBy, TimeWindow};
+use crate::schema;
+use crate::schema::bike_trip::dsl::*;
+
+type Pool = r2d2::Pool<ConnectionManager<PgConnection>>;
+
+/// `(id, name, trip count, distance, moving time, longest ride)` per person or bike
+type TotalsRow = (String, String, i64, Option<f64>, Option<i64>, Option<f64>);
+
+/// Postgres sums BIGINTs into NUMERIC, which fits the total moving time of any club
+fn sum_moving_seconds() -> diesel::expression::SqlLiteral<Nullable<BigInt>> {
+    sql::<Nullable<BigInt>>("SUM(bike_trip.moving_seconds)::BIGINT")
+}
+
+/// Leaderboards over the recorded trips of a time window
+///
+/// Persons and bikes are scoped through their DALs, so the tenant and read
+/// policies of those apply to the ranked rows.
+pub struct StatsDAL {
+    pool: Pool,
+    tenant: Option<String>,
+    persons: PersonDAL,
+    bikes: BikeDAL,
+}
+
+impl StatsDAL {
+    pub fn new(pool: Pool) -> Self {
+        StatsDAL {
+            persons: PersonDAL::new(pool.clone()),
+            bikes: BikeDAL::new(pool.clone()),
+            pool,
+            tenant: None,
+        }
+    }
+
+    pub fn for_tenant(mut self, tenant: &str) -> Self {
+        self.tenant = Some(tenant.to_string());
+        self.persons = self.persons.for_tenant(tenant);
+        self.bikes = self.bikes.for_tenant(tenant);
+        self
+    }
+
+    /// Scopes the ranked persons and bikes by these DALs instead
+    pub(super) fn scoped_by(mut self, persons: PersonDAL, bikes: BikeDAL) -> Self {
+        self.persons = persons;
+        self.bikes = bikes;
+        self
+    }
+
+    /// The IDs of the live trips of the tenant recorded in `window`, as a sub select
+    fn trip_ids(&self, window: TimeWindow) -> Select<BoxedQuery, id> {
+        create_filtered_query(vec![], DeletedMode::Exclude, self.tenant.as_deref(), &self.bikes)
+            .filter(started_at.ge(window.from))
+            .filter(started_at.lt(window.to))
+            .select(id)
+    }
+
+    /// Ranks the persons matching `scope` by the totals of the trips on bikes they owned
+    ///
+    /// Trips count for whoever owned the bike when the trip started, so they stay
+    /// with the previous owner after a transfer. Trips from before the first
+    /// recorded owner of their bike count for nobody. Only persons with a trip in
+    /// `window` are ranked, an empty `scope` ranks all of them.
+    pub fn person_leaderboard(&self, window: TimeWindow, scope: Vec<PersonCondition>, rank_by: RankBy) -> QueryResult<Vec<LeaderboardRow>> {
+        let persons = self.persons.id_query(scope)?;
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+
+        let owned_when_started = schema::ownership::dsl::bike_id
+            .nullable()
+            .eq(bike_id)
+            .and(schema::ownership::dsl::valid_from.nullable().le(started_at))
+            .and(schema::ownership::dsl::valid_to.is_null().or(schema::ownership::dsl::valid_to.gt(started_at)))
+            .and(schema::ownership::dsl::tenant_id.is_not_distinct_from(tenant_id));
+        let totals = bike_trip
+            .inner_join(schema::ownership::table.on(owned_when_started))
+            .inner_join(schema::person::table.on(schema::person::dsl::id.eq(schema::ownership::dsl::owner_id)))
+            .filter(id.eq_any(self.trip_ids(window)))
+            .filter(schema::person::dsl::id.eq_any(persons))
+            .group_by((schema::person::dsl::id, schema::person::dsl::name))
+            .select((
+                schema::person::dsl::id,
+                schema::person::dsl::name,
+                count_star(),
+                diesel::dsl::sum(distance_meters),
+                sum_moving_seconds(),
+                diesel::dsl::max(distance_meters),
+            ))
+            .load::<TotalsRow>(&mut conn)?;
+        Ok(rank(totals, rank_by))
+    }
+
+    /// Ranks the bikes matching `scope` by the totals of their trips
+    ///
+    /// Only bikes with a trip in `window` are ranked, an empty `scope` ranks all of them.
+    pub fn bike_leaderboard(&self, window: TimeWindow, scope: Vec<BikeCondition>, rank_by: RankBy) -> QueryResult<Vec<LeaderboardRow>> {
+        let bikes = self.bikes.id_query(scope)?;
+        let mut conn = self.pool.get().expect("Couldn't get DB connection");
+
+        let totals = bike_trip
+            .inner_join(schema::bike::table)
+            .filter(id.eq_any(self.trip_ids(window)))
+            .filter(schema::bike::dsl::id.eq_any(bikes))
+            .group_by((schema::bike::dsl::id, schema::bike::dsl::name))
+            .select((
+                schema::bike::dsl::id,
+                schema::bike::dsl::name,
+                count_star(),
+                diesel::dsl::sum(distance_meters),
+                sum_moving_seconds(),
+                diesel::dsl::max(distance_meters),
+            ))
+            .load::<TotalsRow>(&mut conn)?;
+        Ok(rank(totals, rank_by))
+    }
+}
+
+/// Sorts the totals by `rank_by` and numbers them, tied rows share a rank and
+/// are listed by name
+fn rank(totals: Vec<TotalsRow>, rank_by: RankBy) -> Vec<LeaderboardRow> {
+    let mut rows: Vec<LeaderboardRow> = totals
+        .into_iter()
+        .map(|(row_id, row_name, trip_count, distance, moving, longest)| LeaderboardRow {
+            rank: 0,
+            id: row_id,
+            name: row_name,
+            trip_count,
+            distance_meters: distance.unwrap_or(0.0),
+            moving_seconds: moving.unwrap_or(0),
+            longest_ride_meters: longest.unwrap_or(0.0),
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        b.score(rank_by)
+            .total_cmp(&a.score(rank_by))
+            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| a.id.cmp(&b.id))
+    });
+
+    let mut previous: Option<(f64, i64)> = None;
+    for (position, row) in rows.iter_mut().enumerate() {
+        let score = row.score(rank_by);
+        row.rank = match previous {
+            Some((previous_score, previous_rank)) if previous_score == score => previous_rank,
+            _ => position as i64 + 1,
+        };
+        previous = Some((score, row.rank));
+    }
+    rows
+}
@@ -0,0 +1,237 @@
+//! Parsing of GPX tracks as exported by bike computers and phone apps
+
+use std::fmt;
+use std::io::{BufReader, Read};
+use chrono::{DateTime, Utc};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// Mean Earth radius used for distances, in meters
+pub(crate) const EARTH_RADIUS_METERS: f64 = 6_371_000.0;
+
+/// Slowest speed counted as moving, in meters per second. Slower steps are GPS
+/// jitter while standing.
+const MIN_MOVING_SPEED: f64 = 0.5;
+
+/// A sample of a track
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Elevation in meters
+    pub elevation: Option<f64>,
+    pub time: Option<DateTime<Utc>>,
+}
+
+impl TrackPoint {
+    /// Great-circle distance to `other` in meters
+    pub fn distance_to(&self, other: &TrackPoint) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.longitude - self.longitude).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_METERS * a.sqrt().asin()
+    }
+}
+
+/// The points of a GPX file, grouped by track segment
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Track {
+    /// The name of the first track, or else of the file
+    pub name: Option<String>,
+    /// Segments in file order. The device stopped recording between segments.
+    pub segments: Vec<Vec<TrackPoint>>,
+}
+
+/// Distance, duration and elevation gain of a track
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackSummary {
+    /// Time of the first timestamped point
+    pub started_at: Option<DateTime<Utc>>,
+    /// Distance covered within the segments, in meters
+    pub distance_meters: f64,
+    /// Seconds from the first to the last timestamped point
+    pub duration_seconds: Option<i64>,
+    /// Seconds spent moving, from the steps between consecutive timestamped points
+    pub moving_seconds: Option<i64>,
+    /// Sum of the climbs between consecutive points with an elevation, in meters
+    pub elevation_gain_meters: Option<f64>,
+}
+
+impl Track {
+    /// All points in recording order
+    pub fn points(&self) -> impl Iterator<Item = &TrackPoint> {
+        self.segments.iter().flatten()
+    }
+
+    /// Summarizes the track
+    ///
+    /// Gaps between segments count towards the duration but not the distance or
+    /// moving time, since nothing is known about the route taken while not recording.
+    pub fn summary(&self) -> TrackSummary {
+        let mut distance_meters = 0.0;
+        let mut elevation_gain_meters = None;
+        let mut moving_millis = None;
+        for segment in &self.segments {
+            for pair in segment.windows(2) {
+                let meters = pair[0].distance_to(&pair[1]);
+                distance_meters += meters;
+                if let (Some(from), Some(to)) = (pair[0].time, pair[1].time) {
+                    let millis = (to - from).num_milliseconds();
+                    let moving = moving_millis.get_or_insert(0);
+                    if millis > 0 && meters / (millis as f64 / 1000.0) >= MIN_MOVING_SPEED {
+                        *moving += millis;
+                    }
+                }
+            }
+            let elevations: Vec<f64> = segment.iter().filter_map(|point| point.elevation).collect();
+            for pair in elevations.windows(2) {
+                *elevation_gain_meters.get_or_insert(0.0) += (pair[1] - pair[0]).max(0.0);
+            }
+        }
+
+        let started_at = self.points().find_map(|point| point.time);
+        let ended_at = self.points().filter_map(|point| point.time).last();
+        TrackSummary {
+            started_at,
+            distance_meters,
+            duration_seconds: started_at.zip(ended_at).map(|(start, end)| (end - start).num_seconds()),
+            moving_seconds: moving_millis.map(|millis| (millis + 500) / 1000),
+            elevation_gain_meters,
+        }
+    }
+}
+
+/// Why a GPX file couldn't be read
+#[derive(Debug)]
+pub enum GpxError {
+    /// The file isn't well-formed XML.
+    Xml(quick_xml::Error),
+    /// An element or attribute holds a value that isn't valid GPX.
+    Invalid(String),
+    /// The file holds no track points.
+    Empty,
+}
+
+impl From<quick_xml::Error> for GpxError {
+    fn from(error: quick_xml::Error) -> Self {
+        GpxError::Xml(error)
+    }
+}
+
+impl fmt::Display for GpxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GpxError::Xml(error) => write!(f, "malformed GPX: {}", error),
+            GpxError::Invalid(message) => write!(f, "invalid GPX: {}", message),
+            GpxError::Empty => write!(f, "GPX file holds no track points"),
+        }
+    }
+}
+
+impl std::error::Error for GpxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GpxError::Xml(error) => Some(error),
+            GpxError::Invalid(_) | GpxError::Empty => None,
+        }
+    }
+}
+
+fn coordinate(element: &BytesStart, key: &str) -> Result<f64, GpxError> {
+    let attribute = element
+        .try_get_attribute(key)
+        .map_err(quick_xml::Error::from)?
+        .ok_or_else(|| GpxError::Invalid(format!("track point without `{}`", key)))?;
+    let value = attribute.unescape_value()?;
+    value
+        .trim()
+        .parse()
+        .map_err(|_| GpxError::Invalid(format!("`{}` is not a valid {}", value, key)))
+}
+
+fn track_point(element: &BytesStart) -> Result<TrackPoint, GpxError> {
+    Ok(TrackPoint {
+        latitude: coordinate(element, "lat")?,
+        longitude: coordinate(element, "lon")?,
+        elevation: None,
+        time: None,
+    })
+}
+
+/// Appends `point` to the last segment, points outside of a segment start one
+fn push(segments: &mut Vec<Vec<TrackPoint>>, point: TrackPoint) {
+    match segments.last_mut() {
+        Some(segment) => segment.push(point),
+        None => segments.push(vec![point]),
+    }
+}
+
+/// Reads the track points of a GPX file
+///
+/// Waypoints and routes are ignored, they are planned rather than recorded.
+pub fn parse(reader: impl Read) -> Result<Track, GpxError> {
+    let mut reader = Reader::from_reader(BufReader::new(reader));
+    reader.config_mut().trim_text(true);
+
+    let mut track = Track::default();
+    let mut file_name = None;
+    // Local names of the open elements
+    let mut open: Vec<Vec<u8>> = Vec::new();
+    let mut point: Option<TrackPoint> = None;
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(element) => {
+                let name = element.local_name().as_ref().to_vec();
+                match name.as_slice() {
+                    b"trkseg" => track.segments.push(Vec::new()),
+                    b"trkpt" => point = Some(track_point(&element)?),
+                    _ => {}
+                }
+                open.push(name);
+            }
+            Event::Empty(element) if element.local_name().as_ref() == b"trkpt" => {
+                push(&mut track.segments, track_point(&element)?);
+            }
+            Event::Text(text) => {
+                let text = text.unescape()?;
+                let parent = open.len().checked_sub(2).map(|index| open[index].as_slice());
+                match (parent, open.last().map(Vec::as_slice)) {
+                    (Some(b"trkpt"), Some(b"ele")) => {
+                        let elevation = text.trim().parse().map_err(|_| GpxError::Invalid(format!("`{}` is not a valid elevation", text)))?;
+                        if let Some(point) = point.as_mut() {
+                            point.elevation = Some(elevation);
+                        }
+                    }
+                    (Some(b"trkpt"), Some(b"time")) => {
+                        let time = DateTime::parse_from_rfc3339(text.trim())
+                            .map_err(|_| GpxError::Invalid(format!("`{}` is not a valid time", text)))?;
+                        if let Some(point) = point.as_mut() {
+                            point.time = Some(time.with_timezone(&Utc));
+                        }
+                    }
+                    (Some(b"trk"), Some(b"name")) if track.name.is_none() => track.name = Some(text.into_owned()),
+                    (Some(b"metadata"), Some(b"name")) => file_name = Some(text.into_owned()),
+                    _ => {}
+                }
+            }
+            Event::End(_) => {
+                let closed = open.pop();
+                if let Some(point) = point.take_if(|_| closed.as_deref() == Some(b"trkpt")) {
+                    push(&mut track.segments, point);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    track.segments.retain(|segment| !segment.is_empty());
+    if track.segments.is_empty() {
+        return Err(GpxError::Empty);
+    }
+    track.name = track.name.or(file_name);
+    Ok(track)
+}
@@ -0,0 +1,150 @@
+use async_graphql::{Enum, InputObject, OneofObject};
+use chrono::{DateTime, Utc};
+use crate::models::bike::BikeCondition;
+use crate::models::common::{Page, SortDirection, StringFilter};
+use crate::models::person::{PersonCondition, PersonOrder};
+
+/// The operators a string field can be filtered with
+#[derive(OneofObject)]
+#[graphql(name = "StringFilter")]
+pub enum StringFilterInput {
+    /// The field equals the value
+    Equal(String),
+    /// The field differs from the value
+    NotEqual(String),
+    /// The field matches the SQL `LIKE` pattern
+    Like(String),
+    /// The field equals one of the values
+    In(Vec<String>),
+}
+
+impl From<StringFilterInput> for StringFilter {
+    fn from(filter: StringFilterInput) -> Self {
+        match filter {
+            StringFilterInput::Equal(value) => StringFilter::Equal(value),
+            StringFilterInput::NotEqual(value) => StringFilter::NotEqual(value),
+            StringFilterInput::Like(value) => StringFilter::Like(value),
+            StringFilterInput::In(values) => StringFilter::In(values),
+        }
+    }
+}
+
+/// A filter on bikes, mirroring `BikeCondition`
+#[derive(OneofObject)]
+pub enum BikeFilter {
+    Id(StringFilterInput),
+    Name(StringFilterInput),
+    Color(StringFilterInput),
+    OwnerId(StringFilterInput),
+    /// Bikes with an installed component that has covered more than this many meters
+    NeedsService(f64),
+    And(Vec<BikeFilter>),
+    Or(Vec<BikeFilter>),
+}
+
+impl From<BikeFilter> for BikeCondition {
+    fn from(filter: BikeFilter) -> Self {
+        match filter {
+            BikeFilter::Id(f) => BikeCondition::id(f.into()),
+            BikeFilter::Name(f) => BikeCondition::name(f.into()),
+            BikeFilter::Color(f) => BikeCondition::color(f.into()),
+            BikeFilter::OwnerId(f) => BikeCondition::owner_id(f.into()),
+            BikeFilter::NeedsService(meters) => BikeCondition::needs_service(meters),
+            BikeFilter::And(filters) => BikeCondition::And(filters.into_iter().map(Into::into).collect()),
+            BikeFilter::Or(filters) => BikeCondition::Or(filters.into_iter().map(Into::into).collect()),
+        }
+    }
+}
+
+/// A filter on persons, mirroring `PersonCondition`
+#[derive(OneofObject)]
+pub enum PersonFilter {
+    Name(StringFilterInput),
+    Id(StringFilterInput),
+    Email(StringFilterInput),
+    /// Persons owning a bike matching all of the filters
+    Bike(Vec<BikeFilter>),
+    /// Persons who owned a bike matching the filters at the given time
+    OwnedBikeAt(OwnedBikeAtInput),
+    And(Vec<PersonFilter>),
+    Or(Vec<PersonFilter>),
+}
+
+impl From<PersonFilter> for PersonCondition {
+    fn from(filter: PersonFilter) -> Self {
+        match filter {
+            PersonFilter::Name(f) => PersonCondition::name(f.into()),
+            PersonFilter::Id(f) => PersonCondition::id(f.into()),
+            PersonFilter::Email(f) => PersonCondition::email(f.into()),
+            PersonFilter::Bike(filters) => PersonCondition::bike(filters.into_iter().map(Into::into).collect()),
+            PersonFilter::OwnedBikeAt(input) => {
+                PersonCondition::owned_bike_at(input.at, input.bike.into_iter().map(Into::into).collect())
+            }
+            PersonFilter::And(filters) => PersonCondition::And(filters.into_iter().map(Into::into).collect()),
+            PersonFilter::Or(filters) => PersonCondition::Or(filters.into_iter().map(Into::into).collect()),
+        }
+    }
+}
+
+/// A bike a person owned at a point in time
+#[derive(InputObject)]
+pub struct OwnedBikeAtInput {
+    pub at: DateTime<Utc>,
+    /// Filters the bike has to match, any bike if empty
+    #[graphql(default)]
+    pub bike: Vec<BikeFilter>,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+pub enum PersonOrderField {
+    Name,
+    Id,
+}
+
+#[derive(Enum, Clone, Copy, Default, PartialEq, Eq)]
+#[graphql(name = "SortDirection")]
+pub enum SortDirectionInput {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// How `persons` are sorted
+#[derive(InputObject)]
+pub struct PersonOrderInput {
+    pub field: PersonOrderField,
+    #[graphql(default)]
+    pub direction: SortDirectionInput,
+}
+
+impl PersonOrderInput {
+    pub fn into_parts(self) -> (PersonOrder, SortDirection) {
+        let order = match self.field {
+            PersonOrderField::Name => PersonOrder::Name,
+            PersonOrderField::Id => PersonOrder::Id,
+        };
+        let direction = match self.direction {
+            SortDirectionInput::Asc => SortDirection::Asc,
+            SortDirectionInput::Desc => SortDirection::Desc,
+        };
+        (order, direction)
+    }
+}
+
+/// Rows returned when no page is given
+pub const DEFAULT_PAGE_SIZE: i32 = 50;
+
+/// The window of `persons` to return, at most 100 rows
+#[derive(InputObject)]
+pub struct PageInput {
+    #[graphql(default = 50, validator(minimum = 0, maximum = 100))]
+    pub limit: i32,
+    #[graphql(default, validator(minimum = 0))]
+    pub offset: i32,
+}
+
+impl From<PageInput> for Page {
+    fn from(page: PageInput) -> Self {
+        Page { limit: page.limit.into(), offset: page.offset.into() }
+    }
+}
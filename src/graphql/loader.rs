@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use async_graphql::dataloader::Loader;
+use crate::dal::DataAccessLayer;
+use crate::models::bike::{Bike, BikeCondition};
+use crate::models::bike_trip::{BikeTrip, BikeTripCondition};
+use crate::models::color::Color;
+use crate::models::common::StringFilter;
+
+/// Loads the bikes of persons, keyed by the owner's ID
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct OwnerId(pub String);
+
+/// Loads colors by their ID
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct ColorId(pub String);
+
+/// Loads the trips of bikes, keyed by the bike's ID
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct BikeId(pub String);
+
+/// Batches the nested resolvers of a request into one query per relation
+///
+/// Holds the request's data access layer, so nested rows are scoped by the
+/// same tenant and policies as the top level query.
+pub struct DalLoader {
+    dal: DataAccessLayer,
+}
+
+impl DalLoader {
+    pub fn new(dal: DataAccessLayer) -> Self {
+        DalLoader { dal }
+    }
+
+    async fn blocking<T, F>(&self, f: F) -> Result<T, async_graphql::Error>
+    where
+        F: FnOnce(DataAccessLayer) -> diesel::QueryResult<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let dal = self.dal.clone();
+        tokio::task::spawn_blocking(move || f(dal))
+            .await?
+            .map_err(super::query_error)
+    }
+}
+
+fn ids<K, F: Fn(&K) -> &String>(keys: &[K], id: F) -> StringFilter {
+    StringFilter::In(keys.iter().map(|key| id(key).clone()).collect())
+}
+
+impl Loader<OwnerId> for DalLoader {
+    type Value = Vec<Bike>;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[OwnerId]) -> Result<HashMap<OwnerId, Vec<Bike>>, Self::Error> {
+        let owners = ids(keys, |key| &key.0);
+        let bikes = self.blocking(move |dal| dal.bike().find_with_filters(vec![BikeCondition::owner_id(owners)])).await?;
+
+        let mut by_owner: HashMap<OwnerId, Vec<Bike>> = HashMap::new();
+        for bike in bikes {
+            if let Some(owner_id) = bike.owner_id.clone() {
+                by_owner.entry(OwnerId(owner_id)).or_default().push(bike);
+            }
+        }
+        Ok(by_owner)
+    }
+}
+
+impl Loader<ColorId> for DalLoader {
+    type Value = Color;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[ColorId]) -> Result<HashMap<ColorId, Color>, Self::Error> {
+        let color_ids: Vec<String> = keys.iter().map(|key| key.0.clone()).collect();
+        let colors = self.blocking(move |dal| dal.color().find_by_ids(&color_ids)).await?;
+
+        Ok(colors.into_iter().map(|color| (ColorId(color.id.clone()), color)).collect())
+    }
+}
+
+impl Loader<BikeId> for DalLoader {
+    type Value = Vec<BikeTrip>;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[BikeId]) -> Result<HashMap<BikeId, Vec<BikeTrip>>, Self::Error> {
+        let bikes = ids(keys, |key| &key.0);
+        let trips = self
+            .blocking(move |dal| dal.bike_trip().find_with_filters(vec![BikeTripCondition::bike(BikeCondition::id(bikes))]))
+            .await?;
+
+        let mut by_bike: HashMap<BikeId, Vec<BikeTrip>> = HashMap::new();
+        for trip in trips {
+            if let Some(bike_id) = trip.bike_id.clone() {
+                by_bike.entry(BikeId(bike_id)).or_default().push(trip);
+            }
+        }
+        Ok(by_bike)
+    }
+}
@@ -0,0 +1,171 @@
+use async_graphql::dataloader::DataLoader;
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Request, Result, Schema};
+use crate::dal::DataAccessLayer;
+use crate::models::bike::{Bike, BikeCondition};
+use crate::models::bike_trip::BikeTrip;
+use crate::models::color::Color;
+use crate::models::common::{FieldAccess, Page, SortDirection};
+use crate::models::person::{Person, PersonCondition, PersonOrder};
+
+mod input;
+mod loader;
+
+pub use input::{BikeFilter, PageInput, PersonFilter, PersonOrderInput, StringFilterInput};
+use input::DEFAULT_PAGE_SIZE;
+use loader::{BikeId, ColorId, DalLoader, OwnerId};
+
+pub type PedalPalSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// The read-only GraphQL schema over the data access layer
+pub fn schema() -> PedalPalSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription).finish()
+}
+
+/// Attaches the data access layer `request` runs on, along with its loaders
+pub fn with_dal(request: Request, dal: DataAccessLayer) -> Request {
+    let loader = DataLoader::new(DalLoader::new(dal.clone()), tokio::spawn);
+    request.data(dal).data(loader)
+}
+
+fn query_error(error: impl std::fmt::Display) -> async_graphql::Error {
+    async_graphql::Error::new(error.to_string())
+}
+
+/// Runs a blocking DAL call off the async runtime
+async fn blocking<T, F>(ctx: &Context<'_>, f: F) -> Result<T>
+where
+    F: FnOnce(DataAccessLayer) -> diesel::QueryResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let dal = ctx.data::<DataAccessLayer>()?.clone();
+    tokio::task::spawn_blocking(move || f(dal))
+        .await?
+        .map_err(query_error)
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Persons matching `filter`, sorted by `order` (name by default)
+    async fn persons(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<PersonFilter>,
+        order: Option<PersonOrderInput>,
+        page: Option<PageInput>,
+    ) -> Result<Vec<PersonNode>> {
+        let conditions: Vec<PersonCondition> = filter.into_iter().map(Into::into).collect();
+        let (order, direction) = order.map(PersonOrderInput::into_parts).unwrap_or((PersonOrder::Name, SortDirection::Asc));
+        let page = page.map(Page::from).unwrap_or(Page { limit: DEFAULT_PAGE_SIZE.into(), offset: 0 });
+
+        let persons = blocking(ctx, move |dal| dal.person().find_page(conditions, order, direction, page)).await?;
+        Ok(persons.into_iter().map(PersonNode).collect())
+    }
+
+    /// Bikes matching `filter`
+    async fn bikes(&self, ctx: &Context<'_>, filter: Option<BikeFilter>) -> Result<Vec<BikeNode>> {
+        let conditions: Vec<BikeCondition> = filter.into_iter().map(Into::into).collect();
+
+        let bikes = blocking(ctx, move |dal| dal.bike().find_with_filters(conditions)).await?;
+        Ok(bikes.into_iter().map(BikeNode).collect())
+    }
+}
+
+pub struct PersonNode(Person);
+
+#[Object(name = "Person")]
+impl PersonNode {
+    async fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    /// Null unless the caller may filter on emails, which are restricted since the
+    /// address itself is what a filter would reveal
+    async fn email(&self, ctx: &Context<'_>) -> Result<Option<&str>> {
+        let clearance = ctx.data::<DataAccessLayer>()?.field_access();
+        Ok(self.0.email.as_deref().filter(|_| clearance >= FieldAccess::Restricted))
+    }
+
+    async fn version(&self) -> i32 {
+        self.0.version
+    }
+
+    /// The live bikes the person owns
+    async fn bikes(&self, ctx: &Context<'_>) -> Result<Vec<BikeNode>> {
+        let loader = ctx.data::<DataLoader<DalLoader>>()?;
+        let bikes = loader.load_one(OwnerId(self.0.id.clone())).await?;
+        Ok(bikes.unwrap_or_default().into_iter().map(BikeNode).collect())
+    }
+}
+
+pub struct BikeNode(Bike);
+
+#[Object(name = "Bike")]
+impl BikeNode {
+    async fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn owner_id(&self) -> Option<&str> {
+        self.0.owner_id.as_deref()
+    }
+
+    async fn version(&self) -> i32 {
+        self.0.version
+    }
+
+    async fn color(&self, ctx: &Context<'_>) -> Result<Option<ColorNode>> {
+        let Some(color_id) = self.0.color_id.clone() else {
+            return Ok(None);
+        };
+        let loader = ctx.data::<DataLoader<DalLoader>>()?;
+        let color = loader.load_one(ColorId(color_id)).await?;
+        Ok(color.map(ColorNode))
+    }
+
+    /// The live trips taken with the bike
+    async fn trips(&self, ctx: &Context<'_>) -> Result<Vec<BikeTripNode>> {
+        let loader = ctx.data::<DataLoader<DalLoader>>()?;
+        let trips = loader.load_one(BikeId(self.0.id.clone())).await?;
+        Ok(trips.unwrap_or_default().into_iter().map(BikeTripNode).collect())
+    }
+}
+
+pub struct ColorNode(Color);
+
+#[Object(name = "Color")]
+impl ColorNode {
+    async fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+}
+
+pub struct BikeTripNode(BikeTrip);
+
+#[Object(name = "BikeTrip")]
+impl BikeTripNode {
+    async fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn bike_id(&self) -> Option<&str> {
+        self.0.bike_id.as_deref()
+    }
+}
@@ -1,3 +1,8 @@
+pub mod api;
+pub mod cli;
 pub mod dal;
+pub mod gpx;
+pub mod graphql;
 pub mod models;
-pub mod schema;
\ No newline at end of file
+pub mod schema;
+pub mod transfer;
\ No newline at end of file
@@ -1,4 +1,42 @@
-fn main() {
-    println!("Hello from the bike trip API binary!");
-    // You can add your binary-specific code here
-}
\ No newline at end of file
+use std::process::ExitCode;
+use clap::Parser;
+use diesel::pg::PgConnection;
+use diesel::r2d2::{self, ConnectionManager};
+use pedal_pal::api;
+use pedal_pal::cli::{Cli, Command};
+use pedal_pal::dal::DataAccessLayer;
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let manager = ConnectionManager::<PgConnection>::new(&cli.database_url);
+    let pool = match r2d2::Pool::builder().build(manager) {
+        Ok(pool) => pool,
+        Err(error) => {
+            eprintln!("Failed to connect to the database: {}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+    let dal = cli.scope(DataAccessLayer::new(pool));
+
+    let result = match cli.command {
+        Command::Serve { bind, tenancy } => serve(dal, &bind, tenancy),
+        command => command.execute(&dal, cli.format, &mut std::io::stdout().lock()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn serve(dal: DataAccessLayer, bind: &str, tenancy: api::Tenancy) -> Result<(), Box<dyn std::error::Error>> {
+    tokio::runtime::Runtime::new()?.block_on(async {
+        let listener = tokio::net::TcpListener::bind(bind).await?;
+        println!("Listening on {}", bind);
+        axum::serve(listener, api::router(dal, tenancy)).await?;
+        Ok(())
+    })
+}
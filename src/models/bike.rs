@@ -1,11 +1,20 @@
+use crate::models::bike_trip::BikeTrip;
+use crate::models::color::Color;
 use crate::models::common::*;
+use crate::models::person::Person;
 use crate::schema::bike;
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use std::ops::BitOr;
 use uuid::Uuid;
 
 /// Represents a bike in the database.
-#[derive(Debug, Clone, Queryable, Identifiable)]
+#[derive(Debug, Clone, Queryable, Identifiable, Associations, Serialize, Deserialize, ToSchema)]
 #[diesel(table_name = bike)]
+#[diesel(belongs_to(Person, foreign_key = owner_id))]
+#[diesel(belongs_to(Color))]
 pub struct Bike {
     /// Unique identifier for the bike.
     pub id: String,
@@ -15,13 +24,136 @@ pub struct Bike {
     pub owner_id: Option<String>,
     /// Optional ID of the color of this bike.
     pub color_id: Option<String>,
+    /// Version of the bike, incremented on every update.
+    pub version: i32,
+    /// When the bike was deleted, if it has been.
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// The tenant the bike belongs to, `None` if it isn't scoped to a tenant.
+    pub tenant_id: Option<String>,
+}
+
+/// Represents a bike projected together with the names of its owner and color.
+///
+/// Loaded in a single query over the bike, color and person left joins, so the
+/// joined names are `None` when the bike has no owner or color.
+#[derive(Debug, Clone, Queryable)]
+pub struct BikeRow {
+    /// Unique identifier for the bike.
+    pub id: String,
+    /// Name or description of the bike.
+    pub name: String,
+    /// Optional ID of the person who owns this bike.
+    pub owner_id: Option<String>,
+    /// Optional ID of the color of this bike.
+    pub color_id: Option<String>,
+    /// Name of the person who owns this bike, if any.
+    pub owner_name: Option<String>,
+    /// Name of the color of this bike, if any.
+    pub color_name: Option<String>,
+}
+
+/// Represents a bike together with its eagerly loaded relations.
+///
+/// Relations that were not requested through [`Includes`] are left as `None`
+/// (or an empty vector for trips).
+#[derive(Debug, Clone)]
+pub struct BikeWithRelations {
+    /// The bike itself.
+    pub bike: Bike,
+    /// The color of the bike, if requested and set.
+    pub color: Option<Color>,
+    /// The owner of the bike, if requested and set.
+    pub owner: Option<Person>,
+    /// The trips taken with the bike, if requested.
+    pub trips: Vec<BikeTrip>,
+}
+
+/// A relation of a bike that can be eagerly loaded.
+///
+/// Relations can be combined with `|`, e.g. `Include::Color | Include::Owner`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Include {
+    /// Load the color of each bike.
+    Color,
+    /// Load the owner of each bike.
+    Owner,
+    /// Load the trips of each bike.
+    Trips,
+}
+
+/// A set of [`Include`] relations to eagerly load.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Includes {
+    color: bool,
+    owner: bool,
+    trips: bool,
+}
+
+impl Includes {
+    /// Returns whether the given relation is part of the set.
+    pub fn contains(&self, include: Include) -> bool {
+        match include {
+            Include::Color => self.color,
+            Include::Owner => self.owner,
+            Include::Trips => self.trips,
+        }
+    }
+
+    fn with(mut self, include: Include) -> Self {
+        match include {
+            Include::Color => self.color = true,
+            Include::Owner => self.owner = true,
+            Include::Trips => self.trips = true,
+        }
+        self
+    }
+}
+
+impl From<Include> for Includes {
+    fn from(include: Include) -> Self {
+        Includes::default().with(include)
+    }
+}
+
+impl BitOr for Include {
+    type Output = Includes;
+
+    fn bitor(self, rhs: Include) -> Includes {
+        Includes::from(self).with(rhs)
+    }
+}
+
+impl BitOr<Include> for Includes {
+    type Output = Includes;
+
+    fn bitor(self, rhs: Include) -> Includes {
+        self.with(rhs)
+    }
+}
+
+/// Represents a partial update of a bike.
+///
+/// Fields left as `None` are not touched. For the nullable columns,
+/// `Some(None)` sets the column to NULL.
+#[derive(Debug, Clone, Default, AsChangeset, Deserialize, ToSchema)]
+#[diesel(table_name = bike)]
+pub struct BikePatch {
+    /// New name for the bike.
+    pub name: Option<String>,
+    /// New owner for the bike.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub owner_id: Option<Option<String>>,
+    /// New color for the bike.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub color_id: Option<Option<String>>,
 }
 
 /// Represents a new bike to be inserted into the database.
-#[derive(Debug, Clone, Insertable)]
+#[derive(Debug, Clone, Insertable, Deserialize, ToSchema)]
 #[diesel(table_name = bike)]
 pub struct NewBike {
     /// Unique identifier for the new bike.
+    #[serde(default = "generate_id")]
     pub id: String,
     /// Name or description of the new bike.
     pub name: String,
@@ -58,15 +190,77 @@ impl NewBike {
 /// This enum is crucial for implementing dynamic filtering in the data access layer.
 /// It allows for the construction of complex query conditions at runtime,
 /// enabling flexible and powerful search capabilities for bikes.
+///
+/// Condition trees are serialized into saved filters. Renamed variants must keep
+/// their old name as a `#[serde(alias = "...")]`, so filters saved before the
+/// rename still load.
 #[allow(non_camel_case_types)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub enum BikeCondition {
+    /// Filter by the ID of the bike.
+    id(StringFilter),
     /// Filter by the name of the bike.
     name(StringFilter),
     /// Filter by the color of the bike.
     color(StringFilter),
+    /// Filter by the ID of the bike's owner.
+    owner_id(StringFilter),
+    /// Keep bikes with an installed component whose bike covered more than
+    /// this many meters since it went on.
+    needs_service(f64),
     /// Combine multiple conditions with a logical AND.
+    #[schema(no_recursion)]
     And(Vec<BikeCondition>),
     /// Combine multiple conditions with a logical OR.
+    #[schema(no_recursion)]
     Or(Vec<BikeCondition>),
-}
\ No newline at end of file
+}
+
+impl BikeCondition {
+    /// The access needed to filter on each field, `None` for combinators.
+    pub fn field_access(&self) -> Option<(&'static str, FieldAccess)> {
+        match self {
+            BikeCondition::id(_) => Some(("BikeCondition::id", FieldAccess::Public)),
+            BikeCondition::name(_) => Some(("BikeCondition::name", FieldAccess::Public)),
+            BikeCondition::color(_) => Some(("BikeCondition::color", FieldAccess::Public)),
+            BikeCondition::owner_id(_) => Some(("BikeCondition::owner_id", FieldAccess::Public)),
+            BikeCondition::needs_service(_) => Some(("BikeCondition::needs_service", FieldAccess::Public)),
+            BikeCondition::And(_) | BikeCondition::Or(_) => None,
+        }
+    }
+
+    /// Checks that a caller with `clearance` may filter on every field in the tree.
+    pub fn validate(&self, clearance: FieldAccess) -> Result<(), FieldDenied> {
+        if let Some((field, access)) = self.field_access() {
+            if access > clearance {
+                return Err(FieldDenied { field });
+            }
+        }
+        match self {
+            BikeCondition::And(conditions) | BikeCondition::Or(conditions) => {
+                conditions.iter().try_for_each(|c| c.validate(clearance))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The relation bikes can be grouped by in aggregations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BikeGroup {
+    /// Group bikes by their color.
+    Color,
+    /// Group bikes by their owner.
+    Owner,
+}
+
+/// The updatable columns of a bike, used to configure upserts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BikeColumn {
+    /// The name of the bike.
+    Name,
+    /// The owner of the bike.
+    OwnerId,
+    /// The color of the bike.
+    ColorId,
+}
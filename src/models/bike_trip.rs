@@ -1,11 +1,16 @@
+use crate::models::bike::Bike;
 use crate::models::common::*;
 use crate::schema::bike_trip;
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 /// Represents a bike trip in the database.
-#[derive(Debug, Clone, Queryable, Identifiable)]
+#[derive(Debug, Clone, Queryable, Identifiable, Associations, Serialize, Deserialize, ToSchema)]
 #[diesel(table_name = bike_trip)]
+#[diesel(belongs_to(Bike))]
 pub struct BikeTrip {
     /// Unique identifier for the bike trip.
     pub id: String,
@@ -13,13 +18,44 @@ pub struct BikeTrip {
     pub name: String,
     /// Optional ID of the bike used for this trip.
     pub bike_id: Option<String>,
+    /// Version of the bike trip, incremented on every update.
+    pub version: i32,
+    /// When the bike trip was deleted, if it has been.
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// The tenant the bike trip belongs to, `None` if it isn't scoped to a tenant.
+    pub tenant_id: Option<String>,
+    /// When the recorded track of the trip started.
+    pub started_at: Option<DateTime<Utc>>,
+    /// Distance covered by the recorded track, in meters.
+    pub distance_meters: Option<f64>,
+    /// Time from the first to the last sample of the recorded track, in seconds.
+    pub duration_seconds: Option<i64>,
+    /// Sum of the climbs along the recorded track, in meters.
+    pub elevation_gain_meters: Option<f64>,
+    /// Time spent moving along the recorded track, in seconds.
+    pub moving_seconds: Option<i64>,
+}
+
+/// Represents a partial update of a bike trip.
+///
+/// Fields left as `None` are not touched. For the nullable columns,
+/// `Some(None)` sets the column to NULL.
+#[derive(Debug, Clone, Default, AsChangeset, Deserialize, ToSchema)]
+#[diesel(table_name = bike_trip)]
+pub struct BikeTripPatch {
+    /// New name for the bike trip.
+    pub name: Option<String>,
+    /// New bike for the bike trip.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub bike_id: Option<Option<String>>,
 }
 
 /// Represents a new bike trip to be inserted into the database.
-#[derive(Debug, Clone, Insertable)]
+#[derive(Debug, Clone, Insertable, Deserialize, ToSchema)]
 #[diesel(table_name = bike_trip)]
 pub struct NewBikeTrip {
     /// Unique identifier for the new bike trip.
+    #[serde(default = "generate_id")]
     pub id: String,
     /// Name or description of the new bike trip.
     pub name: String,
@@ -52,15 +88,94 @@ impl NewBikeTrip {
 /// This enum is crucial for implementing dynamic filtering in the data access layer.
 /// It allows for the construction of complex query conditions at runtime,
 /// enabling flexible and powerful search capabilities for bike trips.
+///
+/// Condition trees are serialized into saved filters. Renamed variants must keep
+/// their old name as a `#[serde(alias = "...")]`, so filters saved before the
+/// rename still load.
 #[allow(non_camel_case_types)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub enum BikeTripCondition {
     /// Filter by the name of the bike trip.
     name(StringFilter),
     /// Filter by conditions related to the associated bike.
     bike(super::bike::BikeCondition),
+    /// Trips with a recorded track point in the area.
+    track(GeoFilter),
+    /// Trips whose recorded track starts in the area.
+    start(GeoFilter),
     /// Combine multiple conditions with a logical AND.
+    #[schema(no_recursion)]
     And(Vec<BikeTripCondition>),
     /// Combine multiple conditions with a logical OR.
+    #[schema(no_recursion)]
     Or(Vec<BikeTripCondition>),
-}
\ No newline at end of file
+}
+
+impl BikeTripCondition {
+    /// The access needed to filter on each field, `None` for combinators.
+    pub fn field_access(&self) -> Option<(&'static str, FieldAccess)> {
+        match self {
+            BikeTripCondition::name(_) => Some(("BikeTripCondition::name", FieldAccess::Public)),
+            BikeTripCondition::bike(_) => Some(("BikeTripCondition::bike", FieldAccess::Public)),
+            BikeTripCondition::track(_) => Some(("BikeTripCondition::track", FieldAccess::Public)),
+            BikeTripCondition::start(_) => Some(("BikeTripCondition::start", FieldAccess::Public)),
+            BikeTripCondition::And(_) | BikeTripCondition::Or(_) => None,
+        }
+    }
+
+    /// Checks that a caller with `clearance` may filter on every field in the tree.
+    pub fn validate(&self, clearance: FieldAccess) -> Result<(), FieldDenied> {
+        if let Some((field, access)) = self.field_access() {
+            if access > clearance {
+                return Err(FieldDenied { field });
+            }
+        }
+        match self {
+            BikeTripCondition::bike(condition) => condition.validate(clearance),
+            BikeTripCondition::And(conditions) | BikeTripCondition::Or(conditions) => {
+                conditions.iter().try_for_each(|c| c.validate(clearance))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that every geo filter in the tree describes an area.
+    pub fn validate_geo(&self) -> Result<(), InvalidGeoFilter> {
+        match self {
+            BikeTripCondition::track(filter) | BikeTripCondition::start(filter) => filter.validate(),
+            BikeTripCondition::And(conditions) | BikeTripCondition::Or(conditions) => {
+                conditions.iter().try_for_each(BikeTripCondition::validate_geo)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The relation bike trips can be grouped by in aggregations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BikeTripGroup {
+    /// Group trips by the bike they were taken with.
+    Bike,
+    /// Group trips by the owner of the bike they were taken with.
+    Owner,
+}
+
+/// The numeric columns of bike trips that can be aggregated per group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BikeTripMetric {
+    /// The distance covered, in meters.
+    Distance,
+    /// The elapsed time, in seconds.
+    Duration,
+    /// The total climb, in meters.
+    ElevationGain,
+}
+
+/// The updatable columns of a bike trip, used to configure upserts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BikeTripColumn {
+    /// The name of the bike trip.
+    Name,
+    /// The bike used for the bike trip.
+    BikeId,
+}
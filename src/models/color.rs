@@ -1,23 +1,43 @@
 use crate::models::common::*;
 use crate::schema::color;
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 /// Represents a color in the database.
-#[derive(Debug, Clone, Queryable, Identifiable)]
+#[derive(Debug, Clone, Queryable, Identifiable, Serialize, Deserialize, ToSchema)]
 #[diesel(table_name = color)]
 pub struct Color {
     /// Unique identifier for the color.
     pub id: String,
     /// Name of the color.
     pub name: String,
+    /// Version of the color, incremented on every update.
+    pub version: i32,
+    /// When the color was deleted, if it has been.
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// The tenant the color belongs to, `None` if it isn't scoped to a tenant.
+    pub tenant_id: Option<String>,
+}
+
+/// Represents a partial update of a color.
+///
+/// Fields left as `None` are not touched.
+#[derive(Debug, Clone, Default, AsChangeset, Deserialize, ToSchema)]
+#[diesel(table_name = color)]
+pub struct ColorPatch {
+    /// New name for the color.
+    pub name: Option<String>,
 }
 
 /// Represents a new color to be inserted into the database.
-#[derive(Debug, Clone, Insertable)]
+#[derive(Debug, Clone, Insertable, Deserialize, ToSchema)]
 #[diesel(table_name = color)]
 pub struct NewColor {
     /// Unique identifier for the new color.
+    #[serde(default = "generate_id")]
     pub id: String,
     /// Name of the new color.
     pub name: String,
@@ -47,12 +67,21 @@ impl NewColor {
 /// It allows for the construction of complex query conditions at runtime,
 /// enabling flexible and powerful search capabilities for colors.
 #[allow(non_camel_case_types)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub enum ColorCondition {
     /// Filter by the name of the color.
     name(StringFilter),
     /// Combine multiple conditions with a logical AND.
+    #[schema(no_recursion)]
     And(Vec<ColorCondition>),
     /// Combine multiple conditions with a logical OR.
+    #[schema(no_recursion)]
     Or(Vec<ColorCondition>),
-}
\ No newline at end of file
+}
+
+/// The updatable columns of a color, used to configure upserts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorColumn {
+    /// The name of the color.
+    Name,
+}
@@ -0,0 +1,34 @@
+use chrono::{DateTime, Utc};
+
+/// The kind of change recorded in a history entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// The row was created.
+    Insert,
+    /// The row was modified, including soft deletes and restores.
+    Update,
+    /// The row was removed from the table.
+    Delete,
+}
+
+/// Represents a recorded change of a row.
+///
+/// History entries are written by database triggers on every insert, update
+/// and delete, so they also cover bulk writes.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry<T> {
+    /// Sequential identifier of the entry.
+    pub history_id: i64,
+    /// ID of the changed row.
+    pub row_id: String,
+    /// The kind of change.
+    pub operation: Operation,
+    /// The row before the change, `None` for inserts.
+    pub old: Option<T>,
+    /// The row after the change, `None` for deletes.
+    pub new: Option<T>,
+    /// ID of the actor the DAL was acting for, if any.
+    pub actor_id: Option<String>,
+    /// When the change happened.
+    pub changed_at: DateTime<Utc>,
+}
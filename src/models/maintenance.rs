@@ -0,0 +1,188 @@
+use crate::models::bike::Bike;
+use crate::schema::{component, maintenance_event};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A part of a bike that wears with the distance ridden.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentKind {
+    /// The chain.
+    Chain,
+    /// A tyre.
+    Tyre,
+    /// A set of brake pads.
+    BrakePads,
+}
+
+impl ComponentKind {
+    /// The name the kind is stored as.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ComponentKind::Chain => "chain",
+            ComponentKind::Tyre => "tyre",
+            ComponentKind::BrakePads => "brake_pads",
+        }
+    }
+}
+
+/// The work recorded by a maintenance event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceKind {
+    /// A component was installed.
+    Install,
+    /// A component was taken off, usually to be replaced.
+    Removal,
+    /// The bike or a component was serviced without replacing anything.
+    Service,
+}
+
+impl MaintenanceKind {
+    /// The name the kind is stored as.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MaintenanceKind::Install => "install",
+            MaintenanceKind::Removal => "removal",
+            MaintenanceKind::Service => "service",
+        }
+    }
+}
+
+/// Represents a component installed on a bike.
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Identifiable, Associations, Serialize)]
+#[diesel(table_name = component)]
+#[diesel(belongs_to(Bike))]
+pub struct Component {
+    /// Unique identifier for the component.
+    pub id: String,
+    /// ID of the bike the component is installed on.
+    pub bike_id: String,
+    /// Kind of the component, see [`ComponentKind::as_str`].
+    pub kind: String,
+    /// When the component was installed.
+    pub installed_at: DateTime<Utc>,
+    /// Distance the bike's trips had covered when the component was installed, in meters.
+    pub installed_at_meters: f64,
+    /// When the component was taken off, `None` while it is installed.
+    pub removed_at: Option<DateTime<Utc>>,
+    /// The tenant the component belongs to, `None` if it isn't scoped to a tenant.
+    pub tenant_id: Option<String>,
+}
+
+/// Represents a new component to be inserted into the database.
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = component)]
+pub struct NewComponent {
+    /// Unique identifier for the new component.
+    pub id: String,
+    /// ID of the bike the component goes on.
+    pub bike_id: String,
+    /// Kind of the component.
+    pub kind: String,
+    /// Distance the bike's trips have covered so far, in meters.
+    pub installed_at_meters: f64,
+}
+
+impl NewComponent {
+    /// Creates a new `NewComponent` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `bike_id` - The ID of the bike the component goes on.
+    /// * `kind` - The kind of the component.
+    /// * `installed_at_meters` - The distance the bike's trips have covered so far.
+    ///
+    /// # Returns
+    ///
+    /// A new `NewComponent` instance with a generated UUID.
+    pub fn new(bike_id: &str, kind: ComponentKind, installed_at_meters: f64) -> Self {
+        NewComponent {
+            id: Uuid::new_v4().to_string(),
+            bike_id: bike_id.to_string(),
+            kind: kind.as_str().to_string(),
+            installed_at_meters,
+        }
+    }
+}
+
+/// Represents an entry of the maintenance log of a bike.
+#[derive(Debug, Clone, PartialEq, Queryable, Identifiable, Associations, Serialize)]
+#[diesel(table_name = maintenance_event)]
+#[diesel(belongs_to(Bike))]
+pub struct MaintenanceEvent {
+    /// Unique identifier for the event.
+    pub id: String,
+    /// ID of the bike the work was done on.
+    pub bike_id: String,
+    /// ID of the component the work was done on, `None` for the bike as a whole.
+    pub component_id: Option<String>,
+    /// Kind of work, see [`MaintenanceKind::as_str`].
+    pub kind: String,
+    /// Distance the bike's trips had covered at the time, in meters.
+    pub odometer_meters: f64,
+    /// Free text describing the work.
+    pub note: Option<String>,
+    /// When the work was done.
+    pub performed_at: DateTime<Utc>,
+    /// The tenant the event belongs to, `None` if it isn't scoped to a tenant.
+    pub tenant_id: Option<String>,
+}
+
+/// Represents a new maintenance event to be inserted into the database.
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = maintenance_event)]
+pub struct NewMaintenanceEvent {
+    /// Unique identifier for the new event.
+    pub id: String,
+    /// ID of the bike the work was done on.
+    pub bike_id: String,
+    /// ID of the component the work was done on.
+    pub component_id: Option<String>,
+    /// Kind of work.
+    pub kind: String,
+    /// Distance the bike's trips have covered, in meters.
+    pub odometer_meters: f64,
+    /// Free text describing the work.
+    pub note: Option<String>,
+    /// When the work was done.
+    pub performed_at: DateTime<Utc>,
+}
+
+impl NewMaintenanceEvent {
+    /// Creates a new `NewMaintenanceEvent` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `bike_id` - The ID of the bike the work was done on.
+    /// * `component_id` - An optional ID of the component the work was done on.
+    /// * `kind` - The kind of work.
+    /// * `odometer_meters` - The distance the bike's trips have covered.
+    /// * `note` - An optional description of the work.
+    ///
+    /// # Returns
+    ///
+    /// A new `NewMaintenanceEvent` instance with a generated UUID, performed now.
+    pub fn new(bike_id: &str, component_id: Option<&str>, kind: MaintenanceKind, odometer_meters: f64, note: Option<&str>) -> Self {
+        NewMaintenanceEvent {
+            id: Uuid::new_v4().to_string(),
+            bike_id: bike_id.to_string(),
+            component_id: component_id.map(|s| s.to_string()),
+            kind: kind.as_str().to_string(),
+            odometer_meters,
+            note: note.map(|s| s.to_string()),
+            performed_at: Utc::now(),
+        }
+    }
+}
+
+/// An installed component that has covered more than the service threshold.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ServiceReminder {
+    /// The worn component.
+    pub component: Component,
+    /// Distance the bike's trips have covered since the component was installed, in meters.
+    pub wear_meters: f64,
+}
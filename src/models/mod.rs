@@ -2,19 +2,33 @@ pub mod person;
 pub mod bike;
 pub mod color;
 pub mod bike_trip;
+pub mod history;
+pub mod maintenance;
+pub mod ownership;
+pub mod saved_filter;
+pub mod stats;
+pub mod trip_point;
 
 
 // Common types and enums
 pub mod common {
-    #[derive(Debug, Clone)]
+    use serde::{Deserialize, Serialize};
+    use utoipa::ToSchema;
+
+    /// The operators a string field can be filtered with.
+    #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
     pub enum StringFilter {
+        /// The field equals the value.
         Equal(String),
+        /// The field differs from the value.
         NotEqual(String),
+        /// The field matches the SQL `LIKE` pattern, `%` matches any run of characters.
         Like(String),
+        /// The field equals one of the values.
         In(Vec<String>),
     }
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
     pub enum NumberFilter<T> {
         Equal(T),
         NotEqual(T),
@@ -24,7 +38,7 @@ pub mod common {
         IsNotNull,
     }
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
     pub enum BooleanFilter {
         True,
         False,
@@ -32,11 +46,319 @@ pub mod common {
         IsNotNull,
     }
 
+    /// A position on the globe.
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+    pub struct GeoPoint {
+        /// Degrees north of the equator, -90 to 90.
+        pub latitude: f64,
+        /// Degrees east of Greenwich, -180 to 180.
+        pub longitude: f64,
+    }
+
+    /// The areas a coordinate can be filtered by.
+    #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+    pub enum GeoFilter {
+        /// Inside the box between two corners. A box whose south west corner
+        /// lies east of its north east corner wraps around the antimeridian.
+        WithinBBox { south_west: GeoPoint, north_east: GeoPoint },
+        /// At most `radius_meters` from `center` along the Earth's surface.
+        WithinRadius { center: GeoPoint, radius_meters: f64 },
+        /// Inside the polygon with these vertices, the last one connecting back
+        /// to the first. Edges run straight in latitude and longitude, taking
+        /// the short way round, so they can't cross the antimeridian.
+        // Filters saved with condition schema version 1 call it Intersects
+        #[serde(alias = "Intersects")]
+        WithinPolygon(Vec<GeoPoint>),
+    }
+
+    impl GeoPoint {
+        fn validate(&self) -> Result<(), InvalidGeoFilter> {
+            if !(-90.0..=90.0).contains(&self.latitude) || !(-180.0..=180.0).contains(&self.longitude) {
+                return Err(InvalidGeoFilter(format!("({}, {}) is not a valid position", self.latitude, self.longitude)));
+            }
+            Ok(())
+        }
+    }
+
+    impl GeoFilter {
+        /// Checks that the filter describes an area.
+        pub fn validate(&self) -> Result<(), InvalidGeoFilter> {
+            match self {
+                GeoFilter::WithinBBox { south_west, north_east } => {
+                    south_west.validate()?;
+                    north_east.validate()?;
+                    if south_west.latitude > north_east.latitude {
+                        return Err(InvalidGeoFilter("the south west corner lies north of the north east corner".to_string()));
+                    }
+                }
+                GeoFilter::WithinRadius { center, radius_meters } => {
+                    center.validate()?;
+                    if !(*radius_meters >= 0.0 && radius_meters.is_finite()) {
+                        return Err(InvalidGeoFilter(format!("{} is not a valid radius", radius_meters)));
+                    }
+                }
+                GeoFilter::WithinPolygon(vertices) => {
+                    if vertices.len() < 3 {
+                        return Err(InvalidGeoFilter("a polygon needs at least 3 vertices".to_string()));
+                    }
+                    vertices.iter().try_for_each(GeoPoint::validate)?;
+                    // An edge spanning more than half the globe would have to wrap around instead
+                    let mut edges = vertices.iter().zip(vertices.iter().cycle().skip(1));
+                    if edges.any(|(from, to)| (to.longitude - from.longitude).abs() > 180.0) {
+                        return Err(InvalidGeoFilter("polygons crossing the antimeridian are not supported".to_string()));
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// A geo filter that doesn't describe an area.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct InvalidGeoFilter(pub String);
+
+    impl std::fmt::Display for InvalidGeoFilter {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "invalid geo filter: {}", self.0)
+        }
+    }
+
+    impl std::error::Error for InvalidGeoFilter {}
+
     #[derive(Debug, Clone)]
     pub enum AndOr {
         And,
         Or,
     }
+
+    /// Which rows reads return with regard to soft deletion.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub enum DeletedMode {
+        /// Only rows that have not been deleted.
+        #[default]
+        Exclude,
+        /// Deleted and live rows alike.
+        Include,
+        /// Only rows that have been deleted.
+        Only,
+    }
+
+    /// Whether a bulk write is executed or only counted.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum WriteMode {
+        /// Apply the write and report the affected rows.
+        Execute,
+        /// Report how many rows the write would affect without applying it.
+        DryRun,
+    }
+
+    /// The direction rows are sorted in.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub enum SortDirection {
+        /// Smallest first.
+        #[default]
+        Asc,
+        /// Largest first.
+        Desc,
+    }
+
+    /// A window of a sorted result.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Page {
+        /// Maximum number of rows to return.
+        pub limit: i64,
+        /// Number of rows to skip.
+        pub offset: i64,
+    }
+
+    /// A fresh UUID, the id of new rows deserialized without one.
+    pub fn generate_id() -> String {
+        uuid::Uuid::new_v4().to_string()
+    }
+
+    /// Deserializes a present field into `Some`, so a patch field can tell an
+    /// explicit `null` (`Some(None)`) apart from a missing one (`None`).
+    pub fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: serde::Deserializer<'de>,
+    {
+        T::deserialize(deserializer).map(Some)
+    }
+
+    /// What happens to rows referencing a row that is deleted.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+    #[serde(rename_all = "snake_case")]
+    pub enum DeleteStrategy {
+        /// Refuse the delete while live rows still reference the row.
+        #[default]
+        Restrict,
+        /// Delete the live referencing rows as well, and the live rows referencing those.
+        Cascade,
+        /// Clear the reference on all referencing rows, deleted ones included.
+        SetNull,
+    }
+
+    /// Outcome of a delete with a [`DeleteStrategy`].
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ToSchema)]
+    pub struct DeleteSummary {
+        /// Number of rows deleted, 0 if the row was missing or already deleted.
+        pub deleted: usize,
+        /// Number of referencing rows deleted or cleared by the strategy.
+        pub dependents: usize,
+    }
+
+    /// The unique constraint an upsert resolves conflicts on.
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub enum ConflictTarget {
+        /// The primary key of the table (`ON CONFLICT (id)`).
+        #[default]
+        PrimaryKey,
+        /// A named unique constraint (`ON CONFLICT ON CONSTRAINT name`).
+        Constraint(String),
+    }
+
+    impl ConflictTarget {
+        /// Returns the constraint name, given the name of the table's primary key.
+        pub fn constraint_name<'a>(&'a self, primary_key: &'a str) -> &'a str {
+            match self {
+                ConflictTarget::PrimaryKey => primary_key,
+                ConflictTarget::Constraint(name) => name,
+            }
+        }
+    }
+
+    /// Configures how `upsert_many` handles rows that already exist.
+    ///
+    /// Conflicting rows get the listed columns overwritten with the new values.
+    /// With no update columns, conflicting rows are left untouched and are not
+    /// returned.
+    #[derive(Debug, Clone)]
+    pub struct Upsert<C> {
+        /// The constraint identifying conflicting rows.
+        pub conflict_target: ConflictTarget,
+        /// The columns to overwrite on conflict.
+        pub update_columns: Vec<C>,
+    }
+
+    impl<C: PartialEq> Upsert<C> {
+        /// Upserts on the primary key, overwriting the given columns.
+        pub fn on_primary_key(update_columns: Vec<C>) -> Self {
+            Upsert {
+                conflict_target: ConflictTarget::PrimaryKey,
+                update_columns,
+            }
+        }
+
+        /// Returns whether the given column is overwritten on conflict.
+        pub fn updates(&self, column: C) -> bool {
+            self.update_columns.contains(&column)
+        }
+    }
+
+    /// A group produced by a `count_by` aggregation.
+    ///
+    /// Rows that have no value for the grouped relation (e.g. bikes without a
+    /// color) are counted in a bucket whose `key` and `label` are `None`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Bucket {
+        /// ID of the grouped entity.
+        pub key: Option<String>,
+        /// Display name of the grouped entity.
+        pub label: Option<String>,
+        /// Number of rows in the group.
+        pub count: i64,
+    }
+
+    impl Bucket {
+        /// Builds buckets from `(key, count)` rows, looking up each key's label.
+        pub fn labelled(
+            counts: Vec<(Option<String>, i64)>,
+            labels: &std::collections::HashMap<String, String>,
+        ) -> Vec<Bucket> {
+            counts
+                .into_iter()
+                .map(|(key, count)| Bucket {
+                    label: key.as_ref().and_then(|k| labels.get(k).cloned()),
+                    key,
+                    count,
+                })
+                .collect()
+        }
+    }
+
+    /// How the values of a numeric column are combined within a group.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Aggregate {
+        /// Total of the values.
+        Sum,
+        /// Mean of the values.
+        Avg,
+        /// Smallest value.
+        Min,
+        /// Largest value.
+        Max,
+    }
+
+    /// A group produced by an `aggregate_by` aggregation, like `Bucket` with
+    /// the aggregated value of the group's rows.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct AggregateBucket {
+        /// ID of the grouped entity.
+        pub key: Option<String>,
+        /// Display name of the grouped entity.
+        pub label: Option<String>,
+        /// Number of rows in the group.
+        pub count: i64,
+        /// The aggregated value, `None` if no row of the group has one.
+        pub value: Option<f64>,
+    }
+
+    impl AggregateBucket {
+        /// Builds buckets from `(key, count, value)` rows, looking up each key's label.
+        pub fn labelled(
+            rows: Vec<(Option<String>, i64, Option<f64>)>,
+            labels: &std::collections::HashMap<String, String>,
+        ) -> Vec<AggregateBucket> {
+            rows.into_iter()
+                .map(|(key, count, value)| AggregateBucket {
+                    label: key.as_ref().and_then(|k| labels.get(k).cloned()),
+                    key,
+                    count,
+                    value,
+                })
+                .collect()
+        }
+    }
+
+    /// Who may filter on a field of a condition enum.
+    ///
+    /// Levels are ordered, a caller cleared for a level may filter on fields
+    /// of that level and below.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+    pub enum FieldAccess {
+        /// Any caller may filter on the field.
+        #[default]
+        Public,
+        /// Only privileged callers may filter on the field, since even yes/no
+        /// answers about it leak data.
+        Restricted,
+    }
+
+    /// A condition tree filters on a field the caller isn't cleared for.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct FieldDenied {
+        /// The condition enum and field, e.g. `PersonCondition::email`.
+        pub field: &'static str,
+    }
+
+    impl std::fmt::Display for FieldDenied {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "filtering on {} is not allowed", self.field)
+        }
+    }
+
+    impl std::error::Error for FieldDenied {}
 }
 
 // Re-export common types for easier access
@@ -0,0 +1,36 @@
+use crate::models::bike::Bike;
+use crate::models::person::Person;
+use crate::schema::ownership;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+
+/// Represents a span of time a person owned a bike.
+///
+/// Rows are written by the database whenever the owner of a bike changes, so
+/// the ranges of a bike follow each other without gaps or overlaps.
+#[derive(Debug, Clone, PartialEq, Queryable, Identifiable, Associations, Serialize)]
+#[diesel(table_name = ownership)]
+#[diesel(belongs_to(Bike))]
+#[diesel(belongs_to(Person, foreign_key = owner_id))]
+pub struct Ownership {
+    /// Unique identifier for the ownership, increasing with each change of owner.
+    pub id: i64,
+    /// ID of the owned bike.
+    pub bike_id: String,
+    /// ID of the person who owned the bike.
+    pub owner_id: String,
+    /// When the person became the owner, inclusive.
+    pub valid_from: DateTime<Utc>,
+    /// When the person stopped being the owner, exclusive. `None` for the current owner.
+    pub valid_to: Option<DateTime<Utc>>,
+    /// The tenant the ownership belongs to, `None` if it isn't scoped to a tenant.
+    pub tenant_id: Option<String>,
+}
+
+impl Ownership {
+    /// Whether the person owned the bike at `at`.
+    pub fn covers(&self, at: DateTime<Utc>) -> bool {
+        self.valid_from <= at && self.valid_to.is_none_or(|valid_to| at < valid_to)
+    }
+}
@@ -1,26 +1,53 @@
 use crate::models::common::*;
 use crate::schema::person;
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 /// Represents a person in the database.
-#[derive(Debug, Clone, Queryable, Identifiable)]
+#[derive(Debug, Clone, Queryable, Identifiable, Serialize, Deserialize, ToSchema)]
 #[diesel(table_name = person)]
 pub struct Person {
     /// Unique identifier for the person.
     pub id: String,
     /// Name of the person.
     pub name: String,
+    /// Version of the person, incremented on every update.
+    pub version: i32,
+    /// When the person was deleted, if it has been.
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// The tenant the person belongs to, `None` if it isn't scoped to a tenant.
+    pub tenant_id: Option<String>,
+    /// Email address of the person. Restricted for filtering.
+    pub email: Option<String>,
+}
+
+/// Represents a partial update of a person.
+///
+/// Fields left as `None` are not touched.
+#[derive(Debug, Clone, Default, AsChangeset, Deserialize, ToSchema)]
+#[diesel(table_name = person)]
+pub struct PersonPatch {
+    /// New name for the person.
+    pub name: Option<String>,
+    /// New email address, `Some(None)` clears it.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub email: Option<Option<String>>,
 }
 
 /// Represents a new person to be inserted into the database.
-#[derive(Debug, Clone, Insertable)]
+#[derive(Debug, Clone, Insertable, Deserialize, ToSchema)]
 #[diesel(table_name = person)]
 pub struct NewPerson {
     /// Unique identifier for the new person.
+    #[serde(default = "generate_id")]
     pub id: String,
     /// Name of the new person.
     pub name: String,
+    /// Email address of the new person.
+    pub email: Option<String>,
 }
 
 impl NewPerson {
@@ -37,6 +64,7 @@ impl NewPerson {
         NewPerson {
             id: Uuid::new_v4().to_string(),
             name: name.to_string(),
+            email: None,
         }
     }
 }
@@ -46,15 +74,90 @@ impl NewPerson {
 /// This enum is crucial for implementing dynamic filtering in the data access layer.
 /// It allows for the construction of complex query conditions at runtime,
 /// enabling flexible and powerful search capabilities for persons.
+///
+/// Condition trees are serialized into saved filters. Renamed variants must keep
+/// their old name as a `#[serde(alias = "...")]`, so filters saved before the
+/// rename still load.
 #[allow(non_camel_case_types)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub enum PersonCondition {
     /// Filter by the name of the person.
     name(StringFilter),
+    /// Filter by the ID of the person.
+    id(StringFilter),
+    /// Filter by the email address of the person.
+    email(StringFilter),
     /// Filter by conditions related to the bikes owned by the person.
     bike(Vec<crate::models::bike::BikeCondition>),
+    /// Keep persons who owned a bike matching the conditions at the given time,
+    /// according to the ownership history. Bikes deleted since still count.
+    owned_bike_at(DateTime<Utc>, Vec<crate::models::bike::BikeCondition>),
     /// Combine multiple conditions with a logical AND.
+    #[schema(no_recursion)]
     And(Vec<PersonCondition>),
     /// Combine multiple conditions with a logical OR.
+    #[schema(no_recursion)]
     Or(Vec<PersonCondition>),
-}
\ No newline at end of file
+}
+
+impl PersonCondition {
+    /// The access needed to filter on each field, `None` for combinators.
+    ///
+    /// Declared as an exhaustive match so new fields can't be added without a rule.
+    pub fn field_access(&self) -> Option<(&'static str, FieldAccess)> {
+        match self {
+            PersonCondition::name(_) => Some(("PersonCondition::name", FieldAccess::Public)),
+            PersonCondition::id(_) => Some(("PersonCondition::id", FieldAccess::Public)),
+            PersonCondition::email(_) => Some(("PersonCondition::email", FieldAccess::Restricted)),
+            PersonCondition::bike(_) => Some(("PersonCondition::bike", FieldAccess::Public)),
+            PersonCondition::owned_bike_at(..) => Some(("PersonCondition::owned_bike_at", FieldAccess::Public)),
+            PersonCondition::And(_) | PersonCondition::Or(_) => None,
+        }
+    }
+
+    /// Checks that a caller with `clearance` may filter on every field in the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `clearance` - The highest field access the caller is cleared for.
+    ///
+    /// # Returns
+    ///
+    /// `Ok` or the first field the caller may not filter on.
+    pub fn validate(&self, clearance: FieldAccess) -> Result<(), FieldDenied> {
+        if let Some((field, access)) = self.field_access() {
+            if access > clearance {
+                return Err(FieldDenied { field });
+            }
+        }
+        match self {
+            PersonCondition::bike(conditions) | PersonCondition::owned_bike_at(_, conditions) => {
+                conditions.iter().try_for_each(|c| c.validate(clearance))
+            }
+            PersonCondition::And(conditions) | PersonCondition::Or(conditions) => {
+                conditions.iter().try_for_each(|c| c.validate(clearance))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The columns persons can be sorted by.
+///
+/// Rows that tie on the column are sorted by ID, so pages don't overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PersonOrder {
+    /// Sort by name.
+    Name,
+    /// Sort by ID.
+    Id,
+}
+
+/// The updatable columns of a person, used to configure upserts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PersonColumn {
+    /// The name of the person.
+    Name,
+    /// The email address of the person.
+    Email,
+}
@@ -0,0 +1,181 @@
+use crate::models::bike::BikeCondition;
+use crate::models::bike_trip::BikeTripCondition;
+use crate::models::person::PersonCondition;
+use crate::schema::saved_filter;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use uuid::Uuid;
+
+/// Version of the JSON shape the condition enums serialize to.
+///
+/// Adding a variant doesn't change the shape of existing filters and needs no
+/// bump. Renaming a variant does: keep the old name as a `#[serde(alias)]`,
+/// bump this version and run `SavedFilterDAL::migrate`, which rewrites older
+/// rows with the new name. The alias can be dropped once every database has
+/// been migrated.
+///
+/// Version 2 renamed `GeoFilter::Intersects` to `WithinPolygon`.
+pub const CONDITION_SCHEMA_VERSION: i32 = 2;
+
+/// The entity a saved filter selects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterEntity {
+    /// The filter holds `PersonCondition`s.
+    Person,
+    /// The filter holds `BikeCondition`s.
+    Bike,
+    /// The filter holds `BikeTripCondition`s.
+    BikeTrip,
+}
+
+impl FilterEntity {
+    /// The name the entity is stored as.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FilterEntity::Person => "person",
+            FilterEntity::Bike => "bike",
+            FilterEntity::BikeTrip => "bike_trip",
+        }
+    }
+}
+
+/// A condition type whose trees can be stored in a saved filter.
+pub trait SavedCondition: Serialize + DeserializeOwned {
+    /// The entity the conditions filter.
+    const ENTITY: FilterEntity;
+}
+
+impl SavedCondition for PersonCondition {
+    const ENTITY: FilterEntity = FilterEntity::Person;
+}
+
+impl SavedCondition for BikeCondition {
+    const ENTITY: FilterEntity = FilterEntity::Bike;
+}
+
+impl SavedCondition for BikeTripCondition {
+    const ENTITY: FilterEntity = FilterEntity::BikeTrip;
+}
+
+/// Represents a named condition tree stored in the database.
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[diesel(table_name = saved_filter)]
+pub struct SavedFilter {
+    /// Unique identifier for the saved filter.
+    pub id: String,
+    /// ID of the person who saved the filter, `None` for filters shared with everyone.
+    pub owner_id: Option<String>,
+    /// Name of the entity the filter selects, see [`FilterEntity::as_str`].
+    pub entity: String,
+    /// Name of the saved filter.
+    pub name: String,
+    /// The [`CONDITION_SCHEMA_VERSION`] the conditions were written with.
+    pub schema_version: i32,
+    /// The serialized conditions, ANDed together when the filter runs.
+    pub conditions: serde_json::Value,
+    /// When the filter was saved.
+    pub created_at: DateTime<Utc>,
+    /// The tenant the filter belongs to, `None` if it isn't scoped to a tenant.
+    pub tenant_id: Option<String>,
+}
+
+impl SavedFilter {
+    /// Deserializes the stored conditions.
+    ///
+    /// # Returns
+    ///
+    /// The conditions, or an error if the filter was saved for another entity,
+    /// by a newer version of the crate, or doesn't match the condition shape.
+    pub fn conditions<C: SavedCondition>(&self) -> Result<Vec<C>, SavedFilterError> {
+        if self.entity != C::ENTITY.as_str() {
+            return Err(SavedFilterError::WrongEntity {
+                expected: C::ENTITY.as_str(),
+                found: self.entity.clone(),
+            });
+        }
+        if self.schema_version > CONDITION_SCHEMA_VERSION {
+            return Err(SavedFilterError::UnsupportedVersion(self.schema_version));
+        }
+        serde_json::from_value(self.conditions.clone()).map_err(SavedFilterError::Invalid)
+    }
+}
+
+/// Represents a new saved filter to be inserted into the database.
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = saved_filter)]
+pub struct NewSavedFilter {
+    /// Unique identifier for the new saved filter.
+    pub id: String,
+    /// Optional ID of the person saving the filter.
+    pub owner_id: Option<String>,
+    /// Name of the entity the filter selects.
+    pub entity: String,
+    /// Name of the new saved filter.
+    pub name: String,
+    /// The schema version the conditions are serialized with.
+    pub schema_version: i32,
+    /// The serialized conditions.
+    pub conditions: serde_json::Value,
+}
+
+impl NewSavedFilter {
+    /// Creates a new `NewSavedFilter` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the filter.
+    /// * `owner_id` - An optional ID of the person saving the filter.
+    /// * `conditions` - The conditions to save, the entity is taken from their type.
+    ///
+    /// # Returns
+    ///
+    /// A new `NewSavedFilter` instance with a generated UUID, written with the
+    /// current schema version.
+    pub fn new<C: SavedCondition>(name: &str, owner_id: Option<&str>, conditions: &[C]) -> Self {
+        NewSavedFilter {
+            id: Uuid::new_v4().to_string(),
+            owner_id: owner_id.map(|s| s.to_string()),
+            entity: C::ENTITY.as_str().to_string(),
+            name: name.to_string(),
+            schema_version: CONDITION_SCHEMA_VERSION,
+            conditions: serde_json::to_value(conditions).expect("Conditions always serialize"),
+        }
+    }
+}
+
+/// Why the conditions of a saved filter couldn't be loaded.
+#[derive(Debug)]
+pub enum SavedFilterError {
+    /// The filter selects another entity.
+    WrongEntity {
+        /// The entity the caller asked for.
+        expected: &'static str,
+        /// The entity the filter was saved for.
+        found: String,
+    },
+    /// The filter was written with a schema version this build doesn't know.
+    UnsupportedVersion(i32),
+    /// The stored JSON doesn't match the condition enums.
+    Invalid(serde_json::Error),
+}
+
+impl fmt::Display for SavedFilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SavedFilterError::WrongEntity { expected, found } => {
+                write!(f, "saved filter selects {}, not {}", found, expected)
+            }
+            SavedFilterError::UnsupportedVersion(version) => write!(
+                f,
+                "saved filter has schema version {}, newer than {}",
+                version, CONDITION_SCHEMA_VERSION
+            ),
+            SavedFilterError::Invalid(error) => write!(f, "invalid saved filter: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for SavedFilterError {}
@@ -0,0 +1,69 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// The time span statistics cover, from `from` up to but excluding `to`.
+///
+/// Trips are placed in time by the start of their recorded track, so trips
+/// without one fall outside every window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TimeWindow {
+    /// Start of the window, inclusive.
+    pub from: DateTime<Utc>,
+    /// End of the window, exclusive.
+    pub to: DateTime<Utc>,
+}
+
+impl TimeWindow {
+    /// The window of `days` days starting at `from`.
+    pub fn days(from: DateTime<Utc>, days: i64) -> Self {
+        TimeWindow { from, to: from + Duration::days(days) }
+    }
+}
+
+/// The total leaderboard rows are ranked by, largest first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RankBy {
+    /// The number of trips.
+    TripCount,
+    /// The distance covered.
+    #[default]
+    Distance,
+    /// The time spent moving.
+    MovingTime,
+    /// The distance of the longest trip.
+    LongestRide,
+}
+
+/// The totals of a person or bike over a time window, ranked among its peers.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct LeaderboardRow {
+    /// Position on the leaderboard. Tied rows share a rank, and the rank after
+    /// them skips the positions they took, so `1, 2, 2, 4`.
+    pub rank: i64,
+    /// ID of the person or bike.
+    pub id: String,
+    /// Name of the person or bike.
+    pub name: String,
+    /// Number of trips in the window.
+    pub trip_count: i64,
+    /// Distance of the trips, in meters.
+    pub distance_meters: f64,
+    /// Time spent moving on the trips, in seconds.
+    pub moving_seconds: i64,
+    /// Distance of the longest trip, in meters.
+    pub longest_ride_meters: f64,
+}
+
+impl LeaderboardRow {
+    /// The total ranked by `rank_by`, comparable across rows.
+    pub fn score(&self, rank_by: RankBy) -> f64 {
+        match rank_by {
+            RankBy::TripCount => self.trip_count as f64,
+            RankBy::Distance => self.distance_meters,
+            RankBy::MovingTime => self.moving_seconds as f64,
+            RankBy::LongestRide => self.longest_ride_meters,
+        }
+    }
+}
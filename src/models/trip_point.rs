@@ -0,0 +1,44 @@
+use crate::models::bike_trip::BikeTrip;
+use crate::schema::trip_point;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Represents a sample of the track a bike trip was recorded with.
+#[derive(Debug, Clone, PartialEq, Queryable, Identifiable, Associations, Serialize, Deserialize, ToSchema)]
+#[diesel(table_name = trip_point)]
+#[diesel(primary_key(trip_id, seq))]
+#[diesel(belongs_to(BikeTrip, foreign_key = trip_id))]
+pub struct TripPoint {
+    /// ID of the bike trip the point was recorded on.
+    pub trip_id: String,
+    /// Position of the point in the track, starting at 0.
+    pub seq: i32,
+    /// When the point was recorded, if the device logged it.
+    pub recorded_at: Option<DateTime<Utc>>,
+    /// Latitude in degrees.
+    pub latitude: f64,
+    /// Longitude in degrees.
+    pub longitude: f64,
+    /// Elevation in meters, if the device logged it.
+    pub elevation: Option<f64>,
+}
+
+/// Represents a new trip point to be inserted into the database.
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = trip_point)]
+pub struct NewTripPoint {
+    /// ID of the bike trip the point belongs to.
+    pub trip_id: String,
+    /// Position of the point in the track.
+    pub seq: i32,
+    /// When the point was recorded.
+    pub recorded_at: Option<DateTime<Utc>>,
+    /// Latitude in degrees.
+    pub latitude: f64,
+    /// Longitude in degrees.
+    pub longitude: f64,
+    /// Elevation in meters.
+    pub elevation: Option<f64>,
+}
@@ -6,6 +6,21 @@ diesel::table! {
         name -> Text,
         owner_id -> Nullable<Text>,
         color_id -> Nullable<Text>,
+        version -> Int4,
+        deleted_at -> Nullable<Timestamptz>,
+        tenant_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    bike_history (history_id) {
+        history_id -> Int8,
+        row_id -> Text,
+        operation -> Text,
+        old_values -> Nullable<Jsonb>,
+        new_values -> Nullable<Jsonb>,
+        actor_id -> Nullable<Text>,
+        changed_at -> Timestamptz,
     }
 }
 
@@ -14,6 +29,26 @@ diesel::table! {
         id -> Text,
         name -> Text,
         bike_id -> Nullable<Text>,
+        version -> Int4,
+        deleted_at -> Nullable<Timestamptz>,
+        tenant_id -> Nullable<Text>,
+        started_at -> Nullable<Timestamptz>,
+        distance_meters -> Nullable<Float8>,
+        duration_seconds -> Nullable<Int8>,
+        elevation_gain_meters -> Nullable<Float8>,
+        moving_seconds -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    bike_trip_history (history_id) {
+        history_id -> Int8,
+        row_id -> Text,
+        operation -> Text,
+        old_values -> Nullable<Jsonb>,
+        new_values -> Nullable<Jsonb>,
+        actor_id -> Nullable<Text>,
+        changed_at -> Timestamptz,
     }
 }
 
@@ -21,6 +56,57 @@ diesel::table! {
     color (id) {
         id -> Text,
         name -> Text,
+        version -> Int4,
+        deleted_at -> Nullable<Timestamptz>,
+        tenant_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    color_history (history_id) {
+        history_id -> Int8,
+        row_id -> Text,
+        operation -> Text,
+        old_values -> Nullable<Jsonb>,
+        new_values -> Nullable<Jsonb>,
+        actor_id -> Nullable<Text>,
+        changed_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    component (id) {
+        id -> Text,
+        bike_id -> Text,
+        kind -> Text,
+        installed_at -> Timestamptz,
+        installed_at_meters -> Float8,
+        removed_at -> Nullable<Timestamptz>,
+        tenant_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    maintenance_event (id) {
+        id -> Text,
+        bike_id -> Text,
+        component_id -> Nullable<Text>,
+        kind -> Text,
+        odometer_meters -> Float8,
+        note -> Nullable<Text>,
+        performed_at -> Timestamptz,
+        tenant_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    ownership (id) {
+        id -> Int8,
+        bike_id -> Text,
+        owner_id -> Text,
+        valid_from -> Timestamptz,
+        valid_to -> Nullable<Timestamptz>,
+        tenant_id -> Nullable<Text>,
     }
 }
 
@@ -28,16 +114,72 @@ diesel::table! {
     person (id) {
         id -> Text,
         name -> Text,
+        version -> Int4,
+        deleted_at -> Nullable<Timestamptz>,
+        tenant_id -> Nullable<Text>,
+        email -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    person_history (history_id) {
+        history_id -> Int8,
+        row_id -> Text,
+        operation -> Text,
+        old_values -> Nullable<Jsonb>,
+        new_values -> Nullable<Jsonb>,
+        actor_id -> Nullable<Text>,
+        changed_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    saved_filter (id) {
+        id -> Text,
+        owner_id -> Nullable<Text>,
+        entity -> Text,
+        name -> Text,
+        schema_version -> Int4,
+        conditions -> Jsonb,
+        created_at -> Timestamptz,
+        tenant_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    trip_point (trip_id, seq) {
+        trip_id -> Text,
+        seq -> Int4,
+        recorded_at -> Nullable<Timestamptz>,
+        latitude -> Float8,
+        longitude -> Float8,
+        elevation -> Nullable<Float8>,
     }
 }
 
 diesel::joinable!(bike -> color (color_id));
 diesel::joinable!(bike -> person (owner_id));
 diesel::joinable!(bike_trip -> bike (bike_id));
+diesel::joinable!(component -> bike (bike_id));
+diesel::joinable!(maintenance_event -> bike (bike_id));
+diesel::joinable!(maintenance_event -> component (component_id));
+diesel::joinable!(ownership -> bike (bike_id));
+diesel::joinable!(ownership -> person (owner_id));
+diesel::joinable!(saved_filter -> person (owner_id));
+diesel::joinable!(trip_point -> bike_trip (trip_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     bike,
+    bike_history,
     bike_trip,
+    bike_trip_history,
     color,
+    color_history,
+    component,
+    maintenance_event,
+    ownership,
     person,
+    person_history,
+    saved_filter,
+    trip_point,
 );
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::io::Write;
+use crate::dal::DataAccessLayer;
+use crate::models::bike::BikeCondition;
+use crate::models::bike_trip::BikeTripCondition;
+use crate::models::common::StringFilter;
+use crate::models::person::PersonCondition;
+use crate::transfer::record::{BikeRecord, BikeTripRecord, ColorRecord, PersonRecord};
+use crate::transfer::{write_records, FileFormat, TransferError};
+
+/// Writes the persons matching `conditions` to `writer`, sorted by name
+///
+/// # Returns
+///
+/// The number of persons written
+pub fn export_persons(
+    dal: &DataAccessLayer,
+    conditions: Vec<PersonCondition>,
+    format: FileFormat,
+    writer: impl Write,
+) -> Result<usize, TransferError> {
+    let mut records: Vec<PersonRecord> = dal
+        .person()
+        .find_with_filters(conditions)?
+        .into_iter()
+        .map(|person| PersonRecord { name: person.name, email: person.email })
+        .collect();
+    records.sort_by(|a, b| a.name.cmp(&b.name));
+
+    write_records(format, &records, writer)?;
+    Ok(records.len())
+}
+
+/// Writes all colors to `writer`, sorted by name
+///
+/// Colors have no condition type, so unlike the other exports this one can't
+/// be filtered.
+pub fn export_colors(dal: &DataAccessLayer, format: FileFormat, writer: impl Write) -> Result<usize, TransferError> {
+    let mut records: Vec<ColorRecord> = dal
+        .color()
+        .find_all()?
+        .into_iter()
+        .map(|color| ColorRecord { name: color.name })
+        .collect();
+    records.sort_by(|a, b| a.name.cmp(&b.name));
+
+    write_records(format, &records, writer)?;
+    Ok(records.len())
+}
+
+/// Writes the bikes matching `conditions` to `writer`, sorted by name, naming
+/// their owner and color
+pub fn export_bikes(
+    dal: &DataAccessLayer,
+    conditions: Vec<BikeCondition>,
+    format: FileFormat,
+    writer: impl Write,
+) -> Result<usize, TransferError> {
+    let mut records: Vec<BikeRecord> = dal
+        .bike()
+        .find_rows_with_filters(conditions)?
+        .into_iter()
+        .map(|row| BikeRecord { name: row.name, owner: row.owner_name, color: row.color_name })
+        .collect();
+    records.sort_by(|a, b| a.name.cmp(&b.name));
+
+    write_records(format, &records, writer)?;
+    Ok(records.len())
+}
+
+/// Writes the bike trips matching `conditions` to `writer`, sorted by name,
+/// naming their bike
+pub fn export_bike_trips(
+    dal: &DataAccessLayer,
+    conditions: Vec<BikeTripCondition>,
+    format: FileFormat,
+    writer: impl Write,
+) -> Result<usize, TransferError> {
+    let trips = dal.bike_trip().find_with_filters(conditions)?;
+
+    let bike_ids: Vec<String> = trips.iter().filter_map(|trip| trip.bike_id.clone()).collect();
+    let bike_names: HashMap<String, String> = match bike_ids.is_empty() {
+        true => HashMap::new(),
+        false => dal
+            .bike()
+            .find_with_filters(vec![BikeCondition::id(StringFilter::In(bike_ids))])?
+            .into_iter()
+            .map(|bike| (bike.id, bike.name))
+            .collect(),
+    };
+
+    let mut records: Vec<BikeTripRecord> = trips
+        .into_iter()
+        .map(|trip| BikeTripRecord {
+            bike: trip.bike_id.and_then(|bike_id| bike_names.get(&bike_id).cloned()),
+            name: trip.name,
+        })
+        .collect();
+    records.sort_by(|a, b| a.name.cmp(&b.name));
+
+    write_records(format, &records, writer)?;
+    Ok(records.len())
+}
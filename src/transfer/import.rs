@@ -0,0 +1,209 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use diesel::QueryResult;
+use crate::dal::DataAccessLayer;
+use crate::models::bike::{BikeCondition, NewBike};
+use crate::models::bike_trip::NewBikeTrip;
+use crate::models::color::NewColor;
+use crate::models::common::StringFilter;
+use crate::models::person::{NewPerson, PersonCondition};
+use crate::transfer::record::{BikeRecord, BikeTripRecord, ColorRecord, PersonRecord};
+use crate::transfer::{read_records, FileFormat, ImportMode, ImportReport, Records, RowError, TransferError};
+
+/// The IDs of the live rows carrying each name
+struct NaturalKeys {
+    /// What the names refer to, for error messages
+    what: &'static str,
+    ids: HashMap<String, Vec<String>>,
+}
+
+impl NaturalKeys {
+    /// Keys from `(name, id)` pairs
+    fn new(what: &'static str, rows: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut ids: HashMap<String, Vec<String>> = HashMap::new();
+        for (name, row_id) in rows {
+            ids.entry(name).or_default().push(row_id);
+        }
+        NaturalKeys { what, ids }
+    }
+
+    /// The ID of the one row named `name`
+    fn resolve(&self, name: Option<String>) -> Result<Option<String>, String> {
+        let Some(name) = name else {
+            return Ok(None);
+        };
+        match self.ids.get(&name).map(Vec::as_slice) {
+            Some([row_id]) => Ok(Some(row_id.clone())),
+            Some(row_ids) => Err(format!("{} `{}` is ambiguous, {} rows have that name", self.what, name, row_ids.len())),
+            None => Err(format!("unknown {} `{}`", self.what, name)),
+        }
+    }
+}
+
+/// `value` unless it's missing or blank
+fn present(value: Option<String>) -> Option<String> {
+    value.filter(|value| !value.trim().is_empty())
+}
+
+fn required_name(name: String) -> Result<String, String> {
+    present(Some(name)).ok_or_else(|| "name is required".to_string())
+}
+
+/// The distinct names `records` refer to through `field`
+fn referenced<T>(records: &Records<T>, field: impl Fn(&T) -> &Option<String>) -> Vec<String> {
+    let names: HashSet<&String> = records.iter().filter_map(|(_, record)| field(record).as_ref()).collect();
+    names.into_iter().cloned().collect()
+}
+
+/// Inserts the valid rows as `mode` demands and reports the others
+fn insert<N>(
+    mode: ImportMode,
+    rows: Vec<(usize, Result<N, String>)>,
+    mut failed: Vec<RowError>,
+    create_many: impl FnOnce(&[N]) -> QueryResult<usize>,
+    create: impl Fn(&N) -> QueryResult<()>,
+) -> Result<ImportReport, TransferError> {
+    let mut valid = Vec::with_capacity(rows.len());
+    for (line, row) in rows {
+        match row {
+            Ok(row) => valid.push((line, row)),
+            Err(message) => failed.push(RowError { line, message }),
+        }
+    }
+
+    let imported = match mode {
+        ImportMode::AllOrNothing if !failed.is_empty() => 0,
+        ImportMode::AllOrNothing => {
+            let rows: Vec<N> = valid.into_iter().map(|(_, row)| row).collect();
+            create_many(&rows)?
+        }
+        ImportMode::BestEffort => {
+            let mut imported = 0;
+            for (line, row) in valid {
+                match create(&row) {
+                    Ok(()) => imported += 1,
+                    Err(error @ diesel::result::Error::DatabaseError(..)) => {
+                        failed.push(RowError { line, message: error.to_string() })
+                    }
+                    Err(error) => return Err(error.into()),
+                }
+            }
+            imported
+        }
+    };
+
+    failed.sort_by_key(|error| error.line);
+    Ok(ImportReport { imported, failed })
+}
+
+/// Imports persons from `reader`
+///
+/// A missing or blank email imports as `None`.
+pub fn import_persons(dal: &DataAccessLayer, format: FileFormat, mode: ImportMode, reader: impl Read) -> Result<ImportReport, TransferError> {
+    let (records, failed) = read_records::<PersonRecord>(format, reader)?;
+
+    let rows = records
+        .into_iter()
+        .map(|(line, record)| {
+            let row = required_name(record.name).map(|name| NewPerson { email: present(record.email), ..NewPerson::new(&name) });
+            (line, row)
+        })
+        .collect();
+
+    let persons = dal.person();
+    insert(mode, rows, failed, |rows| Ok(persons.create_many(rows)?.len()), |row| persons.create(row).map(drop))
+}
+
+/// Imports colors from `reader`
+///
+/// Colors are referred to by name, so a row naming an existing color, or a
+/// color named earlier in the file, fails.
+pub fn import_colors(dal: &DataAccessLayer, format: FileFormat, mode: ImportMode, reader: impl Read) -> Result<ImportReport, TransferError> {
+    let (records, failed) = read_records::<ColorRecord>(format, reader)?;
+
+    let colors = dal.color();
+    let mut taken: HashSet<String> = colors.find_all()?.into_iter().map(|color| color.name).collect();
+    let rows = records
+        .into_iter()
+        .map(|(line, record)| {
+            let row = required_name(record.name).and_then(|name| match taken.insert(name.clone()) {
+                true => Ok(NewColor::new(&name)),
+                false => Err(format!("color `{}` already exists", name)),
+            });
+            (line, row)
+        })
+        .collect();
+
+    insert(mode, rows, failed, |rows| Ok(colors.create_many(rows)?.len()), |row| colors.create(row).map(drop))
+}
+
+/// Imports bikes from `reader`, resolving the `owner` and `color` names
+pub fn import_bikes(dal: &DataAccessLayer, format: FileFormat, mode: ImportMode, reader: impl Read) -> Result<ImportReport, TransferError> {
+    let (records, failed) = read_records::<BikeRecord>(format, reader)?;
+    let records: Records<BikeRecord> = records
+        .into_iter()
+        .map(|(line, record)| (line, BikeRecord { owner: present(record.owner), color: present(record.color), ..record }))
+        .collect();
+
+    let owner_names = referenced(&records, |record| &record.owner);
+    let owners = match owner_names.is_empty() {
+        true => NaturalKeys::new("owner", []),
+        false => NaturalKeys::new(
+            "owner",
+            dal.person()
+                .find_with_filters(vec![PersonCondition::name(StringFilter::In(owner_names))])?
+                .into_iter()
+                .map(|person| (person.name, person.id)),
+        ),
+    };
+    let colors = NaturalKeys::new("color", dal.color().find_all()?.into_iter().map(|color| (color.name, color.id)));
+
+    let rows = records
+        .into_iter()
+        .map(|(line, record)| {
+            let row = (|| {
+                let name = required_name(record.name)?;
+                let owner_id = owners.resolve(record.owner)?;
+                let color_id = colors.resolve(record.color)?;
+                Ok(NewBike::new(&name, owner_id.as_deref(), color_id.as_deref()))
+            })();
+            (line, row)
+        })
+        .collect();
+
+    let bikes = dal.bike();
+    insert(mode, rows, failed, |rows| Ok(bikes.create_many(rows)?.len()), |row| bikes.create(row).map(drop))
+}
+
+/// Imports bike trips from `reader`, resolving the `bike` names
+pub fn import_bike_trips(dal: &DataAccessLayer, format: FileFormat, mode: ImportMode, reader: impl Read) -> Result<ImportReport, TransferError> {
+    let (records, failed) = read_records::<BikeTripRecord>(format, reader)?;
+    let records: Records<BikeTripRecord> = records
+        .into_iter()
+        .map(|(line, record)| (line, BikeTripRecord { bike: present(record.bike), ..record }))
+        .collect();
+
+    let bike_names = referenced(&records, |record| &record.bike);
+    let bikes = match bike_names.is_empty() {
+        true => NaturalKeys::new("bike", []),
+        false => NaturalKeys::new(
+            "bike",
+            dal.bike()
+                .find_with_filters(vec![BikeCondition::name(StringFilter::In(bike_names))])?
+                .into_iter()
+                .map(|bike| (bike.name, bike.id)),
+        ),
+    };
+
+    let rows = records
+        .into_iter()
+        .map(|(line, record)| {
+            let row = required_name(record.name)
+                .and_then(|name| Ok(NewBikeTrip::new(&name, bikes.resolve(record.bike)?.as_deref())));
+            (line, row)
+        })
+        .collect();
+
+    let trips = dal.bike_trip();
+    insert(mode, rows, failed, |rows| Ok(trips.create_many(rows)?.len()), |row| trips.create(row).map(drop))
+}
@@ -0,0 +1,208 @@
+//! Import and export of rows as CSV or NDJSON files
+//!
+//! Files refer to other rows by their natural key rather than their ID: a bike
+//! names its owner and color, a bike trip names its bike. Imports resolve those
+//! names against the live rows the data access layer can see.
+
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use clap::ValueEnum;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+mod export;
+mod import;
+mod record;
+
+pub use export::{export_bike_trips, export_bikes, export_colors, export_persons};
+pub use import::{import_bike_trips, import_bikes, import_colors, import_persons};
+pub use record::{BikeRecord, BikeTripRecord, ColorRecord, PersonRecord};
+
+/// The file formats rows are imported from and exported to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum FileFormat {
+    /// CSV with a header row naming the columns
+    #[default]
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+}
+
+impl FileFormat {
+    /// The format a file is in judging by its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "csv" => Some(FileFormat::Csv),
+            "ndjson" | "jsonl" => Some(FileFormat::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+/// What an import does with the valid rows when some rows fail
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Insert every valid row and report the others.
+    #[default]
+    BestEffort,
+    /// Insert nothing unless every row is valid, then insert all rows in one
+    /// transaction.
+    AllOrNothing,
+}
+
+/// A row that couldn't be imported
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RowError {
+    /// Line of the row in the file, starting at 1
+    pub line: usize,
+    pub message: String,
+}
+
+/// The outcome of an import
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ImportReport {
+    /// Number of rows inserted
+    pub imported: usize,
+    /// The rows that failed, ordered by line
+    pub failed: Vec<RowError>,
+}
+
+impl ImportReport {
+    /// Whether every row of the file was imported
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Error aborting a whole import or export, as opposed to a single row
+#[derive(Debug)]
+pub enum TransferError {
+    /// Reading or writing the file failed.
+    Io(std::io::Error),
+    /// The CSV writer failed.
+    Csv(csv::Error),
+    /// The JSON writer failed.
+    Json(serde_json::Error),
+    /// Looking up or inserting rows failed.
+    Database(diesel::result::Error),
+}
+
+impl From<std::io::Error> for TransferError {
+    fn from(error: std::io::Error) -> Self {
+        TransferError::Io(error)
+    }
+}
+
+impl From<csv::Error> for TransferError {
+    fn from(error: csv::Error) -> Self {
+        TransferError::Csv(error)
+    }
+}
+
+impl From<serde_json::Error> for TransferError {
+    fn from(error: serde_json::Error) -> Self {
+        TransferError::Json(error)
+    }
+}
+
+impl From<diesel::result::Error> for TransferError {
+    fn from(error: diesel::result::Error) -> Self {
+        TransferError::Database(error)
+    }
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransferError::Io(error) => write!(f, "{}", error),
+            TransferError::Csv(error) => write!(f, "{}", error),
+            TransferError::Json(error) => write!(f, "{}", error),
+            TransferError::Database(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for TransferError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TransferError::Io(error) => Some(error),
+            TransferError::Csv(error) => Some(error),
+            TransferError::Json(error) => Some(error),
+            TransferError::Database(error) => Some(error),
+        }
+    }
+}
+
+/// Rows read from a file, each with its line
+type Records<T> = Vec<(usize, T)>;
+
+/// Reads the records of `reader`, reporting rows that don't parse
+///
+/// Only failures to read the file abort; a malformed row is reported and skipped.
+fn read_records<T: DeserializeOwned>(format: FileFormat, reader: impl Read) -> Result<(Records<T>, Vec<RowError>), TransferError> {
+    let mut records = Vec::new();
+    let mut failed = Vec::new();
+    match format {
+        FileFormat::Csv => {
+            let mut csv = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(reader);
+            let headers = csv.headers()?.clone();
+            for result in csv.records() {
+                let record = match result {
+                    Ok(record) => record,
+                    Err(error) if error.is_io_error() => return Err(error.into()),
+                    Err(error) => {
+                        let line = error.position().map_or(0, |position| position.line() as usize);
+                        failed.push(RowError { line, message: error.to_string() });
+                        continue;
+                    }
+                };
+                let line = record.position().map_or(0, |position| position.line() as usize);
+                match record.deserialize(Some(&headers)) {
+                    Ok(parsed) => records.push((line, parsed)),
+                    Err(error) => {
+                        let message = match error.kind() {
+                            csv::ErrorKind::Deserialize { err, .. } => err.to_string(),
+                            _ => error.to_string(),
+                        };
+                        failed.push(RowError { line, message });
+                    }
+                }
+            }
+        }
+        FileFormat::Ndjson => {
+            for (index, text) in BufReader::new(reader).lines().enumerate() {
+                let text = text?;
+                if text.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str(&text) {
+                    Ok(parsed) => records.push((index + 1, parsed)),
+                    Err(error) => failed.push(RowError { line: index + 1, message: error.to_string() }),
+                }
+            }
+        }
+    }
+    Ok((records, failed))
+}
+
+/// Writes `records` to `writer` in `format`
+fn write_records<T: Serialize>(format: FileFormat, records: &[T], mut writer: impl Write) -> Result<(), TransferError> {
+    match format {
+        FileFormat::Csv => {
+            let mut csv = csv::Writer::from_writer(writer);
+            for record in records {
+                csv.serialize(record)?;
+            }
+            csv.flush()?;
+        }
+        FileFormat::Ndjson => {
+            for record in records {
+                serde_json::to_writer(&mut writer, record)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+/// A person as a row of an import or export file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PersonRecord {
+    pub name: String,
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
+/// A color as a row of an import or export file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColorRecord {
+    pub name: String,
+}
+
+/// A bike as a row of an import or export file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BikeRecord {
+    pub name: String,
+    /// Name of the person owning the bike
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// Name of the bike's color
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+/// A bike trip as a row of an import or export file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BikeTripRecord {
+    pub name: String,
+    /// Name of the bike the trip was taken with
+    #[serde(default)]
+    pub bike: Option<String>,
+}
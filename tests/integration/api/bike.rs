@@ -0,0 +1,85 @@
+use axum::http::{Method, StatusCode};
+use axum::response::IntoResponse;
+use serde_json::json;
+use pedal_pal::api::{self, ApiError, Tenancy};
+use crate::api::{dispatch, send, send_via_proxy};
+use crate::fixtures::TestFixture;
+
+#[tokio::test]
+async fn test_api_bike_search() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+
+    let conditions = json!([{
+        "Or": [
+            { "color": { "Equal": "Red" } },
+            { "name": { "Like": "Road%" } }
+        ]
+    }]);
+    let (status, bikes) = send(&fixture, Method::POST, "/bikes/search", Some(conditions), &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    let mut names: Vec<_> = bikes.as_array().unwrap().iter().map(|b| b["name"].as_str().unwrap()).collect();
+    names.sort();
+    assert_eq!(names, vec!["Mountain Bike", "Road Bike"]);
+}
+
+#[tokio::test]
+async fn test_api_bike_references_and_tenants() {
+    let fixture = TestFixture::new();
+
+    let tenant = [("x-tenant-id", "club-a")];
+    let (status, bike) = send_via_proxy(&fixture, Method::POST, "/bikes", Some(json!({ "name": "Club Bike" })), &tenant).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(bike["tenant_id"], "club-a");
+
+    let uri = format!("/bikes/{}", bike["id"].as_str().unwrap());
+    assert_eq!(send_via_proxy(&fixture, Method::GET, &uri, None, &tenant).await.0, StatusCode::OK);
+    assert_eq!(send_via_proxy(&fixture, Method::GET, &uri, None, &[("x-tenant-id", "club-b")]).await.0, StatusCode::NOT_FOUND);
+
+    // Last, as the violation aborts the test transaction
+    let missing_owner = json!({ "name": "Ghost Bike", "owner_id": "missing" });
+    let (status, _) = send(&fixture, Method::POST, "/bikes", Some(missing_owner), &[]).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn test_api_identity_headers_fail_closed() {
+    let fixture = TestFixture::new();
+    let tenant = [("x-tenant-id", "club-a")];
+    let (_, bike) = send_via_proxy(&fixture, Method::POST, "/bikes", Some(json!({ "name": "Club Bike" })), &tenant).await;
+    let uri = format!("/bikes/{}", bike["id"].as_str().unwrap());
+
+    // Behind the proxy, a request without a tenant sees nothing rather than every tenant
+    let (status, _) = send_via_proxy(&fixture, Method::GET, &uri, None, &[]).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = send_via_proxy(&fixture, Method::GET, "/bikes", None, &[("x-actor-id", "admin")]).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // A single-tenant server serves its own tenant and doesn't let callers pick one or pose as an actor
+    let single = api::router(fixture.dal().for_tenant("club-b"), Tenancy::Single);
+    let (status, _) = dispatch(single.clone(), Method::GET, &uri, None, &[]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = dispatch(single.clone(), Method::GET, &uri, None, &tenant).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = dispatch(single, Method::GET, &uri, None, &[("x-actor-id", "admin")]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_api_errors_share_the_error_body() {
+    let fixture = TestFixture::new();
+
+    // Body rejections come as an ErrorBody, not as plain text
+    let (status, body) = send(&fixture, Method::POST, "/bikes", Some(json!({ "owner_id": "nobody" })), &[]).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body["error"].as_str().unwrap().contains("missing field `name`"), "{}", body);
+    let (status, body) = send(&fixture, Method::POST, "/bikes/search", Some(json!("color = Red")), &[]).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body["error"].is_string(), "{}", body);
+
+    // Unexpected database errors don't leak their details
+    let response = ApiError::from(diesel::result::Error::BrokenTransactionManager).into_response();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(serde_json::from_slice::<serde_json::Value>(&bytes).unwrap(), json!({ "error": "internal server error" }));
+}
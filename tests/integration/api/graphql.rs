@@ -0,0 +1,104 @@
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+use pedal_pal::api::{self, Tenancy};
+use pedal_pal::models::common::FieldAccess;
+use pedal_pal::models::maintenance::ComponentKind;
+use pedal_pal::models::person::{NewPerson, PersonPatch};
+use crate::api::{dispatch, send};
+use crate::fixtures::TestFixture;
+
+async fn query(fixture: &TestFixture, query: &str, headers: &[(&str, &str)]) -> Value {
+    let (status, response) = send(fixture, Method::POST, "/graphql", Some(json!({ "query": query })), headers).await;
+    assert_eq!(status, StatusCode::OK);
+    response
+}
+
+#[tokio::test]
+async fn test_graphql_persons_nested() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+    fixture.setup_bike_trips();
+
+    let response = query(&fixture, r#"{
+        persons(filter: { bike: [{ color: { equal: "Blue" } }] }, order: { field: NAME, direction: DESC }) {
+            name
+            bikes { name color { name } trips { name } }
+        }
+    }"#, &[]).await;
+    assert!(response["errors"].is_null(), "{}", response);
+
+    let persons = response["data"]["persons"].as_array().unwrap();
+    let names: Vec<_> = persons.iter().map(|p| p["name"].as_str().unwrap()).collect();
+    assert_eq!(names, vec!["Bob", "Alice"]);
+    let alice_colors: Vec<_> = persons[1]["bikes"].as_array().unwrap().iter().map(|b| b["color"]["name"].as_str().unwrap()).collect();
+    assert_eq!(alice_colors.len(), 2);
+    assert!(alice_colors.contains(&"Red") && alice_colors.contains(&"Blue"));
+
+    // Two mountain bikes exist, only the ownerless one from the trip fixture has trips
+    let response = query(&fixture, r#"{ bikes(filter: { name: { equal: "Mountain Bike" } }) { color { name } trips { name } } }"#, &[]).await;
+    assert!(response["errors"].is_null(), "{}", response);
+    let trip_counts: Vec<_> = response["data"]["bikes"].as_array().unwrap().iter().map(|b| b["trips"].as_array().unwrap().len()).collect();
+    assert_eq!(trip_counts.iter().sum::<usize>(), 5);
+}
+
+#[tokio::test]
+async fn test_graphql_paging_and_errors() {
+    let fixture = TestFixture::new();
+    fixture.setup_people();
+
+    let response = query(&fixture, r#"{ persons(page: { limit: 2, offset: 1 }) { name } }"#, &[]).await;
+    assert_eq!(response["data"]["persons"], json!([{ "name": "Bob" }, { "name": "Charlie" }]));
+
+    // Restricted fields can't be filtered on, and pages are capped
+    let response = query(&fixture, r#"{ persons(filter: { email: { like: "%" } }) { name } }"#, &[]).await;
+    assert!(response["errors"][0]["message"].as_str().unwrap().contains("PersonCondition::email"));
+    let response = query(&fixture, r#"{ persons(page: { limit: 1000 }) { name } }"#, &[]).await;
+    assert!(response["errors"].is_array());
+}
+
+#[tokio::test]
+async fn test_graphql_email_needs_clearance() {
+    let fixture = TestFixture::new();
+    let alice = fixture.dal().person().create(&NewPerson::new("Alice")).unwrap();
+    let patch = PersonPatch { email: Some(Some("alice@example.com".to_string())), ..Default::default() };
+    fixture.dal().person().update_partial(&alice.id, &patch).unwrap();
+    let body = Some(json!({ "query": "{ persons { name email } }" }));
+
+    // Reading the address reveals more than filtering on it, so it takes the same clearance
+    let response = query(&fixture, "{ persons { name email } }", &[]).await;
+    assert_eq!(response["data"]["persons"], json!([{ "name": "Alice", "email": null }]), "{}", response);
+    let cleared = api::router(fixture.dal().with_field_access(FieldAccess::Restricted), Tenancy::Single);
+    let (_, response) = dispatch(cleared, Method::POST, "/graphql", body, &[]).await;
+    assert_eq!(response["data"]["persons"], json!([{ "name": "Alice", "email": "alice@example.com" }]), "{}", response);
+}
+
+#[tokio::test]
+async fn test_graphql_bikes_needing_service() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+    let dal = fixture.dal();
+    let bikes = dal.bike().find_all().unwrap();
+    let city = bikes.iter().find(|bike| bike.name == "City Bike").unwrap();
+    dal.maintenance().install(&city.id, ComponentKind::BrakePads, None).unwrap();
+    let gpx = std::fs::File::open(format!("{}/tests/fixtures/gpx/paused_ride.gpx", env!("CARGO_MANIFEST_DIR"))).unwrap();
+    dal.bike_trip().import_gpx(&city.id, gpx).unwrap();
+
+    let response = query(&fixture, r#"{ bikes(filter: { needsService: 200 }) { name } }"#, &[]).await;
+    assert_eq!(response["data"]["bikes"], json!([{ "name": "City Bike" }]), "{}", response);
+    let response = query(&fixture, r#"{ persons(filter: { bike: [{ needsService: 300 }] }) { name } }"#, &[]).await;
+    assert_eq!(response["data"]["persons"], json!([]), "{}", response);
+}
+
+#[tokio::test]
+async fn test_graphql_persons_owning_a_bike_at() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+
+    let response = query(&fixture, r#"{ persons(filter: { ownedBikeAt: { at: "2100-01-01T00:00:00Z", bike: [{ color: { equal: "Red" } }] } }) { name } }"#, &[]).await;
+    assert_eq!(response["data"]["persons"], json!([{ "name": "Alice" }]), "{}", response);
+    let response = query(&fixture, r#"{ persons(filter: { ownedBikeAt: { at: "2100-01-01T00:00:00Z" } }, order: { field: NAME }) { name } }"#, &[]).await;
+    assert_eq!(response["data"]["persons"], json!([{ "name": "Alice" }, { "name": "Bob" }]), "{}", response);
+    // Nobody owned a bike before the fixture created them
+    let response = query(&fixture, r#"{ persons(filter: { ownedBikeAt: { at: "2000-01-01T00:00:00Z" } }) { name } }"#, &[]).await;
+    assert_eq!(response["data"]["persons"], json!([]), "{}", response);
+}
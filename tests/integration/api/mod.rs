@@ -0,0 +1,56 @@
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use axum::Router;
+use pedal_pal::api::{self, Tenancy};
+use serde_json::Value;
+use tower::ServiceExt;
+use crate::fixtures::TestFixture;
+
+mod bike;
+mod graphql;
+mod openapi;
+mod person;
+
+/// Sends a request to a single-tenant API over the fixture's test transaction
+pub async fn send(
+    fixture: &TestFixture,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+    headers: &[(&str, &str)],
+) -> (StatusCode, Value) {
+    dispatch(api::router(fixture.dal(), Tenancy::Single), method, uri, body, headers).await
+}
+
+/// Sends a request to an API behind a trusted proxy, which sets the identity headers
+pub async fn send_via_proxy(
+    fixture: &TestFixture,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+    headers: &[(&str, &str)],
+) -> (StatusCode, Value) {
+    dispatch(api::router(fixture.dal(), Tenancy::TrustedProxy), method, uri, body, headers).await
+}
+
+pub async fn dispatch(app: Router, method: Method, uri: &str, body: Option<Value>, headers: &[(&str, &str)]) -> (StatusCode, Value) {
+    let mut request = Request::builder().method(method).uri(uri);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let request = match body {
+        Some(body) => request
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    }
+    .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    // Rejections of the path and query extractors are plain text
+    let body = serde_json::from_slice(&bytes)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()));
+    (status, body)
+}
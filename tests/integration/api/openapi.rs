@@ -0,0 +1,33 @@
+use axum::http::{Method, StatusCode};
+use pedal_pal::api::ApiDoc;
+use utoipa::OpenApi;
+use crate::api::send;
+use crate::fixtures::TestFixture;
+
+const SNAPSHOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots/openapi.json");
+
+/// Changes to the endpoints or the condition enums show up as a diff of the
+/// checked in document. Run with `UPDATE_SNAPSHOTS=1` to accept them.
+#[test]
+fn test_openapi_snapshot() {
+    let document = ApiDoc::openapi().to_pretty_json().unwrap() + "\n";
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(SNAPSHOT, &document).unwrap();
+    }
+    let snapshot = std::fs::read_to_string(SNAPSHOT).unwrap();
+    assert!(snapshot == document, "openapi.json is out of date, rerun with UPDATE_SNAPSHOTS=1 and review the diff");
+}
+
+#[tokio::test]
+async fn test_openapi_served() {
+    let fixture = TestFixture::new();
+    let (status, document) = send(&fixture, Method::GET, "/openapi.json", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(document, serde_json::to_value(ApiDoc::openapi()).unwrap());
+
+    // The string operators are listed for the bike filters
+    let filter = &document["components"]["schemas"]["StringFilter"]["oneOf"];
+    let operators: Vec<_> = filter.as_array().unwrap().iter().flat_map(|v| v["properties"].as_object().unwrap().keys()).collect();
+    assert_eq!(operators, vec!["Equal", "NotEqual", "Like", "In"]);
+    assert!(document["paths"]["/bikes/search"]["post"].is_object());
+}
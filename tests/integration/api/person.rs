@@ -0,0 +1,68 @@
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+use crate::api::send;
+use crate::fixtures::TestFixture;
+
+#[tokio::test]
+async fn test_api_person_crud() {
+    let fixture = TestFixture::new();
+
+    let (status, alice) = send(&fixture, Method::POST, "/persons", Some(json!({ "name": "Alice" })), &[]).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let id = alice["id"].as_str().unwrap().to_string();
+    let uri = format!("/persons/{}", id);
+
+    let (status, found) = send(&fixture, Method::GET, &uri, None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(found["name"], "Alice");
+
+    let (status, patched) = send(&fixture, Method::PATCH, &uri, Some(json!({ "email": "alice@example.com" })), &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(patched["version"], 2);
+
+    // A full update based on the stale version conflicts and returns the current row
+    let mut stale = alice.clone();
+    stale["name"] = json!("Alicia");
+    let (status, body) = send(&fixture, Method::PUT, &uri, Some(stale), &[]).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["current"]["email"], "alice@example.com");
+
+    // An explicit null clears the email
+    let (_, cleared) = send(&fixture, Method::PATCH, &uri, Some(json!({ "email": null })), &[]).await;
+    assert!(cleared["email"].is_null());
+
+    let (status, summary) = send(&fixture, Method::DELETE, &uri, None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(summary, json!({ "deleted": 1, "dependents": 0 }));
+    assert_eq!(send(&fixture, Method::GET, &uri, None, &[]).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(send(&fixture, Method::DELETE, &uri, None, &[]).await.0, StatusCode::NOT_FOUND);
+
+    let (status, restored) = send(&fixture, Method::POST, &format!("{}/restore", uri), None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(restored["deleted_at"].is_null());
+}
+
+#[tokio::test]
+async fn test_api_person_errors() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+    let (_, people) = send(&fixture, Method::GET, "/persons", None, &[]).await;
+    let alice = people.as_array().unwrap().iter().find(|p| p["name"] == "Alice").unwrap();
+    let uri = format!("/persons/{}", alice["id"].as_str().unwrap());
+
+    // Alice still owns bikes
+    assert_eq!(send(&fixture, Method::DELETE, &uri, None, &[]).await.0, StatusCode::CONFLICT);
+    let (status, summary) = send(&fixture, Method::DELETE, &format!("{}?strategy=cascade", uri), None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(summary["dependents"], 2);
+
+    // Filtering on a restricted field is forbidden
+    let conditions = json!([{ "email": { "Like": "%@example.com" } }]);
+    let (status, body) = send(&fixture, Method::POST, "/persons/search", Some(conditions), &[]).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body["error"].as_str().unwrap().contains("PersonCondition::email"));
+
+    // Malformed condition trees are rejected before reaching the database
+    let (status, _) = send(&fixture, Method::POST, "/persons/search", Some(json!([{ "age": 3 }])), &[]).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
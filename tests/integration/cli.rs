@@ -0,0 +1,117 @@
+use clap::Parser;
+use pedal_pal::cli::{parse_filter, Cli};
+use pedal_pal::models::bike::BikeCondition;
+use pedal_pal::models::bike_trip::BikeTripCondition;
+use pedal_pal::models::common::StringFilter;
+use pedal_pal::models::person::PersonCondition;
+use crate::fixtures::TestFixture;
+
+fn run(fixture: &TestFixture, args: &[&str]) -> String {
+    let cli = Cli::try_parse_from(["pedal_pal", "--database-url", "postgres://localhost/unused"].into_iter().chain(args.iter().copied())).unwrap();
+    let dal = cli.scope(fixture.dal());
+    let mut out = Vec::new();
+    cli.command.execute(&dal, cli.format, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_parse_filter() {
+    let condition: BikeCondition = parse_filter(r#"color = "Red" and name like "Road%" or owner_id in ("a", "b")"#).unwrap();
+    match condition {
+        BikeCondition::Or(conditions) => {
+            assert!(matches!(&conditions[0], BikeCondition::And(and) if and.len() == 2));
+            assert!(matches!(&conditions[1], BikeCondition::owner_id(StringFilter::In(ids)) if ids.len() == 2));
+        }
+        other => panic!("expected an Or, got {:?}", other),
+    }
+
+    let condition: PersonCondition = parse_filter(r#"(name != "Bob") AND bike.color = "Blue \"ish\"""#).unwrap();
+    match condition {
+        PersonCondition::And(conditions) => match &conditions[1] {
+            PersonCondition::bike(bikes) => {
+                assert!(matches!(&bikes[0], BikeCondition::color(StringFilter::Equal(color)) if color == "Blue \"ish\""));
+            }
+            other => panic!("expected a bike condition, got {:?}", other),
+        },
+        other => panic!("expected an And, got {:?}", other),
+    }
+
+    let error = parse_filter::<BikeCondition>(r#"colour = "Red""#).unwrap_err();
+    assert_eq!((error.position, error.message.as_str()), (0, "unknown field `colour`"));
+    let error = parse_filter::<BikeCondition>(r#"color = "Red" name = "x""#).unwrap_err();
+    assert_eq!(error.position, 14);
+    assert!(parse_filter::<BikeCondition>(r#"color = Red"#).is_err());
+    assert!(parse_filter::<BikeCondition>(r#"(color = "Red""#).is_err());
+
+    let condition: BikeCondition = parse_filter(r#"needs_service(500) and color = "Red""#).unwrap();
+    assert!(matches!(&condition, BikeCondition::And(and) if matches!(and[0], BikeCondition::needs_service(meters) if meters == 500.0)));
+    let condition: BikeTripCondition = parse_filter(r#"bike.needs_service("2500.5")"#).unwrap();
+    assert!(matches!(condition, BikeTripCondition::bike(BikeCondition::needs_service(meters)) if meters == 2500.5));
+    let error = parse_filter::<BikeCondition>(r#"color = "Red" or needs_service(far)"#).unwrap_err();
+    assert_eq!((error.position, error.message.as_str()), (17, "`needs_service` takes a distance in meters"));
+    let error = parse_filter::<PersonCondition>(r#"needs_service(500)"#).unwrap_err();
+    assert_eq!(error.message, "unknown function `needs_service`");
+
+    let condition: PersonCondition = parse_filter(r#"owned_bike_at("2024-10-05T12:00:00Z", color = "Red" or needs_service(10))"#).unwrap();
+    match condition {
+        PersonCondition::owned_bike_at(at, bikes) => {
+            assert_eq!(at.to_rfc3339(), "2024-10-05T12:00:00+00:00");
+            assert!(matches!(&bikes[..], [BikeCondition::Or(or)] if or.len() == 2));
+        }
+        other => panic!("expected an owned_bike_at condition, got {:?}", other),
+    }
+    assert!(matches!(parse_filter(r#"owned_bike_at("2024-10-05T12:00:00Z")"#), Ok(PersonCondition::owned_bike_at(_, bikes)) if bikes.is_empty()));
+    let error = parse_filter::<PersonCondition>(r#"owned_bike_at("yesterday")"#).unwrap_err();
+    assert_eq!(error.message, "`owned_bike_at` takes an RFC 3339 timestamp");
+    let error = parse_filter::<BikeCondition>(r#"needs_service(10, color = "Red")"#).unwrap_err();
+    assert_eq!(error.message, "`needs_service` takes a single argument");
+}
+
+#[test]
+fn test_cli_commands() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+
+    let table = run(&fixture, &["bikes", "list", "--filter", r#"color = "Red""#, "--format", "table"]);
+    let lines: Vec<_> = table.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("id "));
+    assert!(lines[1].contains("Mountain Bike"));
+
+    let json = run(&fixture, &["person", "create", "Alice", "--email", "alice@example.com", "--format", "json"]);
+    let created: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(created[0]["email"], "alice@example.com");
+
+    let csv = run(&fixture, &["persons", "list", "--filter", r#"name = "Alice""#, "--format", "csv"]);
+    assert_eq!(csv.lines().count(), 3);
+    assert_eq!(csv.lines().next().unwrap(), "id,name,version,deleted_at,tenant_id,email");
+
+    // Tenants come from the global flag
+    run(&fixture, &["--tenant", "club-a", "color", "create", "Teal"]);
+    assert_eq!(run(&fixture, &["colors", "list", "--tenant", "club-b", "--format", "csv"]), "");
+    assert!(run(&fixture, &["colors", "list", "--tenant", "club-a"]).contains("Teal"));
+}
+
+#[test]
+fn test_cli_import_export() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+
+    let path = std::env::temp_dir().join(format!("pedal_pal_import_{}.ndjson", std::process::id()));
+    std::fs::write(&path, "{\"name\": \"Gravel Bike\", \"owner\": \"Bob\", \"color\": \"Red\"}\n").unwrap();
+    let output = run(&fixture, &["import", "bikes", path.to_str().unwrap(), "--all-or-nothing"]);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(output, "imported 1 rows\n");
+
+    let csv = run(&fixture, &["export", "bike", "--filter", r#"color = "Red""#]);
+    assert_eq!(csv, "name,owner,color\nGravel Bike,Bob,Red\nMountain Bike,Alice,Red\n");
+}
+
+#[test]
+fn test_cli_requires_database_url() {
+    // Only meaningful without DATABASE_URL, which clap would fall back to
+    if std::env::var_os("DATABASE_URL").is_none() {
+        let error = Cli::try_parse_from(["pedal_pal", "bike", "list"]).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+    }
+}
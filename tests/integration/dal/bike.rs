@@ -1,12 +1,17 @@
-use pedal_pal::models::bike::{NewBike, Condition};
-use pedal_pal::models::person::NewPerson;
+use pedal_pal::models::bike::{Bike, NewBike, BikeColumn, BikePatch, BikeCondition, BikeGroup, Include};
+use pedal_pal::models::bike_trip::{BikeTripCondition, BikeTripPatch, NewBikeTrip};
+use pedal_pal::models::person::PersonCondition;
 use pedal_pal::models::color::NewColor;
-use pedal_pal::models::common::StringFilter;
+use pedal_pal::models::person::NewPerson;
+use pedal_pal::models::common::{DeleteStrategy, DeleteSummary, StringFilter, Upsert, WriteMode};
+use std::sync::Arc;
+use pedal_pal::dal::{Action, DeleteError, Policy, PolicyDenied, UpdateError};
+use pedal_pal::models::history::Operation;
+use diesel::result::{DatabaseErrorKind, Error};
 use crate::fixtures::TestFixture;
 
 fn setup() -> TestFixture {
     let fixture = TestFixture::new();
-    let dal = fixture.dal();
 
     // Create persons
     let alice = fixture.create_person("Alice");
@@ -97,7 +102,7 @@ fn test_bike_filter_by_name() {
     let fixture = setup();
     let dal = fixture.dal();
 
-    let conditions = vec![Condition::name(StringFilter::Equal("Mountain Bike".to_string()))];
+    let conditions = vec![BikeCondition::name(StringFilter::Equal("Mountain Bike".to_string()))];
     let bikes = dal.bike().find_with_filters(conditions).unwrap();
     assert_eq!(bikes.len(), 1);
     assert_eq!(bikes[0].name, "Mountain Bike");
@@ -108,7 +113,7 @@ fn test_bike_filter_by_color() {
     let fixture = setup();
     let dal = fixture.dal();
 
-    let conditions = vec![Condition::color(StringFilter::Equal("Red".to_string()))];
+    let conditions = vec![BikeCondition::color(StringFilter::Equal("Red".to_string()))];
     let bikes = dal.bike().find_with_filters(conditions).unwrap();
     assert_eq!(bikes.len(), 2);
 }
@@ -119,8 +124,8 @@ fn test_bike_filter_combined() {
     let dal = fixture.dal();
 
     let conditions = vec![
-        Condition::name(StringFilter::Equal("City Bike".to_string())),
-        Condition::color(StringFilter::Equal("Green".to_string())),
+        BikeCondition::name(StringFilter::Equal("City Bike".to_string())),
+        BikeCondition::color(StringFilter::Equal("Green".to_string())),
     ];
     let bikes = dal.bike().find_with_filters(conditions).unwrap();
     assert_eq!(bikes.len(), 1);
@@ -132,7 +137,7 @@ fn test_bike_filter_no_results() {
     let fixture = setup();
     let dal = fixture.dal();
 
-    let conditions = vec![Condition::name(StringFilter::Equal("Nonexistent Bike".to_string()))];
+    let conditions = vec![BikeCondition::name(StringFilter::Equal("Nonexistent Bike".to_string()))];
     let bikes = dal.bike().find_with_filters(conditions).unwrap();
     assert_eq!(bikes.len(), 0);
 }
@@ -142,7 +147,7 @@ fn test_bike_filter_like() {
     let fixture = setup();
     let dal = fixture.dal();
 
-    let conditions = vec![Condition::name(StringFilter::Like("%Bike%".to_string()))];
+    let conditions = vec![BikeCondition::name(StringFilter::Like("%Bike%".to_string()))];
     let bikes = dal.bike().find_with_filters(conditions).unwrap();
     assert_eq!(bikes.len(), 4);
 }
@@ -152,7 +157,7 @@ fn test_bike_filter_in() {
     let fixture = setup();
     let dal = fixture.dal();
 
-    let conditions = vec![Condition::name(StringFilter::In(vec![
+    let conditions = vec![BikeCondition::name(StringFilter::In(vec![
         "Mountain Bike".to_string(),
         "Road Bike".to_string(),
     ]))];
@@ -165,9 +170,660 @@ fn test_bike_filter_no_owner() {
     let fixture = setup();
     let dal = fixture.dal();
 
-    let conditions = vec![Condition::name(StringFilter::Equal("BMX Bike".to_string()))];
+    let conditions = vec![BikeCondition::name(StringFilter::Equal("BMX Bike".to_string()))];
     let bikes = dal.bike().find_with_filters(conditions).unwrap();
     assert_eq!(bikes.len(), 1);
     assert_eq!(bikes[0].name, "BMX Bike");
     assert_eq!(bikes[0].owner_id, None);
-}
\ No newline at end of file
+}
+#[test]
+fn test_bike_to_sql_string() {
+    let fixture = setup();
+    let dal = fixture.dal();
+
+    let conditions = vec![BikeCondition::color(StringFilter::Equal("Red".to_string()))];
+    let sql = dal.bike().to_sql_string(conditions).unwrap();
+    assert!(sql.contains("LEFT OUTER JOIN \"color\""));
+    assert!(sql.contains("\"color\".\"name\" = $1"));
+    assert!(sql.contains("\"Red\""));
+}
+
+#[test]
+fn test_bike_explain_with_filters() {
+    let fixture = setup();
+    let dal = fixture.dal();
+
+    let conditions = vec![BikeCondition::color(StringFilter::Equal("Red".to_string()))];
+    let plan = dal.bike().explain_with_filters(conditions.clone(), false).unwrap();
+    assert_eq!(plan.sql, dal.bike().to_sql_string(conditions.clone()).unwrap());
+    assert!(plan.seq_scans.contains(&"bike".to_string()));
+    assert!(plan.actual_rows.is_none());
+    assert!(plan.execution_time_ms.is_none());
+
+    let analyzed = dal.bike().explain_with_filters(conditions, true).unwrap();
+    assert_eq!(analyzed.actual_rows, Some(2));
+    assert!(analyzed.execution_time_ms.is_some());
+}
+
+#[test]
+fn test_bike_find_rows_with_filters() {
+    let fixture = setup();
+    let dal = fixture.dal();
+
+    let conditions = vec![BikeCondition::color(StringFilter::Equal("Red".to_string()))];
+    let mut rows = dal.bike().find_rows_with_filters(conditions).unwrap();
+    rows.sort_by(|a, b| a.name.cmp(&b.name));
+    assert_eq!(rows.len(), 2);
+
+    assert_eq!(rows[0].name, "BMX Bike");
+    assert_eq!(rows[0].owner_name, None);
+    assert_eq!(rows[0].color_name.as_deref(), Some("Red"));
+
+    assert_eq!(rows[1].name, "Mountain Bike");
+    assert_eq!(rows[1].owner_name.as_deref(), Some("Alice"));
+    assert_eq!(rows[1].color_name.as_deref(), Some("Red"));
+}
+
+#[test]
+fn test_bike_find_with_filters_including() {
+    let fixture = setup();
+    let dal = fixture.dal();
+
+    let mountain_bike = dal
+        .bike()
+        .find_with_filters(vec![BikeCondition::name(StringFilter::Equal("Mountain Bike".to_string()))])
+        .unwrap()
+        .remove(0);
+    for trip in ["Morning Ride", "Evening Ride"] {
+        dal.bike_trip().create(&NewBikeTrip::new(trip, Some(&mountain_bike.id))).unwrap();
+    }
+
+    let conditions = vec![BikeCondition::color(StringFilter::Equal("Red".to_string()))];
+    let mut bikes = dal
+        .bike()
+        .find_with_filters_including(conditions, Include::Color | Include::Owner | Include::Trips)
+        .unwrap();
+    bikes.sort_by(|a, b| a.bike.name.cmp(&b.bike.name));
+    assert_eq!(bikes.len(), 2);
+
+    assert_eq!(bikes[0].bike.name, "BMX Bike");
+    assert_eq!(bikes[0].color.as_ref().unwrap().name, "Red");
+    assert!(bikes[0].owner.is_none());
+    assert!(bikes[0].trips.is_empty());
+
+    assert_eq!(bikes[1].bike.name, "Mountain Bike");
+    assert_eq!(bikes[1].color.as_ref().unwrap().name, "Red");
+    assert_eq!(bikes[1].owner.as_ref().unwrap().name, "Alice");
+    assert_eq!(bikes[1].trips.len(), 2);
+}
+
+#[test]
+fn test_bike_find_with_filters_including_only_requested() {
+    let fixture = setup();
+    let dal = fixture.dal();
+
+    let conditions = vec![BikeCondition::name(StringFilter::Equal("Road Bike".to_string()))];
+    let bikes = dal.bike().find_with_filters_including(conditions, Include::Owner).unwrap();
+    assert_eq!(bikes.len(), 1);
+    assert_eq!(bikes[0].owner.as_ref().unwrap().name, "Bob");
+    assert!(bikes[0].color.is_none());
+}
+
+#[test]
+fn test_bike_count_by_color() {
+    let fixture = setup();
+    let dal = fixture.dal();
+
+    fixture.create_bike("Unpainted Bike", None, None);
+
+    let buckets = dal.bike().count_by(vec![], BikeGroup::Color).unwrap();
+    assert_eq!(buckets.len(), 4);
+    assert_eq!(buckets[0].label.as_deref(), Some("Red"));
+    assert_eq!(buckets[0].count, 2);
+
+    let unpainted = buckets.iter().find(|b| b.key.is_none()).unwrap();
+    assert_eq!(unpainted.label, None);
+    assert_eq!(unpainted.count, 1);
+}
+
+#[test]
+fn test_bike_count_by_owner_with_filters() {
+    let fixture = setup();
+    let dal = fixture.dal();
+
+    let conditions = vec![BikeCondition::Or(vec![
+        BikeCondition::color(StringFilter::Equal("Red".to_string())),
+        BikeCondition::color(StringFilter::Equal("Green".to_string())),
+    ])];
+    let buckets = dal.bike().count_by(conditions, BikeGroup::Owner).unwrap();
+    assert_eq!(buckets.len(), 2);
+    assert_eq!(buckets[0].label.as_deref(), Some("Alice"));
+    assert_eq!(buckets[0].count, 2);
+    assert_eq!(buckets[1].key, None);
+    assert_eq!(buckets[1].count, 1);
+}
+
+#[test]
+fn test_bike_update_where() {
+    let fixture = setup();
+    let dal = fixture.dal();
+
+    let conditions = vec![BikeCondition::color(StringFilter::Equal("Red".to_string()))];
+    let patch = BikePatch {
+        owner_id: Some(None),
+        ..Default::default()
+    };
+
+    let would_update = dal.bike().update_where(conditions.clone(), &patch, WriteMode::DryRun).unwrap();
+    assert_eq!(would_update, 2);
+    let mountain_bike = dal
+        .bike()
+        .find_with_filters(vec![BikeCondition::name(StringFilter::Equal("Mountain Bike".to_string()))])
+        .unwrap();
+    assert!(mountain_bike[0].owner_id.is_some());
+
+    let updated = dal.bike().update_where(conditions.clone(), &patch, WriteMode::Execute).unwrap();
+    assert_eq!(updated, 2);
+    let red_bikes = dal.bike().find_with_filters(conditions).unwrap();
+    assert!(red_bikes.iter().all(|b| b.owner_id.is_none()));
+    assert!(red_bikes.iter().all(|b| b.color_id.is_some()));
+}
+
+#[test]
+fn test_bike_delete_where() {
+    let fixture = setup();
+    let dal = fixture.dal();
+
+    let conditions = vec![BikeCondition::color(StringFilter::In(vec![
+        "Red".to_string(),
+        "Blue".to_string(),
+    ]))];
+
+    let would_delete = dal.bike().delete_where(conditions.clone(), WriteMode::DryRun).unwrap();
+    assert_eq!(would_delete, 3);
+    assert_eq!(dal.bike().find_all().unwrap().len(), 4);
+
+    let deleted = dal.bike().delete_where(conditions, WriteMode::Execute).unwrap();
+    assert_eq!(deleted, 3);
+    let remaining = dal.bike().find_all().unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].name, "City Bike");
+}
+
+#[test]
+fn test_bike_update_partial() {
+    let fixture = setup();
+    let dal = fixture.dal();
+
+    let bikes = dal.bike().find_with_filters(vec![BikeCondition::name(StringFilter::Equal("Mountain Bike".to_string()))]).unwrap();
+    let mountain_bike = &bikes[0];
+
+    // Two clients editing different fields don't clobber each other
+    let renamed = dal.bike().update_partial(&mountain_bike.id, &BikePatch {
+        name: Some("Trail Bike".to_string()),
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(renamed.owner_id, mountain_bike.owner_id);
+
+    let disowned = dal.bike().update_partial(&mountain_bike.id, &BikePatch {
+        owner_id: Some(None),
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(disowned.name, "Trail Bike");
+    assert_eq!(disowned.owner_id, None);
+    assert_eq!(disowned.color_id, mountain_bike.color_id);
+}
+
+#[test]
+fn test_bike_create_many() {
+    let fixture = TestFixture::new();
+    let dal = fixture.dal();
+
+    // Enough bikes to need more than one batch
+    let new_bikes: Vec<NewBike> = (0..20_000)
+        .map(|i| NewBike::new(&format!("Bike {}", i), None, None))
+        .collect();
+    let created = dal.bike().create_many(&new_bikes).unwrap();
+    assert_eq!(created.len(), 20_000);
+    assert_eq!(dal.bike().find_all().unwrap().len(), 20_000);
+}
+
+#[test]
+fn test_bike_upsert_many() {
+    let fixture = setup();
+    let dal = fixture.dal();
+
+    let existing = dal.bike().find_with_filters(vec![BikeCondition::name(StringFilter::Equal("Road Bike".to_string()))]).unwrap().remove(0);
+    let rows = vec![
+        NewBike {
+            id: existing.id.clone(),
+            name: "Gravel Bike".to_string(),
+            owner_id: None,
+            color_id: None,
+        },
+        NewBike::new("Tandem", None, None),
+    ];
+
+    let upserted = dal.bike().upsert_many(&rows, &Upsert::on_primary_key(vec![BikeColumn::Name])).unwrap();
+    assert_eq!(upserted.len(), 2);
+    let updated = dal.bike().find_by_id(&existing.id).unwrap();
+    assert_eq!(updated.name, "Gravel Bike");
+    assert_eq!(updated.owner_id, existing.owner_id);
+    assert_eq!(updated.color_id, existing.color_id);
+
+    // Without update columns existing rows are skipped
+    let rows = vec![NewBike { name: "Ignored".to_string(), ..rows[0].clone() }, NewBike::new("Cargo Bike", None, None)];
+    let upserted = dal.bike().upsert_many(&rows, &Upsert::on_primary_key(vec![])).unwrap();
+    assert_eq!(upserted.len(), 1);
+    assert_eq!(upserted[0].name, "Cargo Bike");
+    assert_eq!(dal.bike().find_by_id(&existing.id).unwrap().name, "Gravel Bike");
+    assert_eq!(dal.bike().find_all().unwrap().len(), 6);
+}
+
+#[test]
+fn test_bike_update_version_conflict() {
+    let fixture = setup();
+    let dal = fixture.dal();
+
+    let bike = dal.bike().find_all().unwrap().remove(0);
+    dal.bike().update_partial(&bike.id, &BikePatch {
+        color_id: Some(None),
+        ..Default::default()
+    }).unwrap();
+
+    // The full update was based on the version before the partial update
+    let mut updated_bike = bike.clone();
+    updated_bike.name = "Renamed".to_string();
+    let result = dal.bike().update(&bike.id, &updated_bike);
+    assert!(matches!(result, Err(UpdateError::Conflict(ref current)) if current.color_id.is_none()));
+
+    let current = dal.bike().find_by_id(&bike.id).unwrap();
+    assert_eq!(current.name, bike.name);
+    assert_eq!(current.version, bike.version + 1);
+}
+
+#[test]
+fn test_bike_soft_delete_where_and_restore() {
+    let fixture = setup();
+    let dal = fixture.dal();
+
+    let conditions = vec![BikeCondition::color(StringFilter::Equal("Red".to_string()))];
+    assert_eq!(dal.bike().delete_where(conditions.clone(), WriteMode::Execute).unwrap(), 2);
+    assert!(dal.bike().find_with_filters(conditions.clone()).unwrap().is_empty());
+    assert_eq!(dal.bike().find_all().unwrap().len(), 2);
+
+    // Deleting again affects nothing, even when deleted rows are visible
+    assert_eq!(dal.bike().include_deleted().delete_where(conditions.clone(), WriteMode::DryRun).unwrap(), 0);
+
+    let deleted = dal.bike().only_deleted().find_with_filters(conditions.clone()).unwrap();
+    assert_eq!(deleted.len(), 2);
+    // Deleted bikes can't be updated until they're restored
+    let renamed = BikePatch { name: Some("Renamed".to_string()), ..Default::default() };
+    assert!(matches!(dal.bike().update_partial(&deleted[0].id, &renamed), Err(UpdateError::Database(Error::NotFound))));
+    assert!(matches!(dal.bike().update(&deleted[0].id, &deleted[0]), Err(UpdateError::Database(Error::NotFound))));
+    let updated = dal.bike().include_deleted().update_where(conditions.clone(), &renamed, WriteMode::Execute).unwrap();
+    assert_eq!(updated, 0);
+    let untouched = dal.bike().only_deleted().find_with_filters(conditions.clone()).unwrap();
+    assert!(untouched.iter().all(|b| b.name != "Renamed" && deleted.iter().any(|d| d.id == b.id && d.version == b.version)));
+    for b in &deleted {
+        dal.bike().restore(&b.id).unwrap();
+    }
+    assert_eq!(dal.bike().find_with_filters(conditions).unwrap().len(), 2);
+}
+
+#[test]
+fn test_bike_history_records_actor() {
+    let fixture = setup();
+    let dal = fixture.dal().with_actor("admin");
+
+    let carol = fixture.create_person("Carol");
+    let bike = dal.bike().find_all().unwrap().remove(0);
+    dal.bike().update_partial(&bike.id, &BikePatch {
+        owner_id: Some(Some(carol.id.clone())),
+        ..Default::default()
+    }).unwrap();
+
+    let history = dal.bike().history(&bike.id).unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].operation, Operation::Insert);
+    assert_eq!(history[0].actor_id, None);
+
+    let change = &history[1];
+    assert_eq!(change.operation, Operation::Update);
+    assert_eq!(change.actor_id.as_deref(), Some("admin"));
+    assert_eq!(change.old.as_ref().unwrap().owner_id, bike.owner_id);
+    assert_eq!(change.new.as_ref().unwrap().owner_id, Some(carol.id));
+}
+
+#[test]
+fn test_bike_as_of() {
+    let fixture = setup();
+    let dal = fixture.dal();
+
+    let bike = dal.bike().find_all().unwrap().remove(0);
+    dal.bike().update_partial(&bike.id, &BikePatch {
+        name: Some("Renamed".to_string()),
+        ..Default::default()
+    }).unwrap();
+
+    let history = dal.bike().history(&bike.id).unwrap();
+    let created_at = history[0].changed_at;
+    let renamed_at = history[1].changed_at;
+
+    let before = created_at - chrono::Duration::seconds(1);
+    assert!(dal.bike().as_of(&bike.id, before).unwrap().is_none());
+    assert_eq!(dal.bike().as_of(&bike.id, created_at).unwrap().unwrap().name, bike.name);
+    assert_eq!(dal.bike().as_of(&bike.id, renamed_at).unwrap().unwrap().name, "Renamed");
+}
+
+#[test]
+fn test_bike_delete_with_cascade() {
+    let fixture = TestFixture::new();
+    fixture.setup_bike_trips();
+    let dal = fixture.dal();
+
+    let bike = dal.bike().find_all().unwrap().remove(0);
+    let restricted = dal.bike().delete_with(&bike.id, DeleteStrategy::Restrict);
+    assert!(matches!(restricted, Err(DeleteError::Restricted(5))));
+
+    let summary = dal.bike().delete_with(&bike.id, DeleteStrategy::Cascade).unwrap();
+    assert_eq!(summary, DeleteSummary { deleted: 1, dependents: 5 });
+    assert!(dal.bike_trip().find_all().unwrap().is_empty());
+    assert_eq!(dal.bike_trip().only_deleted().find_all().unwrap().len(), 5);
+}
+
+fn setup_tenants() -> TestFixture {
+    let fixture = TestFixture::new();
+    for tenant in ["club-a", "club-b"] {
+        let dal = fixture.dal().for_tenant(tenant);
+        let owner = dal.person().create(&NewPerson::new(&format!("{} owner", tenant))).unwrap();
+        let red = dal.color().create(&NewColor::new("Red")).unwrap();
+        dal.bike().create(&NewBike::new(&format!("{} bike", tenant), Some(&owner.id), Some(&red.id))).unwrap();
+    }
+    fixture
+}
+
+#[test]
+fn test_bike_tenant_filters_cannot_escape_via_or() {
+    let fixture = setup_tenants();
+    let dal = fixture.dal().for_tenant("club-a");
+
+    let conditions = vec![BikeCondition::Or(vec![
+        BikeCondition::name(StringFilter::Equal("club-b bike".to_string())),
+        BikeCondition::color(StringFilter::Equal("Red".to_string())),
+    ])];
+    let bikes = dal.bike().find_with_filters(conditions.clone()).unwrap();
+    assert_eq!(bikes.len(), 1);
+    assert_eq!(bikes[0].name, "club-a bike");
+    assert_eq!(bikes[0].tenant_id.as_deref(), Some("club-a"));
+
+    assert_eq!(dal.bike().delete_where(conditions, WriteMode::DryRun).unwrap(), 1);
+
+    // Without a tenant every bike is visible
+    assert_eq!(fixture.dal().bike().find_all().unwrap().len(), 2);
+}
+
+#[test]
+fn test_bike_tenant_cannot_read_or_write_other_tenants() {
+    let fixture = setup_tenants();
+    let dal = fixture.dal().for_tenant("club-a");
+    let other = fixture.dal().for_tenant("club-b").bike().find_all().unwrap().remove(0);
+
+    assert!(matches!(dal.bike().find_by_id(&other.id), Err(diesel::result::Error::NotFound)));
+    let result = dal.bike().update(&other.id, &other);
+    assert!(matches!(result, Err(UpdateError::Database(diesel::result::Error::NotFound))));
+    let patch = BikePatch { name: Some("Stolen".to_string()), ..Default::default() };
+    assert!(dal.bike().update_partial(&other.id, &patch).is_err());
+    assert_eq!(dal.bike().delete(&other.id).unwrap(), 0);
+
+    // An upsert colliding with the other tenant's bike leaves it alone
+    let colliding = NewBike { id: other.id.clone(), name: "Stolen".to_string(), owner_id: None, color_id: None };
+    let upserted = dal.bike().upsert_many(&[colliding], &Upsert::on_primary_key(vec![BikeColumn::Name])).unwrap();
+    assert!(upserted.is_empty());
+
+    let unchanged = fixture.dal().bike().find_by_id(&other.id).unwrap();
+    assert_eq!(unchanged.name, other.name);
+    assert_eq!(unchanged.version, other.version);
+}
+
+#[test]
+fn test_bike_relations_in_other_tenants_are_not_loaded() {
+    let fixture = setup_tenants();
+    let club_b = fixture.dal().for_tenant("club-b");
+    let owner = club_b.person().find_all().unwrap().remove(0);
+    let red = club_b.color().find_all().unwrap().remove(0);
+    // A club-a bike pointing at the owner and color of club-b
+    fixture.execute(&format!(
+        "UPDATE bike SET owner_id = '{}', color_id = '{}' WHERE tenant_id = 'club-a'",
+        owner.id, red.id
+    ));
+    club_b.bike_trip().create(&NewBikeTrip::new("club-b trip", None)).unwrap();
+    let dal = fixture.dal().for_tenant("club-a");
+    let bike = dal.bike().find_all().unwrap().remove(0);
+    fixture.execute(&format!("UPDATE bike_trip SET bike_id = '{}' WHERE tenant_id = 'club-b'", bike.id));
+
+    let rows = dal.bike().find_rows_with_filters(vec![]).unwrap();
+    assert_eq!((rows[0].owner_name.as_deref(), rows[0].color_name.as_deref()), (None, None));
+    let colored = vec![BikeCondition::color(StringFilter::Equal("Red".to_string()))];
+    assert!(dal.bike().find_with_filters(colored).unwrap().is_empty());
+
+    let bikes = dal.bike().find_with_filters_including(vec![], Include::Owner | Include::Color | Include::Trips).unwrap();
+    assert!(bikes[0].owner.is_none());
+    assert!(bikes[0].color.is_none());
+    assert!(bikes[0].trips.is_empty());
+
+    let buckets = dal.bike().count_by(vec![], BikeGroup::Owner).unwrap();
+    assert_eq!(buckets[0].label, None);
+    let buckets = dal.bike().count_by(vec![], BikeGroup::Color).unwrap();
+    assert_eq!(buckets[0].label, None);
+}
+
+fn is_missing_reference<T>(result: &Result<T, Error>) -> bool {
+    matches!(result, Err(Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _)))
+}
+
+#[test]
+fn test_bike_references_must_be_in_tenant() {
+    let fixture = setup_tenants();
+    let club_b = fixture.dal().for_tenant("club-b");
+    let owner = club_b.person().find_all().unwrap().remove(0);
+    let red = club_b.color().find_all().unwrap().remove(0);
+    let other_bike = club_b.bike().find_all().unwrap().remove(0);
+    let dal = fixture.dal().for_tenant("club-a");
+    let bike = dal.bike().find_all().unwrap().remove(0);
+
+    assert!(is_missing_reference(&dal.bike().create(&NewBike::new("Stolen", Some(&owner.id), None))));
+    assert!(is_missing_reference(&dal.bike().create_many(&[NewBike::new("Stolen", None, Some(&red.id))])));
+    let upsert = Upsert::on_primary_key(vec![BikeColumn::OwnerId]);
+    assert!(is_missing_reference(&dal.bike().upsert_many(&[NewBike::new("Stolen", Some(&owner.id), None)], &upsert)));
+    let moved = Bike { owner_id: Some(owner.id.clone()), ..bike.clone() };
+    assert!(matches!(
+        dal.bike().update(&bike.id, &moved),
+        Err(UpdateError::Database(Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _)))
+    ));
+    let patch = BikePatch { color_id: Some(Some(red.id.clone())), ..Default::default() };
+    assert!(matches!(dal.bike().update_partial(&bike.id, &patch), Err(UpdateError::Database(_))));
+    assert!(is_missing_reference(&dal.bike().update_where(vec![], &patch, WriteMode::Execute)));
+    assert!(is_missing_reference(&dal.bike_trip().create(&NewBikeTrip::new("Stolen", Some(&other_bike.id)))));
+    let trip = dal.bike_trip().create(&NewBikeTrip::new("Ride", Some(&bike.id))).unwrap();
+    let trip_patch = BikeTripPatch { bike_id: Some(Some(other_bike.id.clone())), ..Default::default() };
+    assert!(matches!(
+        dal.bike_trip().update_partial(&trip.id, &trip_patch),
+        Err(UpdateError::Database(Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _)))
+    ));
+
+    let unchanged = dal.bike().find_by_id(&bike.id).unwrap();
+    assert_eq!((unchanged.owner_id, unchanged.color_id, unchanged.version), (bike.owner_id.clone(), bike.color_id.clone(), bike.version));
+    assert_eq!(dal.bike().find_all().unwrap().len(), 1);
+    // Own references are still fine
+    let own = dal.person().find_all().unwrap().remove(0);
+    assert_eq!(dal.bike().create(&NewBike::new("Spare", Some(&own.id), bike.color_id.as_deref())).unwrap().name, "Spare");
+}
+
+struct OwnBikes;
+
+impl Policy<BikeCondition> for OwnBikes {
+    fn condition(&self, actor: Option<&str>, action: Action) -> Option<BikeCondition> {
+        match (actor, action) {
+            (Some("admin"), _) => None,
+            // Only admins may delete
+            (_, Action::Delete) => Some(BikeCondition::owner_id(StringFilter::In(vec![]))),
+            (Some(actor), _) => Some(BikeCondition::owner_id(StringFilter::Equal(actor.to_string()))),
+            (None, _) => Some(BikeCondition::owner_id(StringFilter::In(vec![]))),
+        }
+    }
+}
+
+#[test]
+fn test_bike_policy_restricts_reads() {
+    let fixture = setup();
+    let alice = fixture.dal().person().find_all().unwrap().into_iter().find(|p| p.name == "Alice").unwrap();
+
+    let dal = fixture.dal().with_actor(&alice.id).with_bike_policy(Arc::new(OwnBikes));
+    assert_eq!(dal.bike().find_all().unwrap().len(), 2);
+
+    let conditions = vec![BikeCondition::Or(vec![
+        BikeCondition::name(StringFilter::Equal("Road Bike".to_string())),
+        BikeCondition::name(StringFilter::Equal("Mountain Bike".to_string())),
+    ])];
+    let bikes = dal.bike().find_with_filters(conditions.clone()).unwrap();
+    assert_eq!(bikes.len(), 1);
+    assert_eq!(bikes[0].name, "Mountain Bike");
+
+    let patch = BikePatch { name: Some("Renamed".to_string()), ..Default::default() };
+    assert_eq!(dal.bike().update_where(conditions, &patch, WriteMode::DryRun).unwrap(), 1);
+
+    let admin = fixture.dal().with_actor("admin").with_bike_policy(Arc::new(OwnBikes));
+    assert_eq!(admin.bike().find_all().unwrap().len(), 4);
+}
+
+#[test]
+fn test_bike_policy_applies_to_nested_bike_conditions() {
+    let fixture = setup();
+    let alice = fixture.dal().person().find_all().unwrap().into_iter().find(|p| p.name == "Alice").unwrap();
+    let road = fixture.dal().bike().find_with_filters(vec![BikeCondition::name(StringFilter::Equal("Road Bike".to_string()))]).unwrap().remove(0);
+    fixture.dal().bike_trip().create(&NewBikeTrip::new("Commute", Some(&road.id))).unwrap();
+
+    // Alice can't read the Road Bike, so she can't find its owner or trips through it either
+    let dal = fixture.dal().with_actor(&alice.id).with_bike_policy(Arc::new(OwnBikes));
+    let road_bike = || BikeCondition::name(StringFilter::Equal("Road Bike".to_string()));
+    assert!(dal.person().find_with_filters(vec![PersonCondition::bike(vec![road_bike()])]).unwrap().is_empty());
+    let owned = PersonCondition::owned_bike_at(chrono::Utc::now(), vec![road_bike()]);
+    assert!(dal.person().find_with_filters(vec![owned]).unwrap().is_empty());
+    assert!(dal.bike_trip().find_with_filters(vec![BikeTripCondition::bike(road_bike())]).unwrap().is_empty());
+
+    let admin = fixture.dal().with_actor("admin").with_bike_policy(Arc::new(OwnBikes));
+    let owners = admin.person().find_with_filters(vec![PersonCondition::bike(vec![road_bike()])]).unwrap();
+    assert_eq!(owners.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), vec!["Bob"]);
+    assert_eq!(admin.bike_trip().find_with_filters(vec![BikeTripCondition::bike(road_bike())]).unwrap().len(), 1);
+}
+
+#[test]
+fn test_bike_policy_denies_writes_outside_policy() {
+    let fixture = setup();
+    let alice = fixture.dal().person().find_all().unwrap().into_iter().find(|p| p.name == "Alice").unwrap();
+    let bikes = fixture.dal().bike().find_all().unwrap();
+    let own = bikes.iter().find(|b| b.name == "Mountain Bike").unwrap();
+    let other = bikes.iter().find(|b| b.name == "Road Bike").unwrap();
+
+    let dal = fixture.dal().with_actor(&alice.id).with_bike_policy(Arc::new(OwnBikes));
+    let patch = BikePatch { name: Some("Renamed".to_string()), ..Default::default() };
+
+    assert!(matches!(dal.bike().update_partial(&other.id, &patch), Err(UpdateError::Denied)));
+    assert!(matches!(dal.bike().update(&other.id, other), Err(UpdateError::Denied)));
+    assert!(matches!(dal.bike().delete(&own.id), Err(DeleteError::Denied)));
+    assert_eq!(dal.bike().update_partial(&own.id, &patch).unwrap().name, "Renamed");
+
+    // Missing bikes are not found rather than denied
+    assert!(matches!(
+        dal.bike().update_partial("missing", &patch),
+        Err(UpdateError::Database(diesel::result::Error::NotFound))
+    ));
+}
+
+#[test]
+fn test_bike_policy_checks_written_and_dependent_bikes() {
+    let fixture = setup();
+    let persons = fixture.dal().person().find_all().unwrap();
+    let alice = persons.iter().find(|p| p.name == "Alice").unwrap();
+    let bob = persons.iter().find(|p| p.name == "Bob").unwrap();
+    let own = fixture.dal().bike().find_all().unwrap().into_iter().find(|b| b.name == "Mountain Bike").unwrap();
+    let red = own.color_id.clone().unwrap();
+    let dal = fixture.dal().with_actor(&alice.id).with_bike_policy(Arc::new(OwnBikes));
+
+    // Alice can't move her bikes out of her own scope, or create bikes outside of it
+    let patch = BikePatch { owner_id: Some(Some(bob.id.clone())), ..Default::default() };
+    assert!(matches!(dal.bike().update_partial(&own.id, &patch), Err(UpdateError::Denied)));
+    let given_away = Bike { owner_id: Some(bob.id.clone()), ..own.clone() };
+    assert!(matches!(dal.bike().update(&own.id, &given_away), Err(UpdateError::Denied)));
+    let own_bikes = vec![BikeCondition::owner_id(StringFilter::Equal(alice.id.clone()))];
+    let denied = dal.bike().update_where(own_bikes, &patch, WriteMode::Execute).unwrap_err();
+    assert!(matches!(denied, Error::QueryBuilderError(e) if e.is::<PolicyDenied>()));
+    let denied = dal.bike().create(&NewBike::new("Gift", Some(&bob.id), None)).unwrap_err();
+    assert!(matches!(denied, Error::QueryBuilderError(e) if e.is::<PolicyDenied>()));
+    assert_eq!(dal.bike().create(&NewBike::new("Spare", Some(&alice.id), None)).unwrap().name, "Spare");
+
+    // Deleting a person or color writes their bikes, which only admins may delete
+    assert!(matches!(dal.person().delete_with(&bob.id, DeleteStrategy::Cascade), Err(DeleteError::Denied)));
+    assert!(matches!(dal.person().delete_with(&bob.id, DeleteStrategy::SetNull), Err(DeleteError::Denied)));
+    assert!(matches!(dal.color().delete_with(&red, DeleteStrategy::Cascade), Err(DeleteError::Denied)));
+    assert_eq!(fixture.dal().bike().find_all().unwrap().len(), 5);
+    assert_eq!(fixture.dal().person().find_all().unwrap().len(), 2);
+    assert_eq!(fixture.dal().bike().find_by_id(&own.id).unwrap().version, own.version);
+
+    let admin = fixture.dal().with_actor("admin").with_bike_policy(Arc::new(OwnBikes));
+    let summary = admin.person().delete_with(&bob.id, DeleteStrategy::SetNull).unwrap();
+    assert_eq!(summary, DeleteSummary { deleted: 1, dependents: 1 });
+}
+
+#[test]
+fn test_bike_transfer_records_ownership() {
+    let fixture = setup();
+    let dal = fixture.dal();
+    let persons = dal.person().find_all().unwrap();
+    let alice = persons.iter().find(|p| p.name == "Alice").unwrap();
+    let bob = persons.iter().find(|p| p.name == "Bob").unwrap();
+    let bikes = dal.bike().find_all().unwrap();
+    let mountain = bikes.iter().find(|b| b.name == "Mountain Bike").unwrap();
+    let road = bikes.iter().find(|b| b.name == "Road Bike").unwrap();
+
+    let transferred = dal.bike().transfer(&mountain.id, &bob.id).unwrap();
+    assert_eq!(transferred.owner_id.as_deref(), Some(bob.id.as_str()));
+    assert_eq!(transferred.version, mountain.version + 1);
+    // Already Bob's, nothing changes
+    assert_eq!(dal.bike().transfer(&mountain.id, &bob.id).unwrap().version, transferred.version);
+
+    let ownerships = dal.bike().ownerships(&mountain.id).unwrap();
+    let owners: Vec<&str> = ownerships.iter().map(|o| o.owner_id.as_str()).collect();
+    assert_eq!(owners, vec![alice.id.as_str(), bob.id.as_str()]);
+    assert_eq!(ownerships[0].valid_to, Some(ownerships[1].valid_from));
+    assert_eq!(ownerships[1].valid_to, None);
+    assert!(ownerships[0].covers(ownerships[0].valid_from));
+    assert!(!ownerships[0].covers(ownerships[1].valid_from));
+
+    // Any write of the owner is recorded, not only transfers
+    let patch = BikePatch { owner_id: Some(None), ..Default::default() };
+    dal.bike().update_partial(&mountain.id, &patch).unwrap();
+    let ownerships = dal.bike().ownerships(&mountain.id).unwrap();
+    assert_eq!(ownerships.len(), 2);
+    assert!(ownerships[1].valid_to.is_some());
+
+    assert!(matches!(
+        dal.bike().transfer(&road.id, "missing"),
+        Err(UpdateError::Database(diesel::result::Error::NotFound))
+    ));
+    assert!(matches!(
+        dal.for_tenant("other club").bike().transfer(&road.id, &alice.id),
+        Err(UpdateError::Database(diesel::result::Error::NotFound))
+    ));
+    assert!(dal.for_tenant("other club").bike().ownerships(&road.id).unwrap().is_empty());
+    let restricted = fixture.dal().with_actor(&alice.id).with_bike_policy(Arc::new(OwnBikes));
+    assert!(matches!(restricted.bike().transfer(&road.id, &alice.id), Err(UpdateError::Denied)));
+    assert_eq!(dal.bike().ownerships(&road.id).unwrap().len(), 1);
+
+    // Alice may hand her own bike over, even though that moves it out of her scope
+    dal.bike().transfer(&mountain.id, &alice.id).unwrap();
+    let transferred = restricted.bike().transfer(&mountain.id, &bob.id).unwrap();
+    assert_eq!(transferred.owner_id.as_deref(), Some(bob.id.as_str()));
+    assert!(matches!(restricted.bike().transfer(&mountain.id, &alice.id), Err(UpdateError::Denied)));
+}
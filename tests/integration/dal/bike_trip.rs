@@ -1,9 +1,14 @@
 use pedal_pal::models::{
-    bike_trip::NewBikeTrip,
+    bike_trip::{BikeTrip, BikeTripColumn, BikeTripCondition, BikeTripGroup, BikeTripMetric, BikeTripPatch, NewBikeTrip},
+    bike::BikeCondition,
+    common::{Aggregate, GeoFilter, GeoPoint, InvalidGeoFilter, StringFilter, Upsert},
     bike::NewBike,
     person::NewPerson,
     color::NewColor,
 };
+use pedal_pal::dal::{Action, Policy, PolicyDenied, UpdateError};
+use pedal_pal::gpx::{self, GpxError};
+use std::sync::Arc;
 use crate::fixtures::TestFixture;
 
 fn setup() -> (TestFixture, NewBikeTrip) {
@@ -84,4 +89,322 @@ fn test_bike_trip_delete() {
 
     let remaining_trips = dal.bike_trip().find_all().unwrap();
     assert_eq!(remaining_trips.len(), 4);
-}
\ No newline at end of file
+}
+#[test]
+fn test_bike_trip_filter_by_bike() {
+    let fixture = TestFixture::new();
+    let dal = fixture.dal();
+
+    fixture.setup_bike_trips();
+    let road_bike = fixture.create_bike("Road Bike", None, None);
+    dal.bike_trip().create(&NewBikeTrip::new("Road Trip", Some(&road_bike.id))).unwrap();
+
+    let conditions = vec![BikeTripCondition::bike(BikeCondition::name(StringFilter::Equal(
+        "Mountain Bike".to_string(),
+    )))];
+    let trips = dal.bike_trip().find_with_filters(conditions).unwrap();
+    assert_eq!(trips.len(), 5);
+
+    let conditions = vec![
+        BikeTripCondition::name(StringFilter::Like("% Trip".to_string())),
+        BikeTripCondition::bike(BikeCondition::name(StringFilter::Equal("Road Bike".to_string()))),
+    ];
+    let trips = dal.bike_trip().find_with_filters(conditions).unwrap();
+    assert_eq!(trips.len(), 1);
+    assert_eq!(trips[0].name, "Road Trip");
+}
+
+#[test]
+fn test_bike_trip_explain_with_filters() {
+    let (fixture, new_bike_trip) = setup();
+    let dal = fixture.dal();
+    dal.bike_trip().create(&new_bike_trip).unwrap();
+
+    let conditions = vec![
+        BikeTripCondition::name(StringFilter::Like("City%".to_string())),
+        BikeTripCondition::bike(BikeCondition::name(StringFilter::Equal("City Bike".to_string()))),
+    ];
+    let sql = dal.bike_trip().to_sql_string(conditions.clone()).unwrap();
+    assert!(sql.contains("\"bike_trip\".\"name\" LIKE $1"), "{}", sql);
+    assert!(sql.contains("\"City%\"") && sql.contains("\"City Bike\""), "{}", sql);
+
+    let plan = dal.bike_trip().explain_with_filters(conditions.clone(), false).unwrap();
+    assert_eq!(plan.sql, sql);
+    assert!(plan.seq_scans.contains(&"bike_trip".to_string()));
+    assert!(plan.actual_rows.is_none());
+
+    let analyzed = dal.bike_trip().explain_with_filters(conditions, true).unwrap();
+    assert_eq!(analyzed.actual_rows, Some(1));
+
+    let invalid = vec![BikeTripCondition::track(GeoFilter::WithinRadius { center: point(0.0, 0.0), radius_meters: -1.0 })];
+    assert!(dal.bike_trip().to_sql_string(invalid).is_err());
+}
+
+#[test]
+fn test_bike_trip_count_by_owner() {
+    let fixture = TestFixture::new();
+    let dal = fixture.dal();
+
+    let alice = fixture.create_person("Alice");
+    let alice_bike = fixture.create_bike("Alice's Bike", Some(&alice.id), None);
+    let ownerless_bike = fixture.create_bike("Ownerless Bike", None, None);
+    for i in 1..=3 {
+        dal.bike_trip().create(&NewBikeTrip::new(&format!("Alice Trip {}", i), Some(&alice_bike.id))).unwrap();
+    }
+    dal.bike_trip().create(&NewBikeTrip::new("Ownerless Trip", Some(&ownerless_bike.id))).unwrap();
+    dal.bike_trip().create(&NewBikeTrip::new("Walk", None)).unwrap();
+
+    let buckets = dal.bike_trip().count_by(vec![], BikeTripGroup::Owner).unwrap();
+    assert_eq!(buckets.len(), 2);
+    assert_eq!(buckets[0].label.as_deref(), Some("Alice"));
+    assert_eq!(buckets[0].count, 3);
+    assert_eq!(buckets[1].key, None);
+    assert_eq!(buckets[1].count, 2);
+
+    let conditions = vec![BikeTripCondition::name(StringFilter::Like("Alice%".to_string()))];
+    let buckets = dal.bike_trip().count_by(conditions, BikeTripGroup::Bike).unwrap();
+    assert_eq!(buckets.len(), 1);
+    assert_eq!(buckets[0].label.as_deref(), Some("Alice's Bike"));
+    assert_eq!(buckets[0].count, 3);
+}
+
+#[test]
+fn test_bike_trip_aggregate_by() {
+    let fixture = TestFixture::new();
+    let dal = fixture.dal();
+
+    let alice = fixture.create_person("Alice");
+    let road = fixture.create_bike("Road Bike", Some(&alice.id), None);
+    let city = fixture.create_bike("City Bike", Some(&alice.id), None);
+    for (name, bike) in [("Road 1", &road), ("Road 2", &road), ("Road 3", &road), ("City 1", &city)] {
+        dal.bike_trip().create(&NewBikeTrip::new(name, Some(&bike.id))).unwrap();
+    }
+    dal.bike_trip().create(&NewBikeTrip::new("Walk", None)).unwrap();
+    // Road 3 wasn't recorded, so it has no metrics
+    fixture.execute("UPDATE bike_trip SET distance_meters = 10000, duration_seconds = 1800, elevation_gain_meters = 50 WHERE name = 'Road 1'");
+    fixture.execute("UPDATE bike_trip SET distance_meters = 30000, duration_seconds = 3601, elevation_gain_meters = 250 WHERE name = 'Road 2'");
+    fixture.execute("UPDATE bike_trip SET distance_meters = 5000, duration_seconds = 900 WHERE name IN ('City 1', 'Walk')");
+
+    let buckets = dal.bike_trip().aggregate_by(vec![], BikeTripGroup::Bike, BikeTripMetric::Distance, Aggregate::Sum).unwrap();
+    let rows: Vec<_> = buckets.iter().map(|b| (b.label.as_deref(), b.count, b.value)).collect();
+    assert_eq!(rows, vec![(Some("Road Bike"), 3, Some(40000.0)), (Some("City Bike"), 1, Some(5000.0)), (None, 1, Some(5000.0))]);
+
+    let buckets = dal.bike_trip().aggregate_by(vec![], BikeTripGroup::Owner, BikeTripMetric::Duration, Aggregate::Avg).unwrap();
+    assert_eq!((buckets[0].label.as_deref(), buckets[0].count, buckets[0].value), (Some("Alice"), 4, Some(6301.0 / 3.0)));
+    assert_eq!((buckets[1].key.as_deref(), buckets[1].value), (None, Some(900.0)));
+
+    let conditions = vec![BikeTripCondition::name(StringFilter::Like("Road%".to_string()))];
+    let max = dal.bike_trip().aggregate_by(conditions.clone(), BikeTripGroup::Bike, BikeTripMetric::ElevationGain, Aggregate::Max).unwrap();
+    let min = dal.bike_trip().aggregate_by(conditions, BikeTripGroup::Bike, BikeTripMetric::ElevationGain, Aggregate::Min).unwrap();
+    assert_eq!((max.len(), max[0].value, min[0].value), (1, Some(250.0), Some(50.0)));
+
+    let conditions = vec![BikeTripCondition::name(StringFilter::Equal("Road 3".to_string()))];
+    let buckets = dal.bike_trip().aggregate_by(conditions, BikeTripGroup::Bike, BikeTripMetric::Distance, Aggregate::Sum).unwrap();
+    assert_eq!((buckets[0].count, buckets[0].value), (1, None));
+}
+
+#[test]
+fn test_bike_trip_update_partial() {
+    let (fixture, new_bike_trip) = setup();
+    let dal = fixture.dal();
+
+    let trip = dal.bike_trip().create(&new_bike_trip).unwrap();
+    let result = dal.bike_trip().update_partial(&trip.id, &BikeTripPatch {
+        bike_id: Some(None),
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(result.name, "City Tour");
+    assert_eq!(result.bike_id, None);
+}
+
+/// Trips take the policy of their bike, which lets actors access the bikes they own
+struct OwnBikes;
+
+impl Policy<BikeCondition> for OwnBikes {
+    fn condition(&self, actor: Option<&str>, _action: Action) -> Option<BikeCondition> {
+        Some(BikeCondition::owner_id(StringFilter::In(actor.map(str::to_string).into_iter().collect())))
+    }
+}
+
+#[test]
+fn test_bike_trip_policy() {
+    let fixture = TestFixture::new();
+    let dal = fixture.dal();
+    let alice = dal.person().create(&NewPerson::new("Alice")).unwrap();
+    let bob = dal.person().create(&NewPerson::new("Bob")).unwrap();
+    let own_bike = dal.bike().create(&NewBike::new("Alice's Bike", Some(&alice.id), None)).unwrap();
+    let other_bike = dal.bike().create(&NewBike::new("Bob's Bike", Some(&bob.id), None)).unwrap();
+    let own_trip = dal.bike_trip().create(&NewBikeTrip::new("Commute", Some(&own_bike.id))).unwrap();
+    let other_trip = dal.bike_trip().create(&NewBikeTrip::new("Ride", Some(&other_bike.id))).unwrap();
+    let restricted = dal.clone().with_actor(&alice.id).with_bike_policy(Arc::new(OwnBikes));
+    let is_denied = |error: &diesel::result::Error| matches!(error, diesel::result::Error::QueryBuilderError(e) if e.is::<PolicyDenied>());
+
+    // Trips can only be written on bikes the policy allows updating
+    assert!(is_denied(&restricted.bike_trip().create(&NewBikeTrip::new("Joyride", Some(&other_bike.id))).unwrap_err()));
+    assert!(is_denied(&restricted.bike_trip().create_many(&[NewBikeTrip::new("Joyride", Some(&other_bike.id))]).unwrap_err()));
+    assert!(restricted.bike_trip().create(&NewBikeTrip::new("Walk", None)).is_ok());
+    let patch = BikeTripPatch { bike_id: Some(Some(other_bike.id.clone())), ..Default::default() };
+    assert!(matches!(restricted.bike_trip().update_partial(&own_trip.id, &patch), Err(UpdateError::Denied)));
+    let renamed = BikeTrip { name: "Race".to_string(), ..other_trip.clone() };
+    assert!(matches!(restricted.bike_trip().update(&other_trip.id, &renamed), Err(UpdateError::Denied)));
+    assert!(is_denied(&restricted.bike_trip().delete(&other_trip.id).unwrap_err()));
+
+    // Upserts skip conflicting trips on other bikes instead of overwriting them
+    let takeover = NewBikeTrip { id: other_trip.id.clone(), name: "Race".to_string(), bike_id: other_trip.bike_id.clone() };
+    let upserted = restricted.bike_trip().upsert_many(&[takeover], &Upsert::on_primary_key(vec![BikeTripColumn::Name])).unwrap();
+    assert!(upserted.is_empty());
+    assert_eq!(dal.bike_trip().find_by_id(&other_trip.id).unwrap().name, "Ride");
+    assert_eq!(dal.bike_trip().find_by_id(&own_trip.id).unwrap().bike_id.as_deref(), Some(own_bike.id.as_str()));
+
+    let patch = BikeTripPatch { name: Some("Long Commute".to_string()), ..Default::default() };
+    assert_eq!(restricted.bike_trip().update_partial(&own_trip.id, &patch).unwrap().name, "Long Commute");
+}
+
+fn gpx_fixture(file: &str) -> std::fs::File {
+    std::fs::File::open(format!("{}/tests/fixtures/gpx/{}", env!("CARGO_MANIFEST_DIR"), file)).unwrap()
+}
+
+#[test]
+fn test_bike_trip_import_gpx() {
+    let (fixture, new_bike_trip) = setup();
+    let dal = fixture.dal();
+    let bike_id = new_bike_trip.bike_id.unwrap();
+
+    let trip = dal.bike_trip().import_gpx(&bike_id, gpx_fixture("morning_ride.gpx")).unwrap();
+    assert_eq!(trip.name, "Morning Ride");
+    assert_eq!(trip.bike_id.as_deref(), Some(bike_id.as_str()));
+    assert_eq!(trip.started_at.unwrap().to_rfc3339(), "2024-10-05T08:00:00+00:00");
+    assert_eq!(trip.duration_seconds, Some(90));
+    assert_eq!(trip.moving_seconds, Some(90));
+    assert_eq!(trip.elevation_gain_meters, Some(12.0));
+    assert!((trip.distance_meters.unwrap() - 333.585).abs() < 0.01);
+
+    let points = dal.bike_trip().find_points(&trip.id).unwrap();
+    assert_eq!(points.iter().map(|point| point.seq).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+    assert_eq!((points[2].latitude, points[2].elevation), (52.002, Some(33.0)));
+    assert!(dal.bike_trip().for_tenant("other club").find_points(&trip.id).unwrap().is_empty());
+
+    // Distance stops at segment breaks, the duration doesn't
+    let paused = dal.bike_trip().import_gpx(&bike_id, gpx_fixture("paused_ride.gpx")).unwrap();
+    assert_eq!(paused.name, "Lunch & Coffee");
+    assert_eq!(paused.duration_seconds, Some(1800));
+    assert_eq!(paused.moving_seconds, Some(60));
+    assert_eq!(paused.elevation_gain_meters, None);
+    assert!((paused.distance_meters.unwrap() - 222.390).abs() < 0.01);
+    let points = dal.bike_trip().find_points(&paused.id).unwrap();
+    assert_eq!(points.len(), 4);
+    assert_eq!(points[2].recorded_at, None);
+
+    let error = dal.bike_trip().import_gpx(&bike_id, gpx_fixture("invalid_point.gpx")).unwrap_err();
+    match error {
+        diesel::result::Error::DeserializationError(error) => {
+            assert_eq!(error.to_string(), "invalid GPX: `north` is not a valid lat");
+            assert!(error.is::<GpxError>());
+        }
+        other => panic!("expected a deserialization error, got {:?}", other),
+    }
+    assert!(matches!(gpx::parse(r#"<gpx><trk><trkseg/></trk></gpx>"#.as_bytes()), Err(GpxError::Empty)));
+    assert!(matches!(gpx::parse("<gpx><trk></gpx>".as_bytes()), Err(GpxError::Xml(_))));
+    assert_eq!(dal.bike_trip().find_all().unwrap().len(), 2);
+
+    // Tracks can only be imported for live bikes of the tenant
+    let error = dal.for_tenant("other club").bike_trip().import_gpx(&bike_id, gpx_fixture("morning_ride.gpx")).unwrap_err();
+    assert!(matches!(error, diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::ForeignKeyViolation, _)));
+    dal.bike().delete(&bike_id).unwrap();
+    let error = dal.bike_trip().import_gpx(&bike_id, gpx_fixture("morning_ride.gpx")).unwrap_err();
+    assert!(matches!(error, diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::ForeignKeyViolation, _)));
+    assert_eq!(dal.bike_trip().find_all().unwrap().len(), 2);
+}
+
+fn point(latitude: f64, longitude: f64) -> GeoPoint {
+    GeoPoint { latitude, longitude }
+}
+
+#[test]
+fn test_bike_trip_filter_by_geo() {
+    let (fixture, new_bike_trip) = setup();
+    let dal = fixture.dal();
+    let bike_id = new_bike_trip.bike_id.clone().unwrap();
+    // Morning Ride heads north from (52.0, 13.0), Lunch & Coffee heads east from (0.0, 0.0)
+    dal.bike_trip().import_gpx(&bike_id, gpx_fixture("morning_ride.gpx")).unwrap();
+    dal.bike_trip().import_gpx(&bike_id, gpx_fixture("paused_ride.gpx")).unwrap();
+    dal.bike_trip().create(&new_bike_trip).unwrap();
+
+    let names = |condition: BikeTripCondition| {
+        let mut names: Vec<String> = dal.bike_trip().find_with_filters(vec![condition]).unwrap().into_iter().map(|trip| trip.name).collect();
+        names.sort();
+        names
+    };
+
+    let north_of_start = GeoFilter::WithinBBox { south_west: point(52.0015, 12.99), north_east: point(52.01, 13.01) };
+    assert_eq!(names(BikeTripCondition::track(north_of_start.clone())), vec!["Morning Ride"]);
+    assert!(names(BikeTripCondition::start(north_of_start)).is_empty());
+    // A box wrapping around the antimeridian
+    let wrapping = GeoFilter::WithinBBox { south_west: point(-1.0, 170.0), north_east: point(1.0, 0.0005) };
+    assert_eq!(names(BikeTripCondition::start(wrapping)), vec!["Lunch & Coffee"]);
+
+    let near = |latitude, longitude, radius_meters| GeoFilter::WithinRadius { center: point(latitude, longitude), radius_meters };
+    assert_eq!(names(BikeTripCondition::start(near(52.0, 13.0, 2000.0))), vec!["Morning Ride"]);
+    // 2.2 km north of the start
+    assert!(names(BikeTripCondition::start(near(52.02, 13.0, 2000.0))).is_empty());
+    assert_eq!(names(BikeTripCondition::start(near(52.02, 13.0, 2500.0))), vec!["Morning Ride"]);
+    // The last point is 56 m away
+    assert_eq!(names(BikeTripCondition::track(near(0.0, 0.0035, 100.0))), vec!["Lunch & Coffee"]);
+    assert!(names(BikeTripCondition::track(near(0.0, 0.0035, 50.0))).is_empty());
+
+    let around_third_point = vec![point(-0.001, 0.0015), point(0.001, 0.0015), point(0.001, 0.0025), point(-0.001, 0.0025)];
+    assert_eq!(names(BikeTripCondition::track(GeoFilter::WithinPolygon(around_third_point.clone()))), vec!["Lunch & Coffee"]);
+    assert!(names(BikeTripCondition::start(GeoFilter::WithinPolygon(around_third_point))).is_empty());
+    let triangle = vec![point(51.9, 12.9), point(52.1, 13.0), point(51.9, 13.1)];
+    assert_eq!(names(BikeTripCondition::track(GeoFilter::WithinPolygon(triangle))), vec!["Morning Ride"]);
+
+    let either = BikeTripCondition::Or(vec![
+        BikeTripCondition::start(near(0.0, 0.0, 10.0)),
+        BikeTripCondition::name(StringFilter::Equal("City Tour".to_string())),
+    ]);
+    assert_eq!(names(either), vec!["City Tour", "Lunch & Coffee"]);
+
+    let error = dal.bike_trip().find_with_filters(vec![BikeTripCondition::track(near(0.0, 0.0, -1.0))]).unwrap_err();
+    match error {
+        diesel::result::Error::QueryBuilderError(error) => {
+            assert_eq!(error.downcast_ref::<InvalidGeoFilter>().unwrap().0, "-1 is not a valid radius");
+        }
+        other => panic!("expected a query builder error, got {:?}", other),
+    }
+    let line = GeoFilter::WithinPolygon(vec![point(0.0, 0.0), point(1.0, 1.0)]);
+    assert!(dal.bike_trip().find_with_filters(vec![BikeTripCondition::track(line)]).is_err());
+    let across_antimeridian = GeoFilter::WithinPolygon(vec![point(-1.0, 179.0), point(1.0, 179.0), point(0.0, -179.0)]);
+    let error = dal.bike_trip().find_with_filters(vec![BikeTripCondition::track(across_antimeridian)]).unwrap_err();
+    match error {
+        diesel::result::Error::QueryBuilderError(error) => {
+            assert_eq!(error.downcast_ref::<InvalidGeoFilter>().unwrap().0, "polygons crossing the antimeridian are not supported");
+        }
+        other => panic!("expected a query builder error, got {:?}", other),
+    }
+}
+
+#[cfg(feature = "postgis")]
+#[test]
+fn test_bike_trip_filter_by_geo_with_postgis() {
+    let (fixture, new_bike_trip) = setup();
+    fixture.execute("CREATE EXTENSION IF NOT EXISTS postgis");
+    let dal = fixture.dal();
+    let bike_id = new_bike_trip.bike_id.clone().unwrap();
+    dal.bike_trip().import_gpx(&bike_id, gpx_fixture("morning_ride.gpx")).unwrap();
+    dal.bike_trip().import_gpx(&bike_id, gpx_fixture("paused_ride.gpx")).unwrap();
+
+    let names = |condition: BikeTripCondition| -> Vec<String> {
+        dal.bike_trip().find_with_filters(vec![condition]).unwrap().into_iter().map(|trip| trip.name).collect()
+    };
+
+    let triangle = vec![point(51.9, 12.9), point(52.1, 13.0), point(51.9, 13.1)];
+    assert_eq!(names(BikeTripCondition::track(GeoFilter::WithinPolygon(triangle))), vec!["Morning Ride"]);
+    // Lunch & Coffee starts on the western edge, which counts as inside
+    let east_of_start = vec![point(-0.001, 0.0), point(0.001, 0.0), point(0.001, 0.001), point(-0.001, 0.001)];
+    assert_eq!(names(BikeTripCondition::start(GeoFilter::WithinPolygon(east_of_start))), vec!["Lunch & Coffee"]);
+    let elsewhere = vec![point(10.0, 10.0), point(11.0, 10.0), point(11.0, 11.0)];
+    assert!(names(BikeTripCondition::track(GeoFilter::WithinPolygon(elsewhere))).is_empty());
+
+    let near = GeoFilter::WithinRadius { center: point(52.02, 13.0), radius_meters: 2500.0 };
+    assert_eq!(names(BikeTripCondition::start(near)), vec!["Morning Ride"]);
+}
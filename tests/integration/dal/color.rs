@@ -1,4 +1,7 @@
 
+use pedal_pal::dal::UpdateError;
+use pedal_pal::models::color::{ColorColumn, ColorPatch, NewColor};
+use pedal_pal::models::common::{ConflictTarget, DeleteStrategy, DeleteSummary, Upsert};
 use crate::fixtures::TestFixture;
 
 
@@ -54,4 +57,54 @@ fn test_color_delete() {
 
     let remaining_colors = dal.color().find_all().unwrap();
     assert_eq!(remaining_colors.len(), 15);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_color_update_partial() {
+    let fixture = TestFixture::new();
+    let dal = fixture.dal();
+
+    let red = fixture.create_color("Red");
+    let result = dal.color().update_partial(&red.id, &ColorPatch {
+        name: Some("Crimson".to_string()),
+    }).unwrap();
+    assert_eq!(result.name, "Crimson");
+    assert!(matches!(
+        dal.color().update_partial("missing", &ColorPatch { name: Some("Scarlet".to_string()) }),
+        Err(UpdateError::Database(diesel::result::Error::NotFound))
+    ));
+}
+
+#[test]
+fn test_color_create_many_and_upsert_many() {
+    let fixture = TestFixture::new();
+    let dal = fixture.dal();
+
+    let new_colors = vec![NewColor::new("Red"), NewColor::new("Blue")];
+    let created = dal.color().create_many(&new_colors).unwrap();
+    assert_eq!(created.len(), 2);
+
+    let renamed = vec![NewColor { id: new_colors[0].id.clone(), name: "Crimson".to_string() }];
+    let upsert = Upsert {
+        conflict_target: ConflictTarget::Constraint("color_pkey".to_string()),
+        update_columns: vec![ColorColumn::Name],
+    };
+    let upserted = dal.color().upsert_many(&renamed, &upsert).unwrap();
+    assert_eq!(upserted[0].name, "Crimson");
+    assert_eq!(dal.color().find_all().unwrap().len(), 2);
+}
+
+#[test]
+fn test_color_delete_with_set_null() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+    let dal = fixture.dal();
+
+    let blue = dal.color().find_all().unwrap().into_iter().find(|c| c.name == "Blue").unwrap();
+    let summary = dal.color().delete_with(&blue.id, DeleteStrategy::SetNull).unwrap();
+    assert_eq!(summary, DeleteSummary { deleted: 1, dependents: 2 });
+
+    let bikes = dal.bike().find_all().unwrap();
+    assert_eq!(bikes.len(), 3);
+    assert_eq!(bikes.iter().filter(|b| b.color_id.is_none()).count(), 2);
+}
@@ -0,0 +1,95 @@
+use pedal_pal::models::bike::BikeCondition;
+use pedal_pal::models::common::StringFilter;
+use pedal_pal::models::maintenance::{ComponentKind, ServiceReminder};
+use crate::fixtures::TestFixture;
+
+fn gpx_fixture(file: &str) -> std::fs::File {
+    std::fs::File::open(format!("{}/tests/fixtures/gpx/{}", env!("CARGO_MANIFEST_DIR"), file)).unwrap()
+}
+
+fn bike_id(fixture: &TestFixture, bike_name: &str) -> String {
+    let bikes = fixture.dal().bike().find_with_filters(vec![BikeCondition::name(StringFilter::Equal(bike_name.to_string()))]).unwrap();
+    bikes[0].id.clone()
+}
+
+fn kinds(reminders: &[ServiceReminder]) -> Vec<(&str, &str)> {
+    reminders.iter().map(|reminder| (reminder.component.bike_id.as_str(), reminder.component.kind.as_str())).collect()
+}
+
+#[test]
+fn test_maintenance_service_reminders() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+    let dal = fixture.dal();
+    let mountain = bike_id(&fixture, "Mountain Bike");
+    let city = bike_id(&fixture, "City Bike");
+
+    let chain = dal.maintenance().install(&mountain, ComponentKind::Chain, Some("KMC X11")).unwrap();
+    assert_eq!(chain.installed_at_meters, 0.0);
+    dal.bike_trip().import_gpx(&mountain, gpx_fixture("morning_ride.gpx")).unwrap();
+    // Went on after the morning ride, so it hasn't worn yet
+    let tyre = dal.maintenance().install(&mountain, ComponentKind::Tyre, None).unwrap();
+    assert!((tyre.installed_at_meters - 333.585).abs() < 0.01);
+    dal.maintenance().install(&city, ComponentKind::BrakePads, None).unwrap();
+    dal.bike_trip().import_gpx(&city, gpx_fixture("paused_ride.gpx")).unwrap();
+
+    let reminders = dal.maintenance().due_for_service(200.0).unwrap();
+    assert_eq!(kinds(&reminders), vec![(mountain.as_str(), "chain"), (city.as_str(), "brake_pads")]);
+    assert!((reminders[0].wear_meters - 333.585).abs() < 0.01);
+    assert!((reminders[1].wear_meters - 222.390).abs() < 0.01);
+    assert_eq!(kinds(&dal.maintenance().due_for_service(300.0).unwrap()), vec![(mountain.as_str(), "chain")]);
+
+    let worn = dal.bike().find_with_filters(vec![BikeCondition::needs_service(300.0)]).unwrap();
+    assert_eq!(worn.iter().map(|bike| bike.name.as_str()).collect::<Vec<_>>(), vec!["Mountain Bike"]);
+    let worn = dal.bike().find_with_filters(vec![BikeCondition::needs_service(200.0), BikeCondition::color(StringFilter::Equal("Blue".to_string()))]).unwrap();
+    assert_eq!(worn.iter().map(|bike| bike.name.as_str()).collect::<Vec<_>>(), vec!["City Bike"]);
+
+    // Replacing the chain takes the worn one off
+    let new_chain = dal.maintenance().install(&mountain, ComponentKind::Chain, None).unwrap();
+    assert!(dal.maintenance().due_for_service(300.0).unwrap().is_empty());
+    assert!(dal.bike().find_with_filters(vec![BikeCondition::needs_service(300.0)]).unwrap().is_empty());
+    let installed = dal.maintenance().find_components(&mountain).unwrap();
+    assert_eq!(installed.iter().map(|component| component.id.as_str()).collect::<Vec<_>>(), vec![new_chain.id.as_str(), tyre.id.as_str()]);
+
+    let events = dal.maintenance().find_events(&mountain).unwrap();
+    let log: Vec<(&str, Option<&str>)> = events.iter().map(|event| (event.kind.as_str(), event.component_id.as_deref())).collect();
+    assert_eq!(
+        log,
+        vec![
+            ("install", Some(chain.id.as_str())),
+            ("install", Some(tyre.id.as_str())),
+            ("removal", Some(chain.id.as_str())),
+            ("install", Some(new_chain.id.as_str())),
+        ]
+    );
+    assert_eq!(events[0].note.as_deref(), Some("KMC X11"));
+    assert!((events[3].odometer_meters - 333.585).abs() < 0.01);
+}
+
+#[test]
+fn test_maintenance_scoping() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+    let dal = fixture.dal();
+    let mountain = bike_id(&fixture, "Mountain Bike");
+    let city = bike_id(&fixture, "City Bike");
+    let pads = dal.maintenance().install(&city, ComponentKind::BrakePads, None).unwrap();
+    dal.bike_trip().import_gpx(&city, gpx_fixture("paused_ride.gpx")).unwrap();
+
+    let service = dal.maintenance().log_service(&city, Some(&pads.id), Some("Bled the brakes")).unwrap();
+    assert_eq!((service.kind.as_str(), service.odometer_meters > 222.0), ("service", true));
+    // The pads aren't on the Mountain Bike
+    let error = dal.maintenance().log_service(&mountain, Some(&pads.id), None).unwrap_err();
+    assert!(matches!(error, diesel::result::Error::NotFound));
+
+    let other = dal.for_tenant("other club");
+    assert!(other.maintenance().due_for_service(0.0).unwrap().is_empty());
+    assert!(other.maintenance().find_events(&city).unwrap().is_empty());
+    assert!(matches!(other.maintenance().install(&city, ComponentKind::Chain, None), Err(diesel::result::Error::NotFound)));
+
+    // Deleted trips no longer wear the components
+    assert_eq!(dal.maintenance().due_for_service(0.0).unwrap().len(), 1);
+    let trips = dal.bike_trip().find_all().unwrap();
+    dal.bike_trip().delete(&trips[0].id).unwrap();
+    assert!(dal.maintenance().due_for_service(0.0).unwrap().is_empty());
+}
@@ -1,4 +1,6 @@
 mod person;
 mod bike;
 mod color;
-mod bike_trip;
\ No newline at end of file
+mod bike_trip;mod saved_filter;
+mod stats;
+mod maintenance;
@@ -0,0 +1,351 @@
+use std::sync::Arc;
+use pedal_pal::dal::{Action, DeleteError, Policy, PolicyDenied, UpdateError};
+use pedal_pal::models::bike::{BikeCondition, NewBike};
+use pedal_pal::models::bike_trip::NewBikeTrip;
+use pedal_pal::models::common::{DeleteStrategy, DeleteSummary, FieldAccess, FieldDenied, StringFilter, Upsert};
+use pedal_pal::models::person::{NewPerson, Person, PersonColumn, PersonCondition, PersonPatch};
+use crate::fixtures::TestFixture;
+
+#[test]
+fn test_person_to_sql_string() {
+    let fixture = TestFixture::new();
+    let dal = fixture.dal();
+
+    let conditions = vec![PersonCondition::bike(vec![BikeCondition::color(
+        StringFilter::Equal("Red".to_string()),
+    )])];
+    let sql = dal.person().to_sql_string(conditions).unwrap();
+    assert!(sql.contains("\"person\".\"id\" = ANY(SELECT \"bike\".\"owner_id\""));
+    assert!(sql.contains("\"Red\""));
+
+    // Restricted fields fail like they do in find_with_filters
+    let conditions = vec![PersonCondition::email(StringFilter::Equal("alice@example.com".to_string()))];
+    match dal.person().to_sql_string(conditions) {
+        Err(diesel::result::Error::QueryBuilderError(error)) => assert!(error.is::<FieldDenied>()),
+        other => panic!("expected a FieldDenied, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_person_explain_with_filters() {
+    let fixture = TestFixture::new();
+    let dal = fixture.dal();
+
+    fixture.setup_bikes();
+
+    let conditions = vec![PersonCondition::bike(vec![BikeCondition::color(
+        StringFilter::Equal("Blue".to_string()),
+    )])];
+    let plan = dal.person().explain_with_filters(conditions, true).unwrap();
+    assert_eq!(plan.actual_rows, Some(2));
+    assert!(plan.seq_scans.contains(&"bike".to_string()));
+}
+
+#[test]
+fn test_person_update_partial() {
+    let fixture = TestFixture::new();
+    let dal = fixture.dal();
+
+    let alice = fixture.create_person("Alice");
+    let result = dal.person().update_partial(&alice.id, &PersonPatch {
+        name: Some("Alicia".to_string()),
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(result.name, "Alicia");
+    assert_eq!(result.version, alice.version + 1);
+}
+
+#[test]
+fn test_person_update_version_conflict() {
+    let fixture = TestFixture::new();
+    let dal = fixture.dal();
+
+    let alice = fixture.create_person("Alice");
+    assert_eq!(alice.version, 1);
+
+    let mut first_edit = alice.clone();
+    first_edit.name = "Alicia".to_string();
+    let updated = dal.person().update(&alice.id, &first_edit).unwrap();
+    assert_eq!(updated.version, 2);
+
+    // A second edit based on the stale version is rejected with the current row
+    let mut stale_edit = alice.clone();
+    stale_edit.name = "Ali".to_string();
+    match dal.person().update(&alice.id, &stale_edit) {
+        Err(UpdateError::Conflict(current)) => {
+            assert_eq!(current.name, "Alicia");
+            assert_eq!(current.version, 2);
+        }
+        other => panic!("expected a conflict, got {:?}", other),
+    }
+
+    // Missing rows are not conflicts
+    assert!(matches!(
+        dal.person().update("missing", &stale_edit),
+        Err(UpdateError::Database(diesel::result::Error::NotFound))
+    ));
+}
+
+#[test]
+fn test_person_soft_delete_and_restore() {
+    let fixture = TestFixture::new();
+    let dal = fixture.dal();
+
+    fixture.setup_bikes();
+    let alice = dal
+        .person()
+        .find_with_filters(vec![PersonCondition::name(StringFilter::Equal("Alice".to_string()))])
+        .unwrap()
+        .remove(0);
+
+    // Alice owns bikes, so a hard delete would violate the foreign key
+    assert_eq!(dal.person().delete(&alice.id).unwrap(), 1);
+    assert_eq!(dal.person().delete(&alice.id).unwrap(), 0);
+
+    assert!(dal.person().find_by_id(&alice.id).is_err());
+    assert_eq!(dal.person().find_all().unwrap().len(), 1);
+    let conditions = vec![PersonCondition::name(StringFilter::Equal("Alice".to_string()))];
+    assert!(dal.person().find_with_filters(conditions.clone()).unwrap().is_empty());
+
+    let deleted = dal.person().only_deleted().find_all().unwrap();
+    assert_eq!(deleted.len(), 1);
+    assert!(deleted[0].deleted_at.is_some());
+    assert_eq!(dal.person().include_deleted().find_all().unwrap().len(), 2);
+    assert_eq!(dal.person().include_deleted().find_with_filters(conditions).unwrap().len(), 1);
+    // Deleted persons can't be updated until they're restored
+    let renamed = PersonPatch { name: Some("Alicia".to_string()), ..Default::default() };
+    assert!(matches!(dal.person().update_partial(&alice.id, &renamed), Err(UpdateError::Database(diesel::result::Error::NotFound))));
+    assert!(matches!(dal.person().update(&alice.id, &deleted[0]), Err(UpdateError::Database(diesel::result::Error::NotFound))));
+
+    let restored = dal.person().restore(&alice.id).unwrap();
+    assert!(restored.deleted_at.is_none());
+    assert_eq!(dal.person().find_by_id(&alice.id).unwrap().name, "Alice");
+    assert!(dal.person().restore(&alice.id).is_err());
+}
+
+#[test]
+fn test_person_filter_ignores_deleted_bikes() {
+    let fixture = TestFixture::new();
+    let dal = fixture.dal();
+
+    fixture.setup_bikes();
+    let road_bike = dal.bike().find_with_filters(vec![BikeCondition::name(StringFilter::Equal("Road Bike".to_string()))]).unwrap().remove(0);
+    dal.bike().delete(&road_bike.id).unwrap();
+
+    // Bob's only bike is deleted, so only Alice owns a blue bike
+    let conditions = vec![PersonCondition::bike(vec![BikeCondition::color(
+        StringFilter::Equal("Blue".to_string()),
+    )])];
+    let persons = dal.person().find_with_filters(conditions).unwrap();
+    assert_eq!(persons.len(), 1);
+    assert_eq!(persons[0].name, "Alice");
+}
+
+#[test]
+fn test_person_delete_with_restrict() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+    let dal = fixture.dal();
+
+    let alice = dal.person().find_with_filters(vec![PersonCondition::name(StringFilter::Equal("Alice".to_string()))]).unwrap().remove(0);
+    let result = dal.person().delete_with(&alice.id, DeleteStrategy::Restrict);
+    assert!(matches!(result, Err(DeleteError::Restricted(2))));
+    assert!(dal.person().find_by_id(&alice.id).is_ok());
+
+    // Once the bikes are gone the delete goes through
+    for bike in dal.bike().find_all().unwrap() {
+        if bike.owner_id.as_deref() == Some(alice.id.as_str()) {
+            dal.bike().delete(&bike.id).unwrap();
+        }
+    }
+    let summary = dal.person().delete_with(&alice.id, DeleteStrategy::Restrict).unwrap();
+    assert_eq!(summary, DeleteSummary { deleted: 1, dependents: 0 });
+}
+
+#[test]
+fn test_person_delete_with_cascade_and_set_null() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+    let dal = fixture.dal();
+
+    let people = dal.person().find_all().unwrap();
+    let alice = people.iter().find(|p| p.name == "Alice").unwrap();
+    let bob = people.iter().find(|p| p.name == "Bob").unwrap();
+    for bike in dal.bike().find_all().unwrap() {
+        dal.bike_trip().create(&NewBikeTrip::new(&format!("{} Trip", bike.name), Some(&bike.id))).unwrap();
+    }
+
+    // Alice's two bikes and their trips go with her
+    let summary = dal.person().delete_with(&alice.id, DeleteStrategy::Cascade).unwrap();
+    assert_eq!(summary, DeleteSummary { deleted: 1, dependents: 4 });
+    assert_eq!(dal.bike().find_all().unwrap().len(), 1);
+    let trips = dal.bike_trip().find_all().unwrap();
+    assert_eq!(trips.iter().map(|trip| trip.name.as_str()).collect::<Vec<_>>(), vec!["Road Bike Trip"]);
+
+    let summary = dal.person().delete_with(&bob.id, DeleteStrategy::SetNull).unwrap();
+    assert_eq!(summary, DeleteSummary { deleted: 1, dependents: 1 });
+    let remaining = dal.bike().find_all().unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert!(remaining[0].owner_id.is_none());
+
+    // Deleting again touches nothing
+    let summary = dal.person().delete_with(&bob.id, DeleteStrategy::Cascade).unwrap();
+    assert_eq!(summary, DeleteSummary::default());
+}
+
+#[test]
+fn test_person_tenant_nested_filters_stay_in_tenant() {
+    let fixture = TestFixture::new();
+    for tenant in ["club-a", "club-b"] {
+        let dal = fixture.dal().for_tenant(tenant);
+        let owner = dal.person().create(&NewPerson::new(&format!("{} owner", tenant))).unwrap();
+        dal.bike().create(&NewBike::new("Shared Name", Some(&owner.id), None)).unwrap();
+    }
+
+    let dal = fixture.dal().for_tenant("club-a");
+    let people = dal.person().find_with_filters(vec![PersonCondition::Or(vec![
+        PersonCondition::name(StringFilter::Equal("club-b owner".to_string())),
+        PersonCondition::bike(vec![BikeCondition::name(StringFilter::Equal("Shared Name".to_string()))]),
+    ])]).unwrap();
+    assert_eq!(people.len(), 1);
+    assert_eq!(people[0].name, "club-a owner");
+
+    // Batch inserts are stamped with the tenant too
+    let created = dal.person().create_many(&[NewPerson::new("Carol"), NewPerson::new("Dave")]).unwrap();
+    assert!(created.iter().all(|p| p.tenant_id.as_deref() == Some("club-a")));
+    assert!(fixture.dal().for_tenant("club-b").person().find_by_id(&created[0].id).is_err());
+}
+
+struct OnlySelf;
+
+impl Policy<PersonCondition> for OnlySelf {
+    fn condition(&self, actor: Option<&str>, _action: Action) -> Option<PersonCondition> {
+        Some(PersonCondition::id(StringFilter::In(actor.map(str::to_string).into_iter().collect())))
+    }
+}
+
+#[test]
+fn test_person_policy() {
+    let fixture = TestFixture::new();
+    fixture.setup_people();
+    let people = fixture.dal().person().find_all().unwrap();
+    let alice = people.iter().find(|p| p.name == "Alice").unwrap();
+    let bob = people.iter().find(|p| p.name == "Bob").unwrap();
+
+    let dal = fixture.dal().with_actor(&alice.id).with_person_policy(Arc::new(OnlySelf));
+    let visible = dal.person().find_with_filters(vec![PersonCondition::Or(vec![
+        PersonCondition::name(StringFilter::Equal("Bob".to_string())),
+        PersonCondition::name(StringFilter::Equal("Alice".to_string())),
+    ])]).unwrap();
+    assert_eq!(visible.len(), 1);
+    assert_eq!(visible[0].id, alice.id);
+    assert!(dal.person().find_by_id(&bob.id).is_err());
+
+    let patch = PersonPatch { name: Some("Robert".to_string()), ..Default::default() };
+    assert!(matches!(dal.person().update_partial(&bob.id, &patch), Err(UpdateError::Denied)));
+    assert!(matches!(dal.person().delete_with(&bob.id, DeleteStrategy::Cascade), Err(DeleteError::Denied)));
+    assert_eq!(dal.person().delete(&alice.id).unwrap(), 1);
+
+    // Without an actor nothing is visible
+    let anonymous = fixture.dal().with_person_policy(Arc::new(OnlySelf));
+    assert!(anonymous.person().find_all().unwrap().is_empty());
+}
+
+/// Actors are identified by their name, so renaming moves a person out of scope
+struct OwnName;
+
+impl Policy<PersonCondition> for OwnName {
+    fn condition(&self, actor: Option<&str>, _action: Action) -> Option<PersonCondition> {
+        Some(PersonCondition::name(StringFilter::In(actor.map(str::to_string).into_iter().collect())))
+    }
+}
+
+#[test]
+fn test_person_policy_checks_written_persons() {
+    let fixture = TestFixture::new();
+    fixture.setup_people();
+    let alice = fixture.dal().person().find_all().unwrap().into_iter().find(|p| p.name == "Alice").unwrap();
+    let dal = fixture.dal().with_actor("Alice").with_person_policy(Arc::new(OwnName));
+    let is_denied = |error: &diesel::result::Error| matches!(error, diesel::result::Error::QueryBuilderError(e) if e.is::<PolicyDenied>());
+
+    // Alice can't rename herself out of her own scope
+    let patch = PersonPatch { name: Some("Alicia".to_string()), ..Default::default() };
+    assert!(matches!(dal.person().update_partial(&alice.id, &patch), Err(UpdateError::Denied)));
+    let renamed = Person { name: "Alicia".to_string(), ..alice.clone() };
+    assert!(matches!(dal.person().update(&alice.id, &renamed), Err(UpdateError::Denied)));
+    let patch = PersonPatch { email: Some(Some("alice@example.com".to_string())), ..Default::default() };
+    assert_eq!(dal.person().update_partial(&alice.id, &patch).unwrap().version, alice.version + 1);
+
+    // Nor create or upsert persons outside of it, and nothing of a denied batch is written
+    assert!(is_denied(&dal.person().create(&NewPerson::new("Mallory")).unwrap_err()));
+    assert!(is_denied(&dal.person().create_many(&[NewPerson::new("Alice"), NewPerson::new("Mallory")]).unwrap_err()));
+    let takeover = NewPerson { id: alice.id.clone(), name: "Mallory".to_string(), email: None };
+    let upsert = Upsert::on_primary_key(vec![PersonColumn::Name]);
+    assert!(is_denied(&dal.person().upsert_many(&[takeover], &upsert).unwrap_err()));
+    let persons = fixture.dal().person().find_all().unwrap();
+    assert_eq!(persons.iter().filter(|p| p.name == "Mallory").count(), 0);
+    assert_eq!(persons.iter().find(|p| p.id == alice.id).unwrap().name, "Alice");
+
+    assert_eq!(dal.person().create(&NewPerson::new("Alice")).unwrap().name, "Alice");
+}
+
+#[test]
+fn test_person_restricted_field_filters() {
+    let fixture = TestFixture::new();
+    let dal = fixture.dal();
+    let mut alice = NewPerson::new("Alice");
+    alice.email = Some("alice@example.com".to_string());
+    dal.person().create(&alice).unwrap();
+
+    // Hiding the restricted field in an Or doesn't get it past validation
+    let conditions = vec![PersonCondition::Or(vec![
+        PersonCondition::name(StringFilter::Equal("Bob".to_string())),
+        PersonCondition::email(StringFilter::Like("alice@%".to_string())),
+    ])];
+    match dal.person().find_with_filters(conditions.clone()) {
+        Err(diesel::result::Error::QueryBuilderError(error)) => {
+            let denied = error.downcast_ref::<FieldDenied>().unwrap();
+            assert_eq!(denied.field, "PersonCondition::email");
+        }
+        other => panic!("expected the filter to be denied, got {:?}", other),
+    }
+
+    let privileged = fixture.dal().with_field_access(FieldAccess::Restricted);
+    let found = privileged.person().find_with_filters(conditions).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].email.as_deref(), Some("alice@example.com"));
+}
+
+#[test]
+fn test_person_owned_bike_at() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+    let dal = fixture.dal();
+    let bob = dal.person().find_with_filters(vec![PersonCondition::name(StringFilter::Equal("Bob".to_string()))]).unwrap();
+    let mountain = dal.bike().find_with_filters(vec![BikeCondition::name(StringFilter::Equal("Mountain Bike".to_string()))]).unwrap();
+    dal.bike().transfer(&mountain[0].id, &bob[0].id).unwrap();
+    let ownerships = dal.bike().ownerships(&mountain[0].id).unwrap();
+    let (bought, sold) = (ownerships[0].valid_from, ownerships[1].valid_from);
+
+    let owners_at = |at, conditions: Vec<BikeCondition>| {
+        let persons = dal.person().find_with_filters(vec![PersonCondition::owned_bike_at(at, conditions)]).unwrap();
+        let mut names: Vec<String> = persons.into_iter().map(|p| p.name).collect();
+        names.sort();
+        names
+    };
+    let mountain_bike = || vec![BikeCondition::name(StringFilter::Equal("Mountain Bike".to_string()))];
+    assert_eq!(owners_at(bought, mountain_bike()), vec!["Alice"]);
+    assert_eq!(owners_at(sold, mountain_bike()), vec!["Bob"]);
+    assert!(owners_at(bought - chrono::Duration::seconds(1), mountain_bike()).is_empty());
+    // Alice still has the City Bike, Bob the Road Bike
+    assert_eq!(owners_at(sold, vec![]), vec!["Alice", "Bob"]);
+    assert_eq!(owners_at(sold, vec![BikeCondition::color(StringFilter::Equal("Red".to_string()))]), vec!["Bob"]);
+
+    // Deleting the bike doesn't rewrite who owned it
+    dal.bike().delete(&mountain[0].id).unwrap();
+    assert_eq!(owners_at(bought, mountain_bike()), vec!["Alice"]);
+
+    let other = dal.for_tenant("other club");
+    let condition = PersonCondition::owned_bike_at(sold, vec![]);
+    assert!(other.person().find_with_filters(vec![condition]).unwrap().is_empty());
+}
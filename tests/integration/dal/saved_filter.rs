@@ -0,0 +1,137 @@
+use serde_json::json;
+use pedal_pal::dal::PolicyDenied;
+use pedal_pal::models::bike::BikeCondition;
+use pedal_pal::models::bike_trip::BikeTripCondition;
+use pedal_pal::models::common::{GeoFilter, GeoPoint, StringFilter};
+use pedal_pal::models::person::PersonCondition;
+use pedal_pal::models::saved_filter::{NewSavedFilter, SavedFilterError, CONDITION_SCHEMA_VERSION};
+use crate::fixtures::TestFixture;
+
+fn red_bikes() -> Vec<BikeCondition> {
+    vec![BikeCondition::Or(vec![
+        BikeCondition::color(StringFilter::Equal("Red".to_string())),
+        BikeCondition::name(StringFilter::Like("Road%".to_string())),
+    ])]
+}
+
+fn expect_saved_filter_error(result: diesel::QueryResult<impl std::fmt::Debug>) -> SavedFilterError {
+    match result {
+        Err(diesel::result::Error::DeserializationError(error)) => *error.downcast::<SavedFilterError>().unwrap(),
+        other => panic!("expected a saved filter error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_saved_filter_round_trip() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+    let dal = fixture.dal();
+    let alice = dal.person().find_with_filters(vec![PersonCondition::name(StringFilter::Equal("Alice".to_string()))]).unwrap().remove(0);
+
+    let saved = dal.saved_filter().create(&NewSavedFilter::new("Red or road", Some(&alice.id), &red_bikes())).unwrap();
+    assert_eq!(saved.entity, "bike");
+    assert_eq!(saved.schema_version, CONDITION_SCHEMA_VERSION);
+    assert_eq!(dal.saved_filter().find_by_owner(&alice.id).unwrap().len(), 1);
+
+    let mut names: Vec<_> = dal.bike().run_saved_filter(&saved.id).unwrap().into_iter().map(|b| b.name).collect();
+    names.sort();
+    assert_eq!(names, vec!["Mountain Bike", "Road Bike"]);
+
+    // Bike conditions can't be run as a person filter
+    match expect_saved_filter_error(dal.person().run_saved_filter(&saved.id)) {
+        SavedFilterError::WrongEntity { expected, found } => {
+            assert_eq!(expected, "person");
+            assert_eq!(found, "bike");
+        }
+        other => panic!("expected a wrong entity error, got {:?}", other),
+    }
+
+    assert_eq!(dal.saved_filter().delete(&saved.id).unwrap(), 1);
+    assert!(matches!(dal.bike().run_saved_filter(&saved.id), Err(diesel::result::Error::NotFound)));
+}
+
+#[test]
+fn test_saved_filter_schema_versions() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+    let dal = fixture.dal();
+
+    let mut newer = NewSavedFilter::new("From the future", None, &red_bikes());
+    newer.schema_version = CONDITION_SCHEMA_VERSION + 1;
+    let newer = dal.saved_filter().create(&newer).unwrap();
+    assert!(matches!(
+        expect_saved_filter_error(dal.bike().run_saved_filter(&newer.id)),
+        SavedFilterError::UnsupportedVersion(_)
+    ));
+
+    // Older filters still run, and migrating brings them to the current version
+    let mut older = NewSavedFilter::new("From the past", None, &red_bikes());
+    older.schema_version = CONDITION_SCHEMA_VERSION - 1;
+    let older = dal.saved_filter().create(&older).unwrap();
+    assert_eq!(dal.bike().run_saved_filter(&older.id).unwrap().len(), 2);
+
+    // Version 1 called the polygon filter Intersects, which still loads through its alias
+    let point = |latitude, longitude| GeoPoint { latitude, longitude };
+    let triangle = vec![point(0.0, 0.0), point(1.0, 0.0), point(0.0, 1.0)];
+    let mut renamed = NewSavedFilter::new("Triangle", None, &[BikeTripCondition::track(GeoFilter::WithinPolygon(triangle))]);
+    renamed.conditions = serde_json::from_str(&renamed.conditions.to_string().replace("WithinPolygon", "Intersects")).unwrap();
+    renamed.schema_version = 1;
+    let renamed = dal.saved_filter().create(&renamed).unwrap();
+    assert!(dal.bike_trip().run_saved_filter(&renamed.id).unwrap().is_empty());
+
+    assert_eq!(dal.saved_filter().migrate().unwrap(), 2);
+    assert_eq!(dal.saved_filter().find_by_id(&older.id).unwrap().schema_version, CONDITION_SCHEMA_VERSION);
+    let migrated = dal.saved_filter().find_by_id(&renamed.id).unwrap();
+    assert_eq!(migrated.schema_version, CONDITION_SCHEMA_VERSION);
+    assert_eq!(migrated.conditions, json!([{ "track": { "WithinPolygon": [
+        { "latitude": 0.0, "longitude": 0.0 },
+        { "latitude": 1.0, "longitude": 0.0 },
+        { "latitude": 0.0, "longitude": 1.0 },
+    ] } }]));
+    assert_eq!(dal.saved_filter().migrate().unwrap(), 0);
+}
+
+#[test]
+fn test_saved_filter_owner_scope() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+    let persons = fixture.dal().person().find_all().unwrap();
+    let alice = persons.iter().find(|p| p.name == "Alice").unwrap();
+    let bob = persons.iter().find(|p| p.name == "Bob").unwrap();
+    let shared = fixture.dal().saved_filter().create(&NewSavedFilter::new("Shared", None, &red_bikes())).unwrap();
+    let bobs = fixture.dal().saved_filter().create(&NewSavedFilter::new("Bob's", Some(&bob.id), &red_bikes())).unwrap();
+    let dal = fixture.dal().with_actor(&alice.id);
+
+    // Alice saves filters only as herself
+    let alices = dal.saved_filter().create(&NewSavedFilter::new("Alice's", Some(&alice.id), &red_bikes())).unwrap();
+    for owner in [Some(bob.id.as_str()), None] {
+        match dal.saved_filter().create(&NewSavedFilter::new("Forged", owner, &red_bikes())) {
+            Err(diesel::result::Error::QueryBuilderError(error)) => assert!(error.is::<PolicyDenied>()),
+            other => panic!("expected a PolicyDenied, got {:?}", other),
+        }
+    }
+
+    // She reads her own and the shared filters, but neither reads nor runs Bob's
+    assert!(dal.saved_filter().find_by_id(&alices.id).is_ok());
+    assert_eq!(dal.bike().run_saved_filter(&shared.id).unwrap().len(), 2);
+    assert!(matches!(dal.saved_filter().find_by_id(&bobs.id), Err(diesel::result::Error::NotFound)));
+    assert!(matches!(dal.bike().run_saved_filter(&bobs.id), Err(diesel::result::Error::NotFound)));
+    assert!(dal.saved_filter().find_by_owner(&bob.id).unwrap().is_empty());
+
+    // And only deletes her own
+    assert_eq!(dal.saved_filter().delete(&bobs.id).unwrap(), 0);
+    assert_eq!(dal.saved_filter().delete(&shared.id).unwrap(), 0);
+    assert_eq!(dal.saved_filter().delete(&alices.id).unwrap(), 1);
+    assert_eq!(fixture.dal().saved_filter().find_by_owner(&bob.id).unwrap().len(), 1);
+}
+
+#[test]
+fn test_saved_filter_tenant_scope() {
+    let fixture = TestFixture::new();
+    let saved = fixture.dal().for_tenant("club-a").saved_filter().create(&NewSavedFilter::new("Red", None, &red_bikes())).unwrap();
+    assert_eq!(saved.tenant_id.as_deref(), Some("club-a"));
+
+    let other = fixture.dal().for_tenant("club-b");
+    assert!(other.saved_filter().find_by_id(&saved.id).is_err());
+    assert!(other.bike().run_saved_filter(&saved.id).is_err());
+}
@@ -0,0 +1,119 @@
+use chrono::{TimeZone, Utc};
+use pedal_pal::models::bike::BikeCondition;
+use pedal_pal::models::common::StringFilter;
+use pedal_pal::models::person::PersonCondition;
+use pedal_pal::models::stats::{LeaderboardRow, RankBy, TimeWindow};
+use crate::fixtures::TestFixture;
+
+fn gpx_fixture(file: &str) -> std::fs::File {
+    std::fs::File::open(format!("{}/tests/fixtures/gpx/{}", env!("CARGO_MANIFEST_DIR"), file)).unwrap()
+}
+
+/// Alice rides the morning ride on her Mountain Bike and the paused ride on her
+/// City Bike, Bob rides the paused ride on his Road Bike
+fn setup() -> TestFixture {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+    let dal = fixture.dal();
+    for (bike, track) in [("Mountain Bike", "morning_ride.gpx"), ("City Bike", "paused_ride.gpx"), ("Road Bike", "paused_ride.gpx")] {
+        let bikes = dal.bike().find_with_filters(vec![BikeCondition::name(StringFilter::Equal(bike.to_string()))]).unwrap();
+        dal.bike_trip().import_gpx(&bikes[0].id, gpx_fixture(track)).unwrap();
+    }
+    // Trips without a track are outside every window
+    fixture.setup_bike_trips();
+    // The bikes had their owners before the rides
+    fixture.execute("UPDATE ownership SET valid_from = '2024-01-01T00:00:00Z'");
+    fixture
+}
+
+fn october() -> TimeWindow {
+    TimeWindow::days(Utc.with_ymd_and_hms(2024, 10, 1, 0, 0, 0).unwrap(), 31)
+}
+
+fn ranking(rows: &[LeaderboardRow]) -> Vec<(i64, &str)> {
+    rows.iter().map(|row| (row.rank, row.name.as_str())).collect()
+}
+
+#[test]
+fn test_person_leaderboard() {
+    let fixture = setup();
+    let dal = fixture.dal();
+
+    let rows = dal.stats().person_leaderboard(october(), vec![], RankBy::Distance).unwrap();
+    assert_eq!(ranking(&rows), vec![(1, "Alice"), (2, "Bob")]);
+    assert_eq!((rows[0].trip_count, rows[0].moving_seconds), (2, 150));
+    assert!((rows[0].distance_meters - 555.975).abs() < 0.01);
+    assert!((rows[0].longest_ride_meters - 333.585).abs() < 0.01);
+    assert_eq!((rows[1].trip_count, rows[1].moving_seconds), (1, 60));
+
+    // Without the morning ride Alice and Bob are tied, and listed by name
+    let sunday = TimeWindow::days(Utc.with_ymd_and_hms(2024, 10, 6, 0, 0, 0).unwrap(), 1);
+    let rows = dal.stats().person_leaderboard(sunday, vec![], RankBy::LongestRide).unwrap();
+    assert_eq!(ranking(&rows), vec![(1, "Alice"), (1, "Bob")]);
+
+    let bob = vec![PersonCondition::name(StringFilter::Equal("Bob".to_string()))];
+    let rows = dal.stats().person_leaderboard(october(), bob, RankBy::TripCount).unwrap();
+    assert_eq!(ranking(&rows), vec![(1, "Bob")]);
+
+    assert!(dal.stats().for_tenant("other club").person_leaderboard(october(), vec![], RankBy::Distance).unwrap().is_empty());
+}
+
+#[test]
+fn test_person_leaderboard_follows_transfers() {
+    let fixture = setup();
+    let dal = fixture.dal();
+    let person = |name: &str| dal.person().find_with_filters(vec![PersonCondition::name(StringFilter::Equal(name.to_string()))]).unwrap().remove(0);
+    let (alice, bob) = (person("Alice"), person("Bob"));
+    let mountain = dal
+        .bike()
+        .find_with_filters(vec![
+            BikeCondition::name(StringFilter::Equal("Mountain Bike".to_string())),
+            BikeCondition::owner_id(StringFilter::Equal(alice.id.clone())),
+        ])
+        .unwrap()
+        .remove(0);
+
+    // Bob took the Mountain Bike over between the morning ride and the paused rides
+    dal.bike().transfer(&mountain.id, &bob.id).unwrap();
+    let handover = "'2024-10-05T12:00:00Z'";
+    fixture.execute(&format!("UPDATE ownership SET valid_to = {} WHERE bike_id = '{}' AND valid_to IS NOT NULL", handover, mountain.id));
+    fixture.execute(&format!("UPDATE ownership SET valid_from = {} WHERE bike_id = '{}' AND valid_to IS NULL", handover, mountain.id));
+    dal.bike_trip().import_gpx(&mountain.id, gpx_fixture("paused_ride.gpx")).unwrap();
+
+    // The morning ride stays with Alice, the later ride on the Mountain Bike is Bob's
+    let rows = dal.stats().person_leaderboard(october(), vec![], RankBy::TripCount).unwrap();
+    assert_eq!(ranking(&rows), vec![(1, "Alice"), (1, "Bob")]);
+    assert_eq!((rows[0].trip_count, rows[1].trip_count), (2, 2));
+    assert!((rows[0].longest_ride_meters - 333.585).abs() < 0.01);
+    assert!((rows[1].longest_ride_meters - 222.390).abs() < 0.01);
+
+    // Rides from before a bike had an owner count for nobody
+    fixture.execute(&format!("UPDATE ownership SET valid_from = {} WHERE bike_id = '{}' AND valid_to IS NOT NULL", handover, mountain.id));
+    let rows = dal.stats().person_leaderboard(october(), vec![], RankBy::TripCount).unwrap();
+    assert_eq!((rows[0].name.as_str(), rows[0].trip_count), ("Bob", 2));
+    assert_eq!((rows[1].name.as_str(), rows[1].trip_count), ("Alice", 1));
+}
+
+#[test]
+fn test_bike_leaderboard() {
+    let fixture = setup();
+    let dal = fixture.dal();
+
+    let rows = dal.stats().bike_leaderboard(october(), vec![], RankBy::Distance).unwrap();
+    assert_eq!(ranking(&rows), vec![(1, "Mountain Bike"), (2, "City Bike"), (2, "Road Bike")]);
+    let rows = dal.stats().bike_leaderboard(october(), vec![], RankBy::TripCount).unwrap();
+    assert_eq!(ranking(&rows), vec![(1, "City Bike"), (1, "Mountain Bike"), (1, "Road Bike")]);
+
+    // The window ends before the paused rides start
+    let window = TimeWindow {
+        from: Utc.with_ymd_and_hms(2024, 10, 5, 8, 0, 0).unwrap(),
+        to: Utc.with_ymd_and_hms(2024, 10, 6, 12, 0, 0).unwrap(),
+    };
+    let rows = dal.stats().bike_leaderboard(window, vec![], RankBy::MovingTime).unwrap();
+    assert_eq!(ranking(&rows), vec![(1, "Mountain Bike")]);
+    assert_eq!(rows[0].moving_seconds, 90);
+
+    let blue = vec![BikeCondition::color(StringFilter::Equal("Blue".to_string()))];
+    let rows = dal.stats().bike_leaderboard(october(), blue, RankBy::MovingTime).unwrap();
+    assert_eq!(ranking(&rows), vec![(1, "City Bike"), (1, "Road Bike")]);
+}
@@ -1,4 +1,8 @@
+mod api;
+mod cli;
 mod dal;
+mod transfer;
 
 #[path ="../fixtures.rs"]
+#[allow(dead_code)]
 mod fixtures;
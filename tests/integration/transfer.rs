@@ -0,0 +1,138 @@
+use pedal_pal::models::bike::BikeCondition;
+use pedal_pal::models::bike_trip::BikeTripCondition;
+use pedal_pal::models::common::StringFilter;
+use pedal_pal::transfer::{self, FileFormat, ImportMode, RowError};
+use crate::fixtures::TestFixture;
+
+fn lines(report: &transfer::ImportReport) -> Vec<usize> {
+    report.failed.iter().map(|error| error.line).collect()
+}
+
+#[test]
+fn test_import_bikes_resolves_names() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+    let dal = fixture.dal();
+
+    let csv = "\
+name,owner,color
+Gravel Bike, Alice ,Red
+Tandem,,
+Fixie,Carol,Blue
+,Alice,Red
+Cargo Bike,Alice,Green
+";
+    let report = transfer::import_bikes(&dal, FileFormat::Csv, ImportMode::BestEffort, csv.as_bytes()).unwrap();
+    assert_eq!(report.imported, 2);
+    assert_eq!(
+        report.failed,
+        vec![
+            RowError { line: 4, message: "unknown owner `Carol`".to_string() },
+            RowError { line: 5, message: "name is required".to_string() },
+            RowError { line: 6, message: "unknown color `Green`".to_string() },
+        ]
+    );
+
+    let gravel = dal.bike().find_rows_with_filters(vec![BikeCondition::name(StringFilter::Equal("Gravel Bike".to_string()))]).unwrap();
+    assert_eq!(gravel[0].owner_name.as_deref(), Some("Alice"));
+    assert_eq!(gravel[0].color_name.as_deref(), Some("Red"));
+    let tandem = dal.bike().find_with_filters(vec![BikeCondition::name(StringFilter::Equal("Tandem".to_string()))]).unwrap();
+    assert_eq!((tandem[0].owner_id.as_deref(), tandem[0].color_id.as_deref()), (None, None));
+
+    // A name shared by two persons doesn't identify an owner
+    fixture.create_person("Alice");
+    let ndjson = r#"{"name": "Tourer", "owner": "Alice"}"#;
+    let report = transfer::import_bikes(&dal, FileFormat::Ndjson, ImportMode::BestEffort, ndjson.as_bytes()).unwrap();
+    assert_eq!(report.imported, 0);
+    assert_eq!(report.failed[0].message, "owner `Alice` is ambiguous, 2 rows have that name");
+}
+
+#[test]
+fn test_import_all_or_nothing() {
+    let fixture = TestFixture::new();
+    fixture.setup_bike_trips();
+    let dal = fixture.dal();
+
+    let ndjson = r#"{"name": "Commute", "bike": "Mountain Bike"}
+
+{"name": "Detour", "bike": "Unicycle"}
+{"name":
+"#;
+    let report = transfer::import_bike_trips(&dal, FileFormat::Ndjson, ImportMode::AllOrNothing, ndjson.as_bytes()).unwrap();
+    assert_eq!(report.imported, 0);
+    assert_eq!(lines(&report), vec![3, 4]);
+    assert!(!report.is_complete());
+    assert_eq!(dal.bike_trip().find_all().unwrap().len(), 5);
+
+    let ndjson = r#"{"name": "Commute", "bike": "Mountain Bike"}
+{"name": "Loop"}
+"#;
+    let report = transfer::import_bike_trips(&dal, FileFormat::Ndjson, ImportMode::AllOrNothing, ndjson.as_bytes()).unwrap();
+    assert_eq!(report.imported, 2);
+    assert!(report.is_complete());
+    assert_eq!(dal.bike_trip().find_all().unwrap().len(), 7);
+}
+
+#[test]
+fn test_import_persons_and_colors() {
+    let fixture = TestFixture::new();
+    fixture.create_color("Red");
+    let dal = fixture.dal();
+
+    let csv = "name,email\nCarol,carol@example.com\nDave,\nEve\n";
+    let report = transfer::import_persons(&dal, FileFormat::Csv, ImportMode::BestEffort, csv.as_bytes()).unwrap();
+    assert_eq!(report.imported, 2);
+    assert_eq!(lines(&report), vec![4]);
+    let mut persons = dal.person().find_all().unwrap();
+    persons.sort_by(|a, b| a.name.cmp(&b.name));
+    assert_eq!(persons[0].email.as_deref(), Some("carol@example.com"));
+    assert_eq!(persons[1].email, None);
+
+    let csv = "name\nGreen\nRed\nGreen\n";
+    let report = transfer::import_colors(&dal, FileFormat::Csv, ImportMode::BestEffort, csv.as_bytes()).unwrap();
+    assert_eq!(report.imported, 1);
+    assert_eq!(report.failed[0], RowError { line: 3, message: "color `Red` already exists".to_string() });
+    assert_eq!(report.failed[1], RowError { line: 4, message: "color `Green` already exists".to_string() });
+}
+
+#[test]
+fn test_export_with_filter_round_trips() {
+    let fixture = TestFixture::new();
+    fixture.setup_bikes();
+    let dal = fixture.dal();
+
+    let mut csv = Vec::new();
+    let exported = transfer::export_bikes(&dal, vec![BikeCondition::color(StringFilter::Equal("Blue".to_string()))], FileFormat::Csv, &mut csv).unwrap();
+    assert_eq!(exported, 2);
+    assert_eq!(String::from_utf8(csv.clone()).unwrap(), "name,owner,color\nCity Bike,Alice,Blue\nRoad Bike,Bob,Blue\n");
+
+    let report = transfer::import_bikes(&dal, FileFormat::Csv, ImportMode::AllOrNothing, csv.as_slice()).unwrap();
+    assert_eq!(report.imported, 2);
+    let bikes = dal.bike().find_rows_with_filters(vec![BikeCondition::name(StringFilter::Equal("Road Bike".to_string()))]).unwrap();
+    assert_eq!(bikes.len(), 2);
+    assert!(bikes.iter().all(|bike| bike.owner_name.as_deref() == Some("Bob")));
+
+    let mut ndjson = Vec::new();
+    let trips = vec![BikeTripCondition::name(StringFilter::Equal("Trip".to_string()))];
+    assert_eq!(transfer::export_bike_trips(&dal, trips, FileFormat::Ndjson, &mut ndjson).unwrap(), 0);
+    assert!(ndjson.is_empty());
+
+    let mut ndjson = Vec::new();
+    transfer::export_colors(&dal, FileFormat::Ndjson, &mut ndjson).unwrap();
+    assert_eq!(String::from_utf8(ndjson).unwrap(), "{\"name\":\"Blue\"}\n{\"name\":\"Red\"}\n");
+}
+
+#[test]
+fn test_export_bike_trips_names_bikes() {
+    let fixture = TestFixture::new();
+    fixture.setup_bike_trips();
+    let dal = fixture.dal();
+
+    let mut ndjson = Vec::new();
+    let trips = vec![BikeTripCondition::name(StringFilter::In(vec!["Trip 1".to_string(), "Trip 2".to_string()]))];
+    transfer::export_bike_trips(&dal, trips, FileFormat::Ndjson, &mut ndjson).unwrap();
+    assert_eq!(
+        String::from_utf8(ndjson).unwrap(),
+        "{\"name\":\"Trip 1\",\"bike\":\"Mountain Bike\"}\n{\"name\":\"Trip 2\",\"bike\":\"Mountain Bike\"}\n"
+    );
+}